@@ -0,0 +1,116 @@
+use amble::{
+    AmbleDB, CategoryBlock, DbBlockMatrix, DbCategoryBlock, DbIO, DbPropertyBlock,
+    DbRichTextBlock, DbTextBlock, Parser,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use rusqlite::Connection;
+use std::hint::black_box;
+
+const HEADLINES: usize = 10_000;
+
+/// Builds a document with `headlines` headlines, nested three levels deep,
+/// each with a property and a couple of lines of text
+fn generate_document(headlines: usize) -> String {
+    let mut document = String::new();
+
+    for headline in 0..headlines {
+        let stars = "*".repeat(headline % 3 + 1);
+        document += &format!("{} Headline {}\n:Effort: {}h\n", stars, headline, headline % 8);
+        document += "Some notes about this headline\nAnd a second line of notes\n";
+    }
+
+    document
+}
+
+fn create_db(document: &str) -> (AmbleDB, i64) {
+    let mut db = AmbleDB::new(":memory:").expect("Should be able to open db");
+
+    let category_id = db
+        .write_top_level_category(&CategoryBlock::new(
+            "Large category",
+            0,
+            Parser::new(document).parse(),
+        ))
+        .expect("Should be able to write category")
+        .category_id;
+
+    (db, category_id)
+}
+
+fn load_single_query(connection: &Connection, category_id: i64) -> usize {
+    let matrix = DbBlockMatrix::new(connection, category_id).expect("Should create matrix");
+    let flat_blocks = matrix
+        .produce_flat_db_block_vec()
+        .expect("Should produce db blocks");
+    let category = matrix
+        .form_category_block_tree(&flat_blocks)
+        .expect("Should form category tree");
+
+    category.children.len()
+}
+
+/// Selects every block with its own statement, the way categories were
+/// loaded before the single query loader
+fn load_select_per_block(connection: &Connection, category_id: i64) -> usize {
+    let mut stmt = connection
+        .prepare(
+            "
+            WITH RECURSIVE child_categories(id) AS (
+                SELECT id FROM category_blocks WHERE id = ?1
+                UNION ALL
+                SELECT cb.id FROM category_blocks cb
+                INNER JOIN child_categories cc ON cb.parent_category_id = cc.id
+            )
+            SELECT 0, id FROM child_categories
+            UNION ALL
+            SELECT 1, p.id FROM properties p
+            INNER JOIN child_categories cc ON p.parent_category_id = cc.id
+            UNION ALL
+            SELECT 2, rtb.id FROM rich_text_blocks rtb
+            INNER JOIN child_categories cc ON rtb.parent_category_id = cc.id
+            UNION ALL
+            SELECT 3, tb.id FROM text_blocks tb
+            INNER JOIN rich_text_blocks rtb ON tb.parent_rich_text_block_id = rtb.id
+            INNER JOIN child_categories cc ON rtb.parent_category_id = cc.id
+            ",
+        )
+        .expect("Should prepare id query");
+
+    let ids: Vec<(i64, i64)> = stmt
+        .query_map([category_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .expect("Should query ids")
+        .collect::<Result<_, _>>()
+        .expect("Should read ids");
+
+    for (table, id) in &ids {
+        match table {
+            0 => DbCategoryBlock::zero().select(connection, *id),
+            1 => DbPropertyBlock::zero().select(connection, *id),
+            2 => DbRichTextBlock::zero().select(connection, *id),
+            _ => DbTextBlock::zero().select(connection, *id),
+        }
+        .expect("Should select block");
+    }
+
+    ids.len()
+}
+
+fn load_category(c: &mut Criterion) {
+    let (db, category_id) = create_db(&generate_document(HEADLINES));
+
+    let mut group = c.benchmark_group(format!("load {} headline category", HEADLINES));
+    group.sample_size(10);
+
+    group.bench_function("single query", |b| {
+        b.iter(|| load_single_query(black_box(&db.connection), category_id))
+    });
+
+    group.bench_function("select per block", |b| {
+        b.iter(|| load_select_per_block(black_box(&db.connection), category_id))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, load_category);
+criterion_main!(benches);
@@ -1,9 +1,9 @@
-use amble::Parser;
-use criterion::{criterion_group, criterion_main, Criterion};
+use amble::{Parser, Tokenizer};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use std::{fs, hint::black_box};
 
 fn parse_file(file: &str) {
-    let parser = Parser::new(&file);
+    let parser = Parser::new(file);
     parser.parse();
 }
 
@@ -30,5 +30,46 @@ fn parsing(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, parsing);
+/// Builds a document of at least `size` bytes out of headlines, properties,
+/// long lines of prose, and long runs of text with no separators in them
+fn generate_large_document(size: usize) -> String {
+    let prose = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(200);
+    let unbroken = "長い段落".repeat(4096);
+
+    let mut document = String::with_capacity(size + prose.len() + unbroken.len());
+    let mut headline = 0;
+
+    while document.len() < size {
+        document += &format!("* Headline {}\n:Due: <2024-01-01>\n", headline);
+        document += &prose;
+        document += "\n";
+        document += &unbroken;
+        document += "\n";
+        headline += 1;
+    }
+
+    document
+}
+
+fn large_documents(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large generated document");
+    group.sample_size(10);
+
+    for size in [1 << 20, 4 << 20, 16 << 20] {
+        let document = generate_large_document(size);
+        group.throughput(Throughput::Bytes(document.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("tokenizing", size), &document, |b, document| {
+            b.iter(|| Tokenizer::new(black_box(document)).count())
+        });
+
+        group.bench_with_input(BenchmarkId::new("parsing", size), &document, |b, document| {
+            b.iter(|| parse_file(black_box(document)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parsing, large_documents);
 criterion_main!(benches);
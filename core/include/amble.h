@@ -0,0 +1,335 @@
+/* Generated by cbindgen from core/src/ffi, do not edit by hand */
+
+#ifndef AMBLE_H
+#define AMBLE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Returned by every fallible function exported over the C ABI. When a
+ * function does not return `AmbleStatus::Ok`, `amble_last_error` describes
+ * what went wrong and none of its out parameters have been written.
+ */
+typedef enum AmbleStatus {
+  AMBLE_STATUS_OK = 0,
+  /**
+   * The call failed, e.g. because of invalid input or a database error
+   */
+  AMBLE_STATUS_ERROR = 1,
+  /**
+   * Amble panicked while handling the call. The panic was caught before
+   * it could unwind into the caller
+   */
+  AMBLE_STATUS_PANIC = 2,
+} AmbleStatus;
+
+/**
+ * An open Amble database, handed to C callers as an opaque pointer
+ */
+typedef struct AmbleHandle AmbleHandle;
+
+/**
+ * A top level category passed across the C ABI. Categories written by
+ * `write_category` own their strings, which must be released with
+ * `amble_free_category`.
+ */
+typedef struct TopLevelCategory {
+  int64_t id;
+  const char *name;
+  const char *content;
+} TopLevelCategory;
+
+typedef struct TopLevelCategoryResult {
+  int64_t id;
+  const char *name;
+} TopLevelCategoryResult;
+
+/**
+ * The categories returned by `get_top_level_categories`. Must be released
+ * with `amble_free_category_results`.
+ */
+typedef struct TopLevelCategoryResults {
+  const struct TopLevelCategoryResult *categories;
+  size_t length;
+} TopLevelCategoryResults;
+
+typedef struct SearchResult {
+  /**
+   * Id of the top level category containing the match
+   */
+  int64_t category_id;
+  const char *category_name;
+  /**
+   * Headlines beneath the top level category leading to the match,
+   * separated by " > "
+   */
+  const char *path;
+  /**
+   * The matching text, with matched terms wrapped in `[` and `]`
+   */
+  const char *snippet;
+  /**
+   * Lower is a better match
+   */
+  double rank;
+} SearchResult;
+
+/**
+ * The results of `amble_search`, best match first. Must be released with
+ * `amble_free_search_results`.
+ */
+typedef struct SearchResults {
+  const struct SearchResult *results;
+  size_t length;
+} SearchResults;
+
+typedef struct AgendaResult {
+  /**
+   * Id of the top level category containing the headline
+   */
+  int64_t category_id;
+  const char *category_name;
+  /**
+   * Headlines beneath the top level category leading to the headline,
+   * separated by " > "
+   */
+  const char *path;
+  const char *headline;
+  /**
+   * What the date means for the headline, such as `DEADLINE`
+   */
+  const char *label;
+  /**
+   * The date as an org timestamp
+   */
+  const char *timestamp;
+  /**
+   * The day the headline is listed on, as `YYYY-MM-DD`
+   */
+  const char *date;
+  /**
+   * The headline is still open, though its date has passed
+   */
+  bool overdue;
+} AgendaResult;
+
+/**
+ * The results of `amble_agenda`, ordered by date. Must be released with
+ * `amble_free_agenda_results`.
+ */
+typedef struct AgendaResults {
+  const struct AgendaResult *results;
+  size_t length;
+} AgendaResults;
+
+typedef struct BacklinkResult {
+  /**
+   * Id of the top level category containing the link
+   */
+  int64_t category_id;
+  const char *category_name;
+  /**
+   * Headlines beneath the top level category leading to the link,
+   * separated by " > "
+   */
+  const char *path;
+  /**
+   * The text containing the link
+   */
+  const char *content;
+  /**
+   * Id of the category the link points at, which is either the category
+   * backlinks were asked for or a headline beneath it
+   */
+  int64_t target_category_id;
+} BacklinkResult;
+
+/**
+ * The results of `amble_backlinks`. Must be released with
+ * `amble_free_backlink_results`.
+ */
+typedef struct BacklinkResults {
+  const struct BacklinkResult *results;
+  size_t length;
+} BacklinkResults;
+
+/**
+ * Writes every headline scheduled, due or with a deadline between `start`
+ * and `end` inclusive, both `YYYY-MM-DD` dates, to `out`. When the range
+ * includes today, open items that are overdue are included too. `out` must
+ * be released with `amble_free_agenda_results`.
+ *
+ * # Safety
+ *
+ * `handle` must have been returned by `amble_open`, `start` and `end` must
+ * be valid nul terminated strings and `out` must be valid for writes.
+ */
+enum AmbleStatus amble_agenda(struct AmbleHandle *handle,
+                              const char *start,
+                              const char *end,
+                              struct AgendaResults *out);
+
+/**
+ * Writes every link pointing at the category with `id`, or at a headline
+ * beneath it, to `out`, which must be released with
+ * `amble_free_backlink_results`.
+ *
+ * # Safety
+ *
+ * `handle` must have been returned by `amble_open` and `out` must be valid
+ * for writes.
+ */
+enum AmbleStatus amble_backlinks(struct AmbleHandle *handle,
+                                 int64_t id,
+                                 struct BacklinkResults *out);
+
+/**
+ * Closes a database opened with `amble_open`.
+ *
+ * # Safety
+ *
+ * `handle` must have been returned by `amble_open`, and must not be used
+ * after this call.
+ */
+void amble_close(struct AmbleHandle *handle);
+
+/**
+ * Releases the results written by `amble_agenda`, along with their
+ * strings, and empties `results`.
+ *
+ * # Safety
+ *
+ * `results` must be null or point to results written by `amble_agenda`.
+ */
+void amble_free_agenda_results(struct AgendaResults *results);
+
+/**
+ * Releases the results written by `amble_backlinks`, along with their
+ * strings, and empties `results`.
+ *
+ * # Safety
+ *
+ * `results` must be null or point to results written by `amble_backlinks`.
+ */
+void amble_free_backlink_results(struct BacklinkResults *results);
+
+/**
+ * Releases the strings of a category written by `write_category` and sets
+ * them to null, so freeing the same category twice is harmless. The
+ * category itself belongs to the caller.
+ *
+ * # Safety
+ *
+ * `category` must be null or point to a category written by
+ * `write_category`.
+ */
+void amble_free_category(struct TopLevelCategory *category);
+
+/**
+ * Releases the categories written by `get_top_level_categories`, along with
+ * their names, and empties `results`.
+ *
+ * # Safety
+ *
+ * `results` must be null or point to results written by
+ * `get_top_level_categories`.
+ */
+void amble_free_category_results(struct TopLevelCategoryResults *results);
+
+/**
+ * Releases the results written by `amble_search`, along with their
+ * strings, and empties `results`.
+ *
+ * # Safety
+ *
+ * `results` must be null or point to results written by `amble_search`.
+ */
+void amble_free_search_results(struct SearchResults *results);
+
+/**
+ * Releases a string returned by Amble. Does nothing if `string` is null.
+ *
+ * # Safety
+ *
+ * `string` must be null or have been returned by Amble, and must not be
+ * used after this call. `amble_last_error` is owned by Amble and must not be
+ * passed here.
+ */
+void amble_free_string(char *string);
+
+/**
+ * Returns a description of the most recent failure on the calling thread,
+ * including the full chain of error context, or null if no call has
+ * failed yet. The string is owned by Amble and remains valid until the
+ * next failing call on the same thread.
+ */
+const char *amble_last_error(void);
+
+/**
+ * Opens the database at `path`, creating it if it does not exist, and
+ * writes the new handle to `out`. `path` may be absolute, relative to the
+ * current directory, or `:memory:`.
+ *
+ * # Safety
+ *
+ * `path` must be a valid nul terminated string and `out` must be valid for
+ * writes.
+ */
+enum AmbleStatus amble_open(const char *path, struct AmbleHandle **out);
+
+/**
+ * Searches the headlines and text of every note for the words in `query`
+ * and writes the matches to `out`, which must be released with
+ * `amble_free_search_results`.
+ *
+ * # Safety
+ *
+ * `handle` must have been returned by `amble_open`, `query` must be a valid
+ * nul terminated string and `out` must be valid for writes.
+ */
+enum AmbleStatus amble_search(struct AmbleHandle *handle,
+                              const char *query,
+                              struct SearchResults *out);
+
+/**
+ * Writes the content of the category with `id`, rendered as org, to `out`.
+ * The string must be released with `amble_free_string`.
+ *
+ * # Safety
+ *
+ * `handle` must have been returned by `amble_open` and `out` must be valid
+ * for writes.
+ */
+enum AmbleStatus get_category_content(struct AmbleHandle *handle, int64_t id, char **out);
+
+/**
+ * Writes the id and name of every top level category to `out`, which must
+ * be released with `amble_free_category_results`.
+ *
+ * # Safety
+ *
+ * `handle` must have been returned by `amble_open` and `out` must be valid
+ * for writes.
+ */
+enum AmbleStatus get_top_level_categories(struct AmbleHandle *handle,
+                                          struct TopLevelCategoryResults *out);
+
+/**
+ * Parses and saves a top level category, creating it when its id is not
+ * positive, then writes the category as stored back to `out`. The strings
+ * in `out` are owned by the caller and must be released with
+ * `amble_free_category`.
+ *
+ * # Safety
+ *
+ * `handle` must have been returned by `amble_open`, `category` must point
+ * to a valid `TopLevelCategory` whose strings are nul terminated, and
+ * `out` must be valid for writes.
+ */
+enum AmbleStatus write_category(struct AmbleHandle *handle,
+                                const struct TopLevelCategory *category,
+                                struct TopLevelCategory *out);
+
+#endif /* AMBLE_H */
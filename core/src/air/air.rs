@@ -1,7 +1,17 @@
+use std::borrow::Cow;
+
 use anyhow::{anyhow, Context};
 use rusqlite::Transaction;
 
-use super::db_io::{DbCategoryBlock, DbIO, DbRichTextBlock, DbTextBlock};
+use super::db_io::{
+    DbCategoryBlock, DbDrawerBlock, DbIO, DbListBlock, DbListItemBlock, DbPropertyBlock,
+    DbRichTextBlock, DbKeywordBlock, DbTableBlock, DbTableCellBlock, DbTableRowBlock,
+    DbTextBlock, DbVerbatimBlock,
+};
+use super::headline::Headline;
+use super::link::Link;
+use super::markup::{split_markup, Markup};
+use super::timestamp::Planning;
 
 /**
 * This file contains the specification of the Amble Intermediate Representation,
@@ -14,6 +24,13 @@ pub enum Block<'a> {
     Category(CategoryBlock<'a>),
     RichText(RichTextBlock<'a>),
     Text(TextBlock<'a>),
+    Property(PropertyBlock<'a>),
+    Drawer(DrawerBlock<'a>),
+    List(ListBlock<'a>),
+    ListItem(ListItemBlock<'a>),
+    Table(TableBlock<'a>),
+    Verbatim(VerbatimBlock<'a>),
+    Keyword(KeywordBlock<'a>),
 }
 
 // -----------------------------------------------------------
@@ -22,7 +39,11 @@ pub enum Block<'a> {
 #[derive(Debug)]
 pub struct CategoryBlock<'a> {
     pub id: Option<i64>,
+    /// The full text of the headline, which is what the category is
+    /// rendered from
     pub name: &'a str,
+    /// The parts of `name`
+    pub headline: Headline<'a>,
     pub level: usize,
     pub children: Vec<Block<'a>>,
 }
@@ -32,6 +53,7 @@ impl<'a> Clone for CategoryBlock<'a> {
         CategoryBlock {
             id: self.id,
             name: self.name,
+            headline: self.headline.clone(),
             level: self.level,
             children: Vec::new(),
         }
@@ -39,44 +61,119 @@ impl<'a> Clone for CategoryBlock<'a> {
 }
 
 impl<'a> CategoryBlock<'a> {
-    fn as_db_type(&self, parent_category_id: Option<i64>) -> DbCategoryBlock {
+    /// Creates a category that has not been stored, splitting its name
+    /// into the parts of its headline
+    pub fn new(name: &'a str, level: usize, children: Vec<Block<'a>>) -> Self {
+        CategoryBlock {
+            id: None,
+            name,
+            headline: Headline::parse(name),
+            level,
+            children,
+        }
+    }
+
+    /// Parses the planning line of the category, which must be the first
+    /// line of text under its headline
+    pub fn planning(&self) -> Option<Planning> {
+        let line = match self.children.first()? {
+            Block::RichText(rich_text_block) => rich_text_block.lines().into_iter().next()?.content,
+            Block::Text(text_block) => Cow::Borrowed(text_block.content),
+            _ => return None,
+        };
+
+        Planning::parse(line.lines().next()?)
+    }
+
+    /// Returns the value of the first `#+KEY:` line directly inside the
+    /// category, such as the `TITLE` of a document. Keys are compared case
+    /// insensitively.
+    pub fn keyword(&self, key: &str) -> Option<&'a str> {
+        self.children.iter().find_map(|child| match child {
+            Block::Keyword(keyword) if keyword.key.eq_ignore_ascii_case(key) => Some(keyword.value),
+            _ => None,
+        })
+    }
+
+    /// Returns the properties directly inside the category, whether they
+    /// are in a drawer or not, in document order
+    pub fn properties(&self) -> impl Iterator<Item = &PropertyBlock<'a>> {
+        self.children.iter().flat_map(|child| match child {
+            Block::Property(property) => std::slice::from_ref(property),
+            Block::Drawer(drawer) => drawer.properties.as_slice(),
+            _ => &[],
+        })
+    }
+
+    fn as_db_type(&self, parent_category_id: Option<i64>, position: i64) -> DbCategoryBlock {
         DbCategoryBlock {
             id: self.id,
             name: self.name.to_string(),
             parent_category_id,
+            position,
         }
     }
 
     pub fn from_db_type(db_block: &'a DbCategoryBlock, level: usize) -> Self {
         CategoryBlock {
-            id: db_block.id.clone(),
+            id: db_block.id,
             name: &db_block.name,
+            headline: Headline::parse(&db_block.name),
             level,
             children: Vec::new(),
         }
     }
 
+    /// Writes the category and all of its children. Each child is stored
+    /// with its index in `children` as its position, which is what the
+    /// category is read back in order by.
     pub fn write_to_db(
         &self,
         tx: &Transaction,
         parent_category_id: Option<i64>,
+        position: i64,
     ) -> Result<i64, anyhow::Error> {
         let db_id = self
-            .as_db_type(parent_category_id)
+            .as_db_type(parent_category_id, position)
             .write(tx)
             .context("Could not perform write of category block")?;
 
-        for child in &self.children {
+        for (index, child) in self.children.iter().enumerate() {
+            let position = index as i64;
+
             match child {
                 Block::Category(cat) => cat
-                    .write_to_db(tx, Some(db_id))
+                    .write_to_db(tx, Some(db_id), position)
                     .context("Could not write category")?,
                 Block::RichText(rt) => rt
-                    .write_to_db(tx, None, Some(db_id))
+                    .write_to_db(tx, None, Some(db_id), position)
                     .context("Could not write rich text block")?,
                 Block::Text(t) => t
-                    .write_to_db(tx, None, Some(db_id), None)
+                    .write_to_db(tx, None, Some(db_id), None, position)
                     .context("Could not write text block")?,
+                Block::Property(p) => p
+                    .write_to_db(tx, Some(db_id), None, position)
+                    .context("Could not write property block")?,
+                Block::Drawer(drawer) => drawer
+                    .write_to_db(tx, Some(db_id), position)
+                    .context("Could not write drawer")?,
+                Block::List(list) => list
+                    .write_to_db(tx, Some(db_id), None, position)
+                    .context("Could not write list")?,
+                Block::ListItem(_) => {
+                    return Err(anyhow!("Cannot have a list item child of a category"))
+                }
+                Block::Table(table) => table
+                    .write_to_db(tx, Some(db_id), position)
+                    .context("Could not write table")?,
+                Block::Verbatim(verbatim) => verbatim
+                    .as_db_type(Some(db_id), position)
+                    .write(tx)
+                    .context("Could not write verbatim block")?,
+                Block::Keyword(keyword) => keyword
+                    .as_db_type(Some(db_id), position)
+                    .write(tx)
+                    .context("Could not write keyword")?,
             };
         }
 
@@ -86,11 +183,21 @@ impl<'a> CategoryBlock<'a> {
 
 // -----------------------------------------------------------
 
+/// Text made of spans, which are the text blocks among its children. A
+/// span ending in a line break ends a line.
 #[derive(Debug)]
 pub struct RichTextBlock<'a> {
     pub children: Vec<Block<'a>>,
 }
 
+/// A line of a rich text block, joined from its spans
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine<'a> {
+    /// Id of the text block the line is stored as
+    pub id: Option<i64>,
+    pub content: Cow<'a, str>,
+}
+
 impl<'a> Clone for RichTextBlock<'a> {
     fn clone(&self) -> Self {
         RichTextBlock {
@@ -100,10 +207,16 @@ impl<'a> Clone for RichTextBlock<'a> {
 }
 
 impl<'a> RichTextBlock<'a> {
-    fn as_db_type(&self, id: Option<i64>, parent_category_id: Option<i64>) -> DbRichTextBlock {
+    fn as_db_type(
+        &self,
+        id: Option<i64>,
+        parent_category_id: Option<i64>,
+        position: i64,
+    ) -> DbRichTextBlock {
         DbRichTextBlock {
             id,
             parent_category_id,
+            position,
         }
     }
 
@@ -113,13 +226,39 @@ impl<'a> RichTextBlock<'a> {
         }
     }
 
+    /// Joins the spans of the block into lines. Lines are what is stored,
+    /// searched and matched against, so formatting never splits a line.
+    pub fn lines(&self) -> Vec<TextLine<'a>> {
+        let mut lines: Vec<TextLine<'a>> = Vec::new();
+        let mut line_ended = true;
+
+        for child in &self.children {
+            let Block::Text(text) = child else {
+                continue;
+            };
+
+            match lines.last_mut() {
+                Some(line) if !line_ended => line.content.to_mut().push_str(text.content),
+                _ => lines.push(TextLine {
+                    id: text.id,
+                    content: Cow::Borrowed(text.content),
+                }),
+            }
+
+            line_ended = text.content.ends_with('\n');
+        }
+
+        lines
+    }
+
     pub fn write_to_db(
         &self,
         tx: &Transaction,
         id: Option<i64>,
         parent_category_id: Option<i64>,
+        position: i64,
     ) -> Result<i64, anyhow::Error> {
-        let db_id = self.as_db_type(id, parent_category_id).write(tx)?;
+        let db_id = self.as_db_type(id, parent_category_id, position).write(tx)?;
 
         for child in &self.children {
             match child {
@@ -131,15 +270,35 @@ impl<'a> RichTextBlock<'a> {
                         "Cannot have a rich text block child of a rich text block",
                     ))
                 }
-                Block::Text(t) => {
-                    t.write_to_db(tx, None, None, Some(db_id))
-                        .context(
-                            format!(
-                                "Could not write text block to database as child of rich text block with id {}",
-                                db_id))?;
-                    ()
+                Block::Property(_) | Block::Drawer(_) => {
+                    return Err(anyhow!("Cannot have a property child of a rich text block"))
+                }
+                Block::List(_) | Block::ListItem(_) => {
+                    return Err(anyhow!("Cannot have a list child of a rich text block"))
+                }
+                Block::Table(_) => {
+                    return Err(anyhow!("Cannot have a table child of a rich text block"))
                 }
+                Block::Verbatim(_) | Block::Keyword(_) => {
+                    return Err(anyhow!("Cannot have a verbatim child of a rich text block"))
+                }
+                Block::Text(_) => (),
+            }
+        }
+
+        for (index, line) in self.lines().into_iter().enumerate() {
+            DbTextBlock {
+                id: line.id,
+                content: line.content.into_owned(),
+                parent_category_id: None,
+                parent_rich_text_block_id: Some(db_id),
+                position: index as i64,
             }
+            .write(tx)
+            .context(format!(
+                "Could not write text block to database as child of rich text block with id {}",
+                db_id
+            ))?;
         }
 
         Ok(db_id)
@@ -150,13 +309,20 @@ impl<'a> RichTextBlock<'a> {
 
 #[derive(Debug)]
 pub struct TextBlock<'a> {
-    pub content: &'a str ,
+    /// Id of the stored line the text is part of
+    pub id: Option<i64>,
+    /// The text as written, including any markers around it
+    pub content: &'a str,
+    /// How the text is formatted, if at all
+    pub markup: Option<Markup>,
 }
 
 impl<'a> Clone for TextBlock<'a> {
     fn clone(&self) -> Self {
         TextBlock {
+            id: self.id,
             content: self.content,
+            markup: self.markup,
         }
     }
 }
@@ -167,18 +333,52 @@ impl<'a> TextBlock<'a> {
         id: Option<i64>,
         parent_category_id: Option<i64>,
         parent_rich_text_block_id: Option<i64>,
+        position: i64,
     ) -> DbTextBlock {
         DbTextBlock {
             id,
             content: self.content.to_string(),
             parent_category_id,
             parent_rich_text_block_id,
+            position,
+        }
+    }
+
+    /// Splits a line of text into its spans
+    pub fn spans(id: Option<i64>, line: &'a str) -> Vec<TextBlock<'a>> {
+        split_markup(line)
+            .into_iter()
+            .map(|(markup, content)| TextBlock { id, content, markup })
+            .collect()
+    }
+
+    /// The text without the markers around it. Links without a description
+    /// give their target as written.
+    pub fn text(&self) -> &'a str {
+        match self.markup {
+            Some(Markup::Link) => self.link().map_or(self.content, |link| {
+                link.description.unwrap_or(&self.content[2..self.content.len() - 2])
+            }),
+            Some(markup) if markup.marker().is_some() => {
+                &self.content[1..self.content.len() - 1]
+            }
+            _ => self.content,
+        }
+    }
+
+    /// The link the text is, if it is one
+    pub fn link(&self) -> Option<Link<'a>> {
+        match self.markup {
+            Some(Markup::Link) => Link::parse(self.content),
+            _ => None,
         }
     }
 
     pub fn from_db_type(db_block: &'a DbTextBlock) -> Self {
         TextBlock {
+            id: db_block.id,
             content: &db_block.content,
+            markup: None,
         }
     }
 
@@ -188,9 +388,10 @@ impl<'a> TextBlock<'a> {
         id: Option<i64>,
         parent_category_id: Option<i64>,
         parent_rich_text_block_id: Option<i64>,
+        position: i64,
     ) -> Result<i64, anyhow::Error> {
         let db_id = self
-            .as_db_type(id, parent_category_id, parent_rich_text_block_id)
+            .as_db_type(id, parent_category_id, parent_rich_text_block_id, position)
             .write(tx)
             .context("Could not write text block into database")?;
 
@@ -199,3 +400,655 @@ impl<'a> TextBlock<'a> {
 }
 
 // -----------------------------------------------------------
+
+#[derive(Debug)]
+pub struct PropertyBlock<'a> {
+    pub id: Option<i64>,
+    pub key: &'a str,
+    pub value: &'a str,
+    /// The whitespace before the first colon
+    pub indent: &'a str,
+    /// The whitespace between the second colon and the value, which aligns
+    /// the values of a drawer
+    pub separator: &'a str,
+    /// The whitespace after the value
+    pub trailing: &'a str,
+}
+
+impl<'a> Clone for PropertyBlock<'a> {
+    fn clone(&self) -> Self {
+        PropertyBlock {
+            id: self.id,
+            key: self.key,
+            value: self.value,
+            indent: self.indent,
+            separator: self.separator,
+            trailing: self.trailing,
+        }
+    }
+}
+
+impl<'a> PropertyBlock<'a> {
+    /// Creates a property that has not been stored, written without
+    /// indentation and with a single space before its value
+    pub fn new(key: &'a str, value: &'a str) -> Self {
+        PropertyBlock {
+            id: None,
+            key,
+            value,
+            indent: "",
+            separator: if value.is_empty() { "" } else { " " },
+            trailing: "",
+        }
+    }
+
+    fn as_db_type(
+        &self,
+        parent_category_id: Option<i64>,
+        parent_drawer_id: Option<i64>,
+        position: i64,
+    ) -> DbPropertyBlock {
+        DbPropertyBlock {
+            id: self.id,
+            key: self.key.to_string(),
+            value: self.value.to_string(),
+            indent: self.indent.to_string(),
+            separator: self.separator.to_string(),
+            trailing: self.trailing.to_string(),
+            parent_category_id,
+            parent_drawer_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &'a DbPropertyBlock) -> Self {
+        PropertyBlock {
+            id: db_block.id,
+            key: &db_block.key,
+            value: &db_block.value,
+            indent: &db_block.indent,
+            separator: &db_block.separator,
+            trailing: &db_block.trailing,
+        }
+    }
+
+    pub fn write_to_db(
+        &self,
+        tx: &Transaction,
+        parent_category_id: Option<i64>,
+        parent_drawer_id: Option<i64>,
+        position: i64,
+    ) -> Result<i64, anyhow::Error> {
+        let db_id = self
+            .as_db_type(parent_category_id, parent_drawer_id, position)
+            .write(tx)
+            .context("Could not write property block into database")?;
+
+        Ok(db_id)
+    }
+}
+
+// -----------------------------------------------------------
+
+/// A `:PROPERTIES:` drawer and the properties inside it. The lines opening
+/// and closing it are kept as written.
+#[derive(Debug, Clone)]
+pub struct DrawerBlock<'a> {
+    pub id: Option<i64>,
+    /// The `:PROPERTIES:` line, without its line break
+    pub begin: &'a str,
+    /// The `:END:` line, without its line break, or None if the drawer was
+    /// never closed
+    pub end: Option<&'a str>,
+    pub properties: Vec<PropertyBlock<'a>>,
+}
+
+impl<'a> DrawerBlock<'a> {
+    /// Creates a drawer that has not been stored, written without
+    /// indentation
+    pub fn new(properties: Vec<PropertyBlock<'a>>) -> Self {
+        DrawerBlock {
+            id: None,
+            begin: ":PROPERTIES:",
+            end: Some(":END:"),
+            properties,
+        }
+    }
+
+    fn as_db_type(&self, parent_category_id: Option<i64>, position: i64) -> DbDrawerBlock {
+        DbDrawerBlock {
+            id: self.id,
+            begin: self.begin.to_string(),
+            end: self.end.map(str::to_string),
+            parent_category_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &'a DbDrawerBlock) -> Self {
+        DrawerBlock {
+            id: db_block.id,
+            begin: &db_block.begin,
+            end: db_block.end.as_deref(),
+            properties: Vec::new(),
+        }
+    }
+
+    /// Writes the drawer along with its properties
+    pub fn write_to_db(
+        &self,
+        tx: &Transaction,
+        parent_category_id: Option<i64>,
+        position: i64,
+    ) -> Result<i64, anyhow::Error> {
+        let db_id = self
+            .as_db_type(parent_category_id, position)
+            .write(tx)
+            .context("Could not write drawer into database")?;
+
+        for (index, property) in self.properties.iter().enumerate() {
+            property
+                .write_to_db(tx, None, Some(db_id), index as i64)
+                .context(format!("Could not write property of drawer with id {}", db_id))?;
+        }
+
+        Ok(db_id)
+    }
+}
+
+// -----------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKind {
+    /// Items start with `-`, `+` or an indented `*`
+    Unordered,
+    /// Items start with a number followed by `.` or `)`
+    Ordered,
+}
+
+/// The state of a checkbox at the start of a list item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checkbox {
+    /// `[ ]`
+    Unchecked,
+    /// `[X]`
+    Checked,
+    /// `[-]`, for items whose children are partly checked
+    Partial,
+}
+
+impl Checkbox {
+    pub fn parse(text: &str) -> Option<Checkbox> {
+        match text {
+            "[ ]" => Some(Checkbox::Unchecked),
+            "[X]" | "[x]" => Some(Checkbox::Checked),
+            "[-]" => Some(Checkbox::Partial),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Checkbox::Unchecked => "[ ]",
+            Checkbox::Checked => "[X]",
+            Checkbox::Partial => "[-]",
+        }
+    }
+}
+
+// -----------------------------------------------------------
+
+/// A plain or numbered list. Its children are list items.
+#[derive(Debug)]
+pub struct ListBlock<'a> {
+    pub id: Option<i64>,
+    pub kind: ListKind,
+    pub children: Vec<Block<'a>>,
+}
+
+impl<'a> Clone for ListBlock<'a> {
+    fn clone(&self) -> Self {
+        ListBlock {
+            id: self.id,
+            kind: self.kind,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl<'a> ListBlock<'a> {
+    fn as_db_type(
+        &self,
+        parent_category_id: Option<i64>,
+        parent_list_item_id: Option<i64>,
+        position: i64,
+    ) -> DbListBlock {
+        DbListBlock {
+            id: self.id,
+            ordered: self.kind == ListKind::Ordered,
+            parent_category_id,
+            parent_list_item_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &DbListBlock) -> Self {
+        ListBlock {
+            id: db_block.id,
+            kind: if db_block.ordered {
+                ListKind::Ordered
+            } else {
+                ListKind::Unordered
+            },
+            children: Vec::new(),
+        }
+    }
+
+    /// Writes the list and its items. A list belongs to either a category
+    /// or, when nested, the list item it is nested in.
+    pub fn write_to_db(
+        &self,
+        tx: &Transaction,
+        parent_category_id: Option<i64>,
+        parent_list_item_id: Option<i64>,
+        position: i64,
+    ) -> Result<i64, anyhow::Error> {
+        let db_id = self
+            .as_db_type(parent_category_id, parent_list_item_id, position)
+            .write(tx)
+            .context("Could not write list into database")?;
+
+        for (index, child) in self.children.iter().enumerate() {
+            let Block::ListItem(item) = child else {
+                return Err(anyhow!("Lists can only contain list items"));
+            };
+
+            item.write_to_db(tx, db_id, index as i64)
+                .context(format!("Could not write item of list with id {}", db_id))?;
+        }
+
+        Ok(db_id)
+    }
+}
+
+// -----------------------------------------------------------
+
+/// An item of a list. Its children are the lists nested beneath it.
+#[derive(Debug)]
+pub struct ListItemBlock<'a> {
+    pub id: Option<i64>,
+    /// Everything before the checkbox or content, including indentation
+    /// and the space after the bullet, such as `  - ` or `1. `
+    pub bullet: &'a str,
+    /// The checkbox as written, such as `[ ]` or `[x]`
+    pub checkbox: Option<&'a str>,
+    /// The text of the item, after its bullet and checkbox
+    pub content: &'a str,
+    pub children: Vec<Block<'a>>,
+}
+
+impl<'a> Clone for ListItemBlock<'a> {
+    fn clone(&self) -> Self {
+        ListItemBlock {
+            id: self.id,
+            bullet: self.bullet,
+            checkbox: self.checkbox,
+            content: self.content,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl<'a> ListItemBlock<'a> {
+    /// The state of the item's checkbox, if it has one
+    pub fn checkbox_state(&self) -> Option<Checkbox> {
+        self.checkbox.and_then(Checkbox::parse)
+    }
+
+    fn as_db_type(&self, parent_list_id: Option<i64>, position: i64) -> DbListItemBlock {
+        DbListItemBlock {
+            id: self.id,
+            bullet: self.bullet.to_string(),
+            checkbox: self.checkbox.map(str::to_string),
+            content: self.content.to_string(),
+            parent_list_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &'a DbListItemBlock) -> Self {
+        ListItemBlock {
+            id: db_block.id,
+            bullet: &db_block.bullet,
+            checkbox: db_block.checkbox.as_deref(),
+            content: &db_block.content,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn write_to_db(
+        &self,
+        tx: &Transaction,
+        parent_list_id: i64,
+        position: i64,
+    ) -> Result<i64, anyhow::Error> {
+        let db_id = self
+            .as_db_type(Some(parent_list_id), position)
+            .write(tx)
+            .context("Could not write list item into database")?;
+
+        for (index, child) in self.children.iter().enumerate() {
+            let Block::List(list) = child else {
+                return Err(anyhow!("List items can only contain lists"));
+            };
+
+            list.write_to_db(tx, None, Some(db_id), index as i64)
+                .context(format!("Could not write list nested in item with id {}", db_id))?;
+        }
+
+        Ok(db_id)
+    }
+}
+
+// -----------------------------------------------------------
+
+/// A table of rows, such as `| a | b |`, and rules separating them
+#[derive(Debug, Clone)]
+pub struct TableBlock<'a> {
+    pub id: Option<i64>,
+    pub rows: Vec<TableRow<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableRow<'a> {
+    pub id: Option<i64>,
+    /// The whitespace before the first bar of the row
+    pub indent: &'a str,
+    /// Whether the row is a rule, like `|---+---|`. Rules have no cells.
+    pub rule: bool,
+    pub cells: Vec<TableCell<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableCell<'a> {
+    pub id: Option<i64>,
+    /// The text of the cell, without the whitespace around it
+    pub content: &'a str,
+}
+
+impl<'a> TableBlock<'a> {
+    /// Returns the index of the first rule, if rows above it name the
+    /// columns of rows below it
+    fn header_end(&self) -> Option<usize> {
+        let rule = self.rows.iter().position(|row| row.rule)?;
+        let has_body = self.rows[rule..].iter().any(|row| !row.rule);
+
+        (rule > 0 && has_body).then_some(rule)
+    }
+
+    /// The row naming the columns: the first row, when a rule separates it
+    /// from rows below
+    pub fn header(&self) -> Option<&TableRow<'a>> {
+        self.header_end().map(|_| &self.rows[0])
+    }
+
+    /// The rows holding values, which are every row below the header that
+    /// is not a rule
+    pub fn body(&self) -> impl Iterator<Item = &TableRow<'a>> {
+        let start = self.header_end().unwrap_or(0);
+        self.rows[start..].iter().filter(|row| !row.rule)
+    }
+
+    /// The number of columns of the widest row
+    pub fn columns(&self) -> usize {
+        self.rows.iter().map(|row| row.cells.len()).max().unwrap_or(0)
+    }
+
+    fn as_db_type(&self, parent_category_id: Option<i64>, position: i64) -> DbTableBlock {
+        DbTableBlock {
+            id: self.id,
+            parent_category_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &DbTableBlock) -> Self {
+        TableBlock {
+            id: db_block.id,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Writes the table along with its rows and their cells
+    pub fn write_to_db(
+        &self,
+        tx: &Transaction,
+        parent_category_id: Option<i64>,
+        position: i64,
+    ) -> Result<i64, anyhow::Error> {
+        let db_id = self
+            .as_db_type(parent_category_id, position)
+            .write(tx)
+            .context("Could not write table into database")?;
+
+        for (row_index, row) in self.rows.iter().enumerate() {
+            let row_id = row
+                .as_db_type(Some(db_id), row_index as i64)
+                .write(tx)
+                .context(format!("Could not write row of table with id {}", db_id))?;
+
+            for (cell_index, cell) in row.cells.iter().enumerate() {
+                cell.as_db_type(Some(row_id), cell_index as i64)
+                    .write(tx)
+                    .context(format!("Could not write cell of table row with id {}", row_id))?;
+            }
+        }
+
+        Ok(db_id)
+    }
+}
+
+impl<'a> TableRow<'a> {
+    fn as_db_type(&self, parent_table_id: Option<i64>, position: i64) -> DbTableRowBlock {
+        DbTableRowBlock {
+            id: self.id,
+            indent: self.indent.to_string(),
+            rule: self.rule,
+            parent_table_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &'a DbTableRowBlock) -> Self {
+        TableRow {
+            id: db_block.id,
+            indent: &db_block.indent,
+            rule: db_block.rule,
+            cells: Vec::new(),
+        }
+    }
+}
+
+impl<'a> TableCell<'a> {
+    fn as_db_type(&self, parent_table_row_id: Option<i64>, position: i64) -> DbTableCellBlock {
+        DbTableCellBlock {
+            id: self.id,
+            content: self.content.to_string(),
+            parent_table_row_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &'a DbTableCellBlock) -> Self {
+        TableCell {
+            id: db_block.id,
+            content: &db_block.content,
+        }
+    }
+}
+
+// -----------------------------------------------------------
+
+/// A block such as `#+BEGIN_SRC rust` ... `#+END_SRC`, whose lines are kept
+/// exactly as written and never parsed
+#[derive(Debug, Clone)]
+pub struct VerbatimBlock<'a> {
+    pub id: Option<i64>,
+    /// The `#+BEGIN_` line, without its line break
+    pub begin: &'a str,
+    /// Every line between the begin and end lines, with their line breaks
+    pub content: &'a str,
+    /// The `#+END_` line, without its line break
+    pub end: &'a str,
+    /// Whether the end line is followed by a line break, which only the
+    /// last line of a document may not be
+    pub end_line_break: bool,
+}
+
+impl<'a> VerbatimBlock<'a> {
+    /// Returns the kind of block a `#+BEGIN_KIND ...` line opens, such as
+    /// `SRC`, as it was written
+    pub fn begin_kind(line: &str) -> Option<&str> {
+        let rest = line.trim_start().strip_prefix("#+")?;
+        let begin = rest.get(..6).filter(|begin| begin.eq_ignore_ascii_case("BEGIN_"))?;
+        let kind = rest[begin.len()..].split_whitespace().next()?;
+
+        Some(kind)
+    }
+
+    /// Determines if `line` closes a block of `kind`
+    pub fn is_end(line: &str, kind: &str) -> bool {
+        line.trim()
+            .strip_prefix("#+")
+            .and_then(|rest| rest.get(..4).zip(rest.get(4..)))
+            .is_some_and(|(end, rest)| {
+                end.eq_ignore_ascii_case("END_") && rest.eq_ignore_ascii_case(kind)
+            })
+    }
+
+    /// The kind of block, such as `SRC` or `EXAMPLE`, as it was written
+    pub fn kind(&self) -> &'a str {
+        VerbatimBlock::begin_kind(self.begin).unwrap_or_default()
+    }
+
+    /// The text after the kind on the begin line
+    fn arguments(&self) -> &'a str {
+        let begin = self.begin.trim_start();
+        let kind_end = "#+BEGIN_".len() + self.kind().len();
+
+        begin.get(kind_end..).unwrap_or_default().trim()
+    }
+
+    /// The language of a source block, which is the first word after `SRC`
+    pub fn language(&self) -> Option<&'a str> {
+        if !self.kind().eq_ignore_ascii_case("SRC") {
+            return None;
+        }
+
+        self.arguments()
+            .split_whitespace()
+            .next()
+            .filter(|word| !word.starts_with([':', '-', '+']))
+    }
+
+    /// The switches and header arguments on the begin line, after the
+    /// language of a source block
+    pub fn parameters(&self) -> &'a str {
+        let arguments = self.arguments();
+
+        match self.language() {
+            Some(language) => arguments[language.len()..].trim_start(),
+            None => arguments,
+        }
+    }
+
+    fn as_db_type(&self, parent_category_id: Option<i64>, position: i64) -> DbVerbatimBlock {
+        DbVerbatimBlock {
+            id: self.id,
+            begin: self.begin.to_string(),
+            content: self.content.to_string(),
+            end: self.end.to_string(),
+            end_line_break: self.end_line_break,
+            parent_category_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &'a DbVerbatimBlock) -> Self {
+        VerbatimBlock {
+            id: db_block.id,
+            begin: &db_block.begin,
+            content: &db_block.content,
+            end: &db_block.end,
+            end_line_break: db_block.end_line_break,
+        }
+    }
+}
+
+// -----------------------------------------------------------
+
+/// A `#+KEY: value` line, such as `#+TITLE: Notes`
+#[derive(Debug, Clone)]
+pub struct KeywordBlock<'a> {
+    pub id: Option<i64>,
+    pub key: &'a str,
+    pub value: &'a str,
+    /// The whitespace before `#+`
+    pub indent: &'a str,
+    /// The whitespace between the colon and the value
+    pub separator: &'a str,
+    /// The whitespace after the value
+    pub trailing: &'a str,
+}
+
+impl<'a> KeywordBlock<'a> {
+    /// Parses a keyword line. Lines opening or closing a block are not
+    /// keywords.
+    pub fn parse(line: &'a str) -> Option<KeywordBlock<'a>> {
+        let rest = line.trim_start().strip_prefix("#+")?;
+        let (key, raw_value) = rest.split_once(':')?;
+
+        let is_block_line = VerbatimBlock::begin_kind(line).is_some()
+            || key.get(..4).is_some_and(|end| end.eq_ignore_ascii_case("END_"));
+
+        if key.is_empty() || key.contains(char::is_whitespace) || is_block_line {
+            return None;
+        }
+
+        let separator = &raw_value[..raw_value.len() - raw_value.trim_start().len()];
+        let value = raw_value[separator.len()..].trim_end();
+
+        Some(KeywordBlock {
+            id: None,
+            key,
+            value,
+            indent: &line[..line.len() - line.trim_start().len()],
+            separator,
+            trailing: &raw_value[separator.len() + value.len()..],
+        })
+    }
+
+    fn as_db_type(&self, parent_category_id: Option<i64>, position: i64) -> DbKeywordBlock {
+        DbKeywordBlock {
+            id: self.id,
+            key: self.key.to_string(),
+            value: self.value.to_string(),
+            indent: self.indent.to_string(),
+            separator: self.separator.to_string(),
+            trailing: self.trailing.to_string(),
+            parent_category_id,
+            position,
+        }
+    }
+
+    pub fn from_db_type(db_block: &'a DbKeywordBlock) -> Self {
+        KeywordBlock {
+            id: db_block.id,
+            key: &db_block.key,
+            value: &db_block.value,
+            indent: &db_block.indent,
+            separator: &db_block.separator,
+            trailing: &db_block.trailing,
+        }
+    }
+}
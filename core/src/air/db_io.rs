@@ -1,17 +1,32 @@
 use rusqlite::{Transaction, Connection};
 use anyhow::{anyhow, Context};
 
-#[derive(Debug)]
+use super::air::VerbatimBlock;
+use super::headline::Headline;
+use super::link::{find_links, LinkTarget};
+use super::timestamp::{find_timestamps, planning_keyword, Timestamp};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum DbBlock {
     Category(DbCategoryBlock),
     RichText(DbRichTextBlock),
     Text(DbTextBlock),
+    Property(DbPropertyBlock),
+    Drawer(DbDrawerBlock),
+    List(DbListBlock),
+    ListItem(DbListItemBlock),
+    Table(DbTableBlock),
+    TableRow(DbTableRowBlock),
+    TableCell(DbTableCellBlock),
+    Verbatim(DbVerbatimBlock),
+    Keyword(DbKeywordBlock),
 }
 
 pub trait DbIO {
     /// Initializes the structure on the stack with default values
     fn zero() -> Self;
-    /// Returns the id of the newly created entity
+    /// Inserts the entity, or updates it in place when a row with its id
+    /// already exists. Returns the id of the written entity
     fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error>;
     /// Returns the number of items deleted in the database
     fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error>;
@@ -19,11 +34,164 @@ pub trait DbIO {
     fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error>;
 }
 
-#[derive(Debug)]
+/// Replaces the tags of the category with `category_id`
+fn write_tags(tx: &Transaction, category_id: i64, tags: &[&str]) -> Result<(), anyhow::Error> {
+    tx.execute(
+        "DELETE FROM category_tags WHERE category_id = ?1",
+        [category_id],
+    )
+    .context(format!("Could not clear tags of category {}", category_id))?;
+
+    for (position, tag) in tags.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO category_tags (category_id, tag, position) VALUES (?1, ?2, ?3)",
+            (category_id, tag, position as i64),
+        )
+        .context(format!("Could not write tag {} of category {}", tag, category_id))?;
+    }
+
+    Ok(())
+}
+
+/// Stores the parts of the headline of the category with `category_id`, so
+/// categories can be queried by keyword, priority and tag
+pub fn write_headline(
+    tx: &Transaction,
+    category_id: i64,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    let headline = Headline::parse(name);
+
+    tx.execute(
+        "UPDATE category_blocks SET todo_keyword = ?1, priority = ?2, title = ?3 WHERE id = ?4",
+        (headline.todo_keyword, headline.priority, headline.title, category_id),
+    )
+    .context(format!("Could not write headline of category {}", category_id))?;
+
+    write_tags(tx, category_id, &headline.tags)
+}
+
+/// Replaces the timestamps stored for the row that `owner_column` of the
+/// timestamps table points at. Each timestamp is stored with a label, along
+/// with its start and end as ISO 8601 strings so they can be sorted.
+fn write_timestamps(
+    tx: &Transaction,
+    owner_column: &str,
+    owner_id: i64,
+    timestamps: &[(Option<&str>, Timestamp)],
+) -> Result<(), anyhow::Error> {
+    tx.execute(
+        &format!("DELETE FROM timestamps WHERE {} = ?1", owner_column),
+        [owner_id],
+    )
+    .context(format!("Could not clear timestamps of {} {}", owner_column, owner_id))?;
+
+    for (label, timestamp) in timestamps {
+        tx.execute(
+            &format!(
+                "INSERT INTO timestamps ({}, label, active, starts_at, ends_at, repeater)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                owner_column
+            ),
+            (
+                owner_id,
+                label,
+                timestamp.active,
+                timestamp.start.to_iso(),
+                timestamp.end.map(|end| end.to_iso()),
+                timestamp.repeater.map(|repeater| repeater.to_string()),
+            ),
+        )
+        .context(format!("Could not write timestamp of {} {}", owner_column, owner_id))?;
+    }
+
+    Ok(())
+}
+
+/// Stores the timestamps in the content of the text block with
+/// `text_block_id`. Timestamps on a planning line are labeled with their
+/// keyword, such as `DEADLINE`.
+pub fn write_text_timestamps(
+    tx: &Transaction,
+    text_block_id: i64,
+    content: &str,
+) -> Result<(), anyhow::Error> {
+    let timestamps: Vec<(Option<&str>, Timestamp)> = find_timestamps(content)
+        .into_iter()
+        .map(|(offset, timestamp)| (planning_keyword(content, offset), timestamp))
+        .collect();
+
+    write_timestamps(tx, "text_block_id", text_block_id, &timestamps)
+}
+
+/// Stores the timestamps in the value of the property with `property_id`,
+/// labeled with its key
+pub fn write_property_timestamps(
+    tx: &Transaction,
+    property_id: i64,
+    key: &str,
+    value: &str,
+) -> Result<(), anyhow::Error> {
+    let timestamps: Vec<(Option<&str>, Timestamp)> = find_timestamps(value)
+        .into_iter()
+        .map(|(_, timestamp)| (Some(key), timestamp))
+        .collect();
+
+    write_timestamps(tx, "property_id", property_id, &timestamps)
+}
+
+/// Replaces the links stored for the row that `owner_column` of the links
+/// table points at with the links found in `content`. Links are stored
+/// unresolved along with the keys they are looked up by, the categories
+/// they point at are found once every block has been written.
+pub fn write_links(
+    tx: &Transaction,
+    owner_column: &str,
+    owner_id: i64,
+    content: &str,
+) -> Result<(), anyhow::Error> {
+    tx.execute(&format!("DELETE FROM links WHERE {} = ?1", owner_column), [owner_id])
+        .context(format!("Could not clear links of {} {}", owner_column, owner_id))?;
+
+    for (_, link) in find_links(content) {
+        let (target, search) = match link.target {
+            LinkTarget::File { path, search } => (path, search),
+            LinkTarget::Id(target) | LinkTarget::Heading(target) | LinkTarget::Url(target) => {
+                (target, None)
+            }
+        };
+        let (target_key, search_key) = link.target.keys();
+
+        tx.execute(
+            &format!(
+                "INSERT INTO links
+                    ({}, kind, target, search, description, target_key, search_key)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                owner_column
+            ),
+            (
+                owner_id,
+                link.target.kind(),
+                target,
+                search,
+                link.description,
+                target_key,
+                search_key,
+            ),
+        )
+        .context(format!("Could not write link of {} {}", owner_column, owner_id))?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
 pub struct DbCategoryBlock {
     pub id: Option<i64>,
     pub name: String,
     pub parent_category_id: Option<i64>,
+    /// Index of the category among the children of its parent
+    pub position: i64,
 }
 
 impl Clone for DbCategoryBlock {
@@ -31,7 +199,8 @@ impl Clone for DbCategoryBlock {
         DbCategoryBlock {
             id: self.id,
             name: self.name.clone(),
-            parent_category_id: self.parent_category_id.clone()
+            parent_category_id: self.parent_category_id,
+            position: self.position,
         }
     }
 }
@@ -42,6 +211,7 @@ impl DbIO for DbCategoryBlock {
             id: Some(0),
             name: "".to_string(),
             parent_category_id: Some(0),
+            position: 0,
         }
     }
 
@@ -49,7 +219,7 @@ impl DbIO for DbCategoryBlock {
         let mut stmt = connection
             .prepare(
                 "
-            SELECT id, name, parent_category_id
+            SELECT id, name, parent_category_id, position
             FROM category_blocks
             WHERE id = ?1",
             )
@@ -61,6 +231,7 @@ impl DbIO for DbCategoryBlock {
                     id: row.get(0)?,
                     name: row.get(1)?,
                     parent_category_id: row.get(2)?,
+                    position: row.get(3)?,
                 })
             })
             .context("Could not prepare select statement query map")?;
@@ -72,6 +243,7 @@ impl DbIO for DbCategoryBlock {
                 self.id = block.id;
                 self.name = block.name;
                 self.parent_category_id = block.parent_category_id;
+                self.position = block.position;
 
                 Ok(())
             }
@@ -80,16 +252,29 @@ impl DbIO for DbCategoryBlock {
     }
 
     fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
-        if self.id != None {
-            self.delete(tx)
-                .context("Could not perform deletion operation on category block")?;
-        }
+        let headline = Headline::parse(&self.name);
 
         tx.execute(
             "
-            INSERT INTO category_blocks (id, name, parent_category_id)
-            VALUES (?1, ?2, ?3)",
-            (&self.id, &self.name, &self.parent_category_id),
+            INSERT INTO category_blocks
+                (id, name, parent_category_id, position, todo_keyword, priority, title)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                parent_category_id = excluded.parent_category_id,
+                position = excluded.position,
+                todo_keyword = excluded.todo_keyword,
+                priority = excluded.priority,
+                title = excluded.title",
+            (
+                &self.id,
+                &self.name,
+                &self.parent_category_id,
+                &self.position,
+                headline.todo_keyword,
+                headline.priority,
+                headline.title,
+            ),
         )
         .context("Could not perform insert of category block")?;
 
@@ -98,11 +283,13 @@ impl DbIO for DbCategoryBlock {
             Some(id) => id,
         };
 
+        write_tags(tx, id, &headline.tags)?;
+
         Ok(id)
     }
 
     fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
-        if self.id == None {
+        if self.id.is_none() {
             return Err(anyhow!(
                 "Attempting to delete a category block that does not have id"
             ));
@@ -118,17 +305,20 @@ impl DbIO for DbCategoryBlock {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct DbRichTextBlock {
     pub id: Option<i64>,
     pub parent_category_id: Option<i64>,
+    /// Index of the block among the children of its category
+    pub position: i64,
 }
 
 impl Clone for DbRichTextBlock {
     fn clone(&self) -> Self {
         DbRichTextBlock {
             id: self.id,
-            parent_category_id: self.parent_category_id
+            parent_category_id: self.parent_category_id,
+            position: self.position,
         }
     }
 }
@@ -138,6 +328,7 @@ impl DbIO for DbRichTextBlock {
         DbRichTextBlock {
             id: Some(0),
             parent_category_id: Some(0),
+            position: 0,
         }
     }
 
@@ -145,7 +336,7 @@ impl DbIO for DbRichTextBlock {
         let mut stmt = connection
             .prepare(
                 "
-            SELECT id, parent_category_id
+            SELECT id, parent_category_id, position
             FROM rich_text_blocks
             WHERE id = ?1",
             )
@@ -156,6 +347,7 @@ impl DbIO for DbRichTextBlock {
                 Ok(DbRichTextBlock {
                     id: row.get(0)?,
                     parent_category_id: row.get(1)?,
+                    position: row.get(2)?,
                 })
             })
             .context("Could not prepare select statement query map")?;
@@ -166,6 +358,7 @@ impl DbIO for DbRichTextBlock {
 
                 self.id = block.id;
                 self.parent_category_id = block.parent_category_id;
+                self.position = block.position;
 
                 Ok(())
             }
@@ -174,16 +367,14 @@ impl DbIO for DbRichTextBlock {
     }
 
     fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
-        if self.id != None {
-            self.delete(tx)
-                .context("Could not perform deletion operation on rich text block")?;
-        }
-
         tx.execute(
             "
-            INSERT INTO rich_text_blocks (id, parent_category_id)
-            VALUES (?1, ?2)",
-            (&self.id, &self.parent_category_id),
+            INSERT INTO rich_text_blocks (id, parent_category_id, position)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT(id) DO UPDATE SET
+                parent_category_id = excluded.parent_category_id,
+                position = excluded.position",
+            (&self.id, &self.parent_category_id, &self.position),
         )
         .context("Could not insert rich text block")?;
 
@@ -196,7 +387,7 @@ impl DbIO for DbRichTextBlock {
     }
 
     fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
-        if self.id == None {
+        if self.id.is_none() {
             return Err(anyhow!(
                 "Attempting to delete a rich text block that does not have id"
             ));
@@ -215,12 +406,14 @@ impl DbIO for DbRichTextBlock {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct DbTextBlock {
     pub id: Option<i64>,
     pub content: String,
     pub parent_category_id: Option<i64>,
     pub parent_rich_text_block_id: Option<i64>,
+    /// Index of the block among the children of its parent
+    pub position: i64,
 }
 
 impl Clone for DbTextBlock {
@@ -229,7 +422,8 @@ impl Clone for DbTextBlock {
             id: self.id,
             content: self.content.clone(),
             parent_category_id: self.parent_category_id,
-            parent_rich_text_block_id: self.parent_rich_text_block_id
+            parent_rich_text_block_id: self.parent_rich_text_block_id,
+            position: self.position,
         }
     }
 }
@@ -241,6 +435,7 @@ impl DbIO for DbTextBlock {
             content: "".to_string(),
             parent_category_id: Some(0),
             parent_rich_text_block_id: Some(0),
+            position: 0,
         }
     }
 
@@ -248,7 +443,7 @@ impl DbIO for DbTextBlock {
         let mut stmt = connection
             .prepare(
                 "
-            SELECT id, content, parent_category_id, parent_rich_text_block_id
+            SELECT id, content, parent_category_id, parent_rich_text_block_id, position
             FROM text_blocks
             WHERE id = ?1",
             )
@@ -261,6 +456,7 @@ impl DbIO for DbTextBlock {
                     content: row.get(1)?,
                     parent_category_id: row.get(2)?,
                     parent_rich_text_block_id: row.get(3)?,
+                    position: row.get(4)?,
                 })
             })
             .context("Could not prepare select statement query map")?;
@@ -273,6 +469,7 @@ impl DbIO for DbTextBlock {
                 self.content = block.content;
                 self.parent_category_id = block.parent_category_id;
                 self.parent_rich_text_block_id = block.parent_rich_text_block_id;
+                self.position = block.position;
 
                 Ok(())
             }
@@ -283,13 +480,19 @@ impl DbIO for DbTextBlock {
     fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
         tx.execute(
             "
-            INSERT INTO text_blocks (id, content, parent_category_id, parent_rich_text_block_id)
-            VALUES (?1, ?2, ?3, ?4)",
+            INSERT INTO text_blocks (id, content, parent_category_id, parent_rich_text_block_id, position)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(id) DO UPDATE SET
+                content = excluded.content,
+                parent_category_id = excluded.parent_category_id,
+                parent_rich_text_block_id = excluded.parent_rich_text_block_id,
+                position = excluded.position",
             (
                 &self.id,
                 &self.content,
                 &self.parent_category_id,
                 &self.parent_rich_text_block_id,
+                &self.position,
             ),
         )
         .context("Could not insert text block into database")?;
@@ -299,11 +502,14 @@ impl DbIO for DbTextBlock {
             Some(id) => id,
         };
 
+        write_text_timestamps(tx, id, &self.content)?;
+        write_links(tx, "text_block_id", id, &self.content)?;
+
         Ok(id)
     }
 
     fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
-        if self.id == None {
+        if self.id.is_none() {
             return Err(anyhow!(
                 "Attempting to delete a text block that does not have id"
             ));
@@ -311,6 +517,11 @@ impl DbIO for DbTextBlock {
 
         let db_id = self.id.unwrap();
 
+        tx.execute("DELETE FROM timestamps WHERE text_block_id = ?1", [db_id])
+            .context(format!("Could not delete timestamps of text block with id {}", db_id))?;
+        tx.execute("DELETE FROM links WHERE text_block_id = ?1", [db_id])
+            .context(format!("Could not delete links of text block with id {}", db_id))?;
+
         let count = tx
             .execute("DELETE from text_blocks WHERE id = ?1", [db_id])
             .context(format!("Could not delete text block with id {}", db_id))?;
@@ -318,3 +529,1118 @@ impl DbIO for DbTextBlock {
         Ok(count)
     }
 }
+
+#[derive(Debug, PartialEq)]
+pub struct DbPropertyBlock {
+    pub id: Option<i64>,
+    pub key: String,
+    pub value: String,
+    pub indent: String,
+    pub separator: String,
+    pub trailing: String,
+    /// Set for properties directly inside a category
+    pub parent_category_id: Option<i64>,
+    /// Set for properties inside a drawer
+    pub parent_drawer_id: Option<i64>,
+    /// Index of the property among the children of its category or drawer
+    pub position: i64,
+}
+
+impl Clone for DbPropertyBlock {
+    fn clone(&self) -> Self {
+        DbPropertyBlock {
+            id: self.id,
+            key: self.key.clone(),
+            value: self.value.clone(),
+            indent: self.indent.clone(),
+            separator: self.separator.clone(),
+            trailing: self.trailing.clone(),
+            parent_category_id: self.parent_category_id,
+            parent_drawer_id: self.parent_drawer_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbPropertyBlock {
+    fn zero() -> Self {
+        DbPropertyBlock {
+            id: Some(0),
+            key: "".to_string(),
+            value: "".to_string(),
+            indent: "".to_string(),
+            separator: "".to_string(),
+            trailing: "".to_string(),
+            parent_category_id: Some(0),
+            parent_drawer_id: None,
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, key, value, indent, separator, trailing, parent_category_id,
+                parent_drawer_id, position
+            FROM properties
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbPropertyBlock {
+                    id: row.get(0)?,
+                    key: row.get(1)?,
+                    value: row.get(2)?,
+                    indent: row.get(3)?,
+                    separator: row.get(4)?,
+                    trailing: row.get(5)?,
+                    parent_category_id: row.get(6)?,
+                    parent_drawer_id: row.get(7)?,
+                    position: row.get(8)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get property from query")?;
+
+                self.id = block.id;
+                self.key = block.key;
+                self.value = block.value;
+                self.indent = block.indent;
+                self.separator = block.separator;
+                self.trailing = block.trailing;
+                self.parent_category_id = block.parent_category_id;
+                self.parent_drawer_id = block.parent_drawer_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO properties
+                (id, key, value, indent, separator, trailing, parent_category_id,
+                    parent_drawer_id, position)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            ON CONFLICT(id) DO UPDATE SET
+                key = excluded.key,
+                value = excluded.value,
+                indent = excluded.indent,
+                separator = excluded.separator,
+                trailing = excluded.trailing,
+                parent_category_id = excluded.parent_category_id,
+                parent_drawer_id = excluded.parent_drawer_id,
+                position = excluded.position",
+            (
+                &self.id,
+                &self.key,
+                &self.value,
+                &self.indent,
+                &self.separator,
+                &self.trailing,
+                &self.parent_category_id,
+                &self.parent_drawer_id,
+                &self.position,
+            ),
+        )
+        .context("Could not insert property into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        write_property_timestamps(tx, id, &self.key, &self.value)?;
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!(
+                "Attempting to delete a property that does not have id"
+            ));
+        }
+
+        let db_id = self.id.unwrap();
+
+        tx.execute("DELETE FROM timestamps WHERE property_id = ?1", [db_id])
+            .context(format!("Could not delete timestamps of property with id {}", db_id))?;
+
+        let count = tx
+            .execute("DELETE from properties WHERE id = ?1", [db_id])
+            .context(format!("Could not delete property with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbDrawerBlock {
+    pub id: Option<i64>,
+    pub begin: String,
+    /// None for a drawer that was never closed
+    pub end: Option<String>,
+    pub parent_category_id: Option<i64>,
+    pub position: i64,
+}
+
+impl Clone for DbDrawerBlock {
+    fn clone(&self) -> Self {
+        DbDrawerBlock {
+            id: self.id,
+            begin: self.begin.clone(),
+            end: self.end.clone(),
+            parent_category_id: self.parent_category_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbDrawerBlock {
+    fn zero() -> Self {
+        DbDrawerBlock {
+            id: Some(0),
+            begin: "".to_string(),
+            end: None,
+            parent_category_id: Some(0),
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, begin_line, end_line, parent_category_id, position
+            FROM drawers
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbDrawerBlock {
+                    id: row.get(0)?,
+                    begin: row.get(1)?,
+                    end: row.get(2)?,
+                    parent_category_id: row.get(3)?,
+                    position: row.get(4)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get drawer from query")?;
+
+                self.id = block.id;
+                self.begin = block.begin;
+                self.end = block.end;
+                self.parent_category_id = block.parent_category_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO drawers (id, begin_line, end_line, parent_category_id, position)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(id) DO UPDATE SET
+                begin_line = excluded.begin_line,
+                end_line = excluded.end_line,
+                parent_category_id = excluded.parent_category_id,
+                position = excluded.position",
+            (
+                &self.id,
+                &self.begin,
+                &self.end,
+                &self.parent_category_id,
+                &self.position,
+            ),
+        )
+        .context("Could not insert drawer into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!("Attempting to delete a drawer that does not have id"));
+        }
+
+        let db_id = self.id.unwrap();
+
+        let count = tx
+            .execute("DELETE from drawers WHERE id = ?1", [db_id])
+            .context(format!("Could not delete drawer with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbListBlock {
+    pub id: Option<i64>,
+    pub ordered: bool,
+    /// Set for lists directly inside a category
+    pub parent_category_id: Option<i64>,
+    /// Set for lists nested in a list item
+    pub parent_list_item_id: Option<i64>,
+    /// Index of the list among the children of its parent
+    pub position: i64,
+}
+
+impl Clone for DbListBlock {
+    fn clone(&self) -> Self {
+        DbListBlock {
+            id: self.id,
+            ordered: self.ordered,
+            parent_category_id: self.parent_category_id,
+            parent_list_item_id: self.parent_list_item_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbListBlock {
+    fn zero() -> Self {
+        DbListBlock {
+            id: Some(0),
+            ordered: false,
+            parent_category_id: Some(0),
+            parent_list_item_id: None,
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, ordered, parent_category_id, parent_list_item_id, position
+            FROM lists
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbListBlock {
+                    id: row.get(0)?,
+                    ordered: row.get(1)?,
+                    parent_category_id: row.get(2)?,
+                    parent_list_item_id: row.get(3)?,
+                    position: row.get(4)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get list from query")?;
+
+                self.id = block.id;
+                self.ordered = block.ordered;
+                self.parent_category_id = block.parent_category_id;
+                self.parent_list_item_id = block.parent_list_item_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO lists (id, ordered, parent_category_id, parent_list_item_id, position)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(id) DO UPDATE SET
+                ordered = excluded.ordered,
+                parent_category_id = excluded.parent_category_id,
+                parent_list_item_id = excluded.parent_list_item_id,
+                position = excluded.position",
+            (
+                &self.id,
+                &self.ordered,
+                &self.parent_category_id,
+                &self.parent_list_item_id,
+                &self.position,
+            ),
+        )
+        .context("Could not insert list into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!("Attempting to delete a list that does not have id"));
+        }
+
+        let db_id = self.id.unwrap();
+
+        let count = tx
+            .execute("DELETE from lists WHERE id = ?1", [db_id])
+            .context(format!("Could not delete list with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbListItemBlock {
+    pub id: Option<i64>,
+    pub bullet: String,
+    /// The checkbox as written, such as `[ ]`
+    pub checkbox: Option<String>,
+    pub content: String,
+    pub parent_list_id: Option<i64>,
+    /// Index of the item within its list
+    pub position: i64,
+}
+
+impl Clone for DbListItemBlock {
+    fn clone(&self) -> Self {
+        DbListItemBlock {
+            id: self.id,
+            bullet: self.bullet.clone(),
+            checkbox: self.checkbox.clone(),
+            content: self.content.clone(),
+            parent_list_id: self.parent_list_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbListItemBlock {
+    fn zero() -> Self {
+        DbListItemBlock {
+            id: Some(0),
+            bullet: "".to_string(),
+            checkbox: None,
+            content: "".to_string(),
+            parent_list_id: Some(0),
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, bullet, checkbox, content, parent_list_id, position
+            FROM list_items
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbListItemBlock {
+                    id: row.get(0)?,
+                    bullet: row.get(1)?,
+                    checkbox: row.get(2)?,
+                    content: row.get(3)?,
+                    parent_list_id: row.get(4)?,
+                    position: row.get(5)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get list item from query")?;
+
+                self.id = block.id;
+                self.bullet = block.bullet;
+                self.checkbox = block.checkbox;
+                self.content = block.content;
+                self.parent_list_id = block.parent_list_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO list_items (id, bullet, checkbox, content, parent_list_id, position)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT(id) DO UPDATE SET
+                bullet = excluded.bullet,
+                checkbox = excluded.checkbox,
+                content = excluded.content,
+                parent_list_id = excluded.parent_list_id,
+                position = excluded.position",
+            (
+                &self.id,
+                &self.bullet,
+                &self.checkbox,
+                &self.content,
+                &self.parent_list_id,
+                &self.position,
+            ),
+        )
+        .context("Could not insert list item into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        write_links(tx, "list_item_id", id, &self.content)?;
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!(
+                "Attempting to delete a list item that does not have id"
+            ));
+        }
+
+        let db_id = self.id.unwrap();
+
+        tx.execute("DELETE FROM links WHERE list_item_id = ?1", [db_id])
+            .context(format!("Could not delete links of list item with id {}", db_id))?;
+
+        let count = tx
+            .execute("DELETE from list_items WHERE id = ?1", [db_id])
+            .context(format!("Could not delete list item with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbTableBlock {
+    pub id: Option<i64>,
+    pub parent_category_id: Option<i64>,
+    pub position: i64,
+}
+
+impl Clone for DbTableBlock {
+    fn clone(&self) -> Self {
+        DbTableBlock {
+            id: self.id,
+            parent_category_id: self.parent_category_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbTableBlock {
+    fn zero() -> Self {
+        DbTableBlock {
+            id: Some(0),
+            parent_category_id: Some(0),
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, parent_category_id, position
+            FROM tables
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbTableBlock {
+                    id: row.get(0)?,
+                    parent_category_id: row.get(1)?,
+                    position: row.get(2)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get table from query")?;
+
+                self.id = block.id;
+                self.parent_category_id = block.parent_category_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO tables (id, parent_category_id, position)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT(id) DO UPDATE SET
+                parent_category_id = excluded.parent_category_id,
+                position = excluded.position",
+            (&self.id, &self.parent_category_id, &self.position),
+        )
+        .context("Could not insert table into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!("Attempting to delete a table that does not have id"));
+        }
+
+        let db_id = self.id.unwrap();
+
+        let count = tx
+            .execute("DELETE from tables WHERE id = ?1", [db_id])
+            .context(format!("Could not delete table with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbTableRowBlock {
+    pub id: Option<i64>,
+    pub indent: String,
+    /// Whether the row is a rule, like `|---+---|`, rather than cells
+    pub rule: bool,
+    pub parent_table_id: Option<i64>,
+    pub position: i64,
+}
+
+impl Clone for DbTableRowBlock {
+    fn clone(&self) -> Self {
+        DbTableRowBlock {
+            id: self.id,
+            indent: self.indent.clone(),
+            rule: self.rule,
+            parent_table_id: self.parent_table_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbTableRowBlock {
+    fn zero() -> Self {
+        DbTableRowBlock {
+            id: Some(0),
+            indent: String::new(),
+            rule: false,
+            parent_table_id: Some(0),
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, indent, rule, parent_table_id, position
+            FROM table_rows
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbTableRowBlock {
+                    id: row.get(0)?,
+                    indent: row.get(1)?,
+                    rule: row.get(2)?,
+                    parent_table_id: row.get(3)?,
+                    position: row.get(4)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get table row from query")?;
+
+                self.id = block.id;
+                self.indent = block.indent;
+                self.rule = block.rule;
+                self.parent_table_id = block.parent_table_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO table_rows (id, indent, rule, parent_table_id, position)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(id) DO UPDATE SET
+                indent = excluded.indent,
+                rule = excluded.rule,
+                parent_table_id = excluded.parent_table_id,
+                position = excluded.position",
+            (&self.id, &self.indent, &self.rule, &self.parent_table_id, &self.position),
+        )
+        .context("Could not insert table row into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!(
+                "Attempting to delete a table row that does not have id"
+            ));
+        }
+
+        let db_id = self.id.unwrap();
+
+        let count = tx
+            .execute("DELETE from table_rows WHERE id = ?1", [db_id])
+            .context(format!("Could not delete table row with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbTableCellBlock {
+    pub id: Option<i64>,
+    pub content: String,
+    pub parent_table_row_id: Option<i64>,
+    /// Index of the column the cell is in
+    pub position: i64,
+}
+
+impl Clone for DbTableCellBlock {
+    fn clone(&self) -> Self {
+        DbTableCellBlock {
+            id: self.id,
+            content: self.content.clone(),
+            parent_table_row_id: self.parent_table_row_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbTableCellBlock {
+    fn zero() -> Self {
+        DbTableCellBlock {
+            id: Some(0),
+            content: "".to_string(),
+            parent_table_row_id: Some(0),
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, content, parent_table_row_id, position
+            FROM table_cells
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbTableCellBlock {
+                    id: row.get(0)?,
+                    content: row.get(1)?,
+                    parent_table_row_id: row.get(2)?,
+                    position: row.get(3)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get table cell from query")?;
+
+                self.id = block.id;
+                self.content = block.content;
+                self.parent_table_row_id = block.parent_table_row_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO table_cells (id, content, parent_table_row_id, position)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(id) DO UPDATE SET
+                content = excluded.content,
+                parent_table_row_id = excluded.parent_table_row_id,
+                position = excluded.position",
+            (&self.id, &self.content, &self.parent_table_row_id, &self.position),
+        )
+        .context("Could not insert table cell into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        write_links(tx, "table_cell_id", id, &self.content)?;
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!(
+                "Attempting to delete a table cell that does not have id"
+            ));
+        }
+
+        let db_id = self.id.unwrap();
+
+        tx.execute("DELETE FROM links WHERE table_cell_id = ?1", [db_id])
+            .context(format!("Could not delete links of table cell with id {}", db_id))?;
+
+        let count = tx
+            .execute("DELETE from table_cells WHERE id = ?1", [db_id])
+            .context(format!("Could not delete table cell with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbVerbatimBlock {
+    pub id: Option<i64>,
+    pub begin: String,
+    pub content: String,
+    pub end: String,
+    pub end_line_break: bool,
+    pub parent_category_id: Option<i64>,
+    /// Index of the block among the children of its category
+    pub position: i64,
+}
+
+impl Clone for DbVerbatimBlock {
+    fn clone(&self) -> Self {
+        DbVerbatimBlock {
+            id: self.id,
+            begin: self.begin.clone(),
+            content: self.content.clone(),
+            end: self.end.clone(),
+            end_line_break: self.end_line_break,
+            parent_category_id: self.parent_category_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbVerbatimBlock {
+    fn zero() -> Self {
+        DbVerbatimBlock {
+            id: Some(0),
+            begin: "".to_string(),
+            content: "".to_string(),
+            end: "".to_string(),
+            end_line_break: true,
+            parent_category_id: Some(0),
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, begin_line, content, end_line, end_line_break, parent_category_id,
+                position
+            FROM verbatim_blocks
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbVerbatimBlock {
+                    id: row.get(0)?,
+                    begin: row.get(1)?,
+                    content: row.get(2)?,
+                    end: row.get(3)?,
+                    end_line_break: row.get(4)?,
+                    parent_category_id: row.get(5)?,
+                    position: row.get(6)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get verbatim block from query")?;
+
+                self.id = block.id;
+                self.begin = block.begin;
+                self.content = block.content;
+                self.end = block.end;
+                self.end_line_break = block.end_line_break;
+                self.parent_category_id = block.parent_category_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Also stores the kind, language and parameters of the block, so
+    /// blocks can be queried by them
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        let block = VerbatimBlock {
+            id: self.id,
+            begin: &self.begin,
+            content: &self.content,
+            end: &self.end,
+            end_line_break: self.end_line_break,
+        };
+
+        tx.execute(
+            "
+            INSERT INTO verbatim_blocks (
+                id, kind, language, parameters, begin_line, content, end_line,
+                end_line_break, parent_category_id, position
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ON CONFLICT(id) DO UPDATE SET
+                kind = excluded.kind,
+                language = excluded.language,
+                parameters = excluded.parameters,
+                begin_line = excluded.begin_line,
+                content = excluded.content,
+                end_line = excluded.end_line,
+                end_line_break = excluded.end_line_break,
+                parent_category_id = excluded.parent_category_id,
+                position = excluded.position",
+            (
+                &self.id,
+                block.kind().to_uppercase(),
+                block.language(),
+                block.parameters(),
+                &self.begin,
+                &self.content,
+                &self.end,
+                &self.end_line_break,
+                &self.parent_category_id,
+                &self.position,
+            ),
+        )
+        .context("Could not insert verbatim block into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!(
+                "Attempting to delete a verbatim block that does not have id"
+            ));
+        }
+
+        let db_id = self.id.unwrap();
+
+        let count = tx
+            .execute("DELETE from verbatim_blocks WHERE id = ?1", [db_id])
+            .context(format!("Could not delete verbatim block with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DbKeywordBlock {
+    pub id: Option<i64>,
+    pub key: String,
+    pub value: String,
+    pub indent: String,
+    pub separator: String,
+    pub trailing: String,
+    pub parent_category_id: Option<i64>,
+    /// Index of the keyword among the children of its category
+    pub position: i64,
+}
+
+impl Clone for DbKeywordBlock {
+    fn clone(&self) -> Self {
+        DbKeywordBlock {
+            id: self.id,
+            key: self.key.clone(),
+            value: self.value.clone(),
+            indent: self.indent.clone(),
+            separator: self.separator.clone(),
+            trailing: self.trailing.clone(),
+            parent_category_id: self.parent_category_id,
+            position: self.position,
+        }
+    }
+}
+
+impl DbIO for DbKeywordBlock {
+    fn zero() -> Self {
+        DbKeywordBlock {
+            id: Some(0),
+            key: "".to_string(),
+            value: "".to_string(),
+            indent: "".to_string(),
+            separator: "".to_string(),
+            trailing: "".to_string(),
+            parent_category_id: Some(0),
+            position: 0,
+        }
+    }
+
+    fn select(&mut self, connection: &Connection, id: i64) -> Result<(), anyhow::Error> {
+        let mut stmt = connection
+            .prepare(
+                "
+            SELECT id, key, value, indent, separator, trailing, parent_category_id, position
+            FROM keywords
+            WHERE id = ?1",
+            )
+            .context("Could not prepare select statement")?;
+
+        let mut row_iter = stmt
+            .query_map([id], |row| {
+                Ok(DbKeywordBlock {
+                    id: row.get(0)?,
+                    key: row.get(1)?,
+                    value: row.get(2)?,
+                    indent: row.get(3)?,
+                    separator: row.get(4)?,
+                    trailing: row.get(5)?,
+                    parent_category_id: row.get(6)?,
+                    position: row.get(7)?,
+                })
+            })
+            .context("Could not prepare select statement query map")?;
+
+        match row_iter.nth(0) {
+            Some(v) => {
+                let block = v.context("Failed to get keyword from query")?;
+
+                self.id = block.id;
+                self.key = block.key;
+                self.value = block.value;
+                self.indent = block.indent;
+                self.separator = block.separator;
+                self.trailing = block.trailing;
+                self.parent_category_id = block.parent_category_id;
+                self.position = block.position;
+
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write(&self, tx: &Transaction) -> Result<i64, anyhow::Error> {
+        tx.execute(
+            "
+            INSERT INTO keywords
+                (id, key, value, indent, separator, trailing, parent_category_id, position)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT(id) DO UPDATE SET
+                key = excluded.key,
+                value = excluded.value,
+                indent = excluded.indent,
+                separator = excluded.separator,
+                trailing = excluded.trailing,
+                parent_category_id = excluded.parent_category_id,
+                position = excluded.position",
+            (
+                &self.id,
+                &self.key,
+                &self.value,
+                &self.indent,
+                &self.separator,
+                &self.trailing,
+                &self.parent_category_id,
+                &self.position,
+            ),
+        )
+        .context("Could not insert keyword into database")?;
+
+        let id = match self.id {
+            None => tx.last_insert_rowid(),
+            Some(id) => id,
+        };
+
+        Ok(id)
+    }
+
+    fn delete(&self, tx: &Transaction) -> Result<usize, anyhow::Error> {
+        if self.id.is_none() {
+            return Err(anyhow!(
+                "Attempting to delete a keyword that does not have id"
+            ));
+        }
+
+        let db_id = self.id.unwrap();
+
+        let count = tx
+            .execute("DELETE from keywords WHERE id = ?1", [db_id])
+            .context(format!("Could not delete keyword with id {}", db_id))?;
+
+        Ok(count)
+    }
+}
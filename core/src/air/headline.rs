@@ -0,0 +1,84 @@
+/// Keywords that mark a headline as a TODO item when they are its first
+/// word. These are org mode's defaults.
+pub const TODO_KEYWORDS: &[&str] = &["TODO", "DONE"];
+
+/// The parts of a headline such as `TODO [#A] Write the report :work:urgent:`.
+/// Every part is a slice of the headline it was parsed from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Headline<'a> {
+    pub todo_keyword: Option<&'a str>,
+    /// The letter or digit of a `[#A]` cookie
+    pub priority: Option<&'a str>,
+    /// The headline without its keyword, priority or tags
+    pub title: &'a str,
+    pub tags: Vec<&'a str>,
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '%')
+}
+
+/// Splits a trailing `:tag:other:` group off of `text`, returning the text
+/// before it and the tags. Text without tags is returned unchanged.
+pub fn split_tags(text: &str) -> (&str, Vec<&str>) {
+    let text = text.trim_end();
+    let (before, last) = match text.rsplit_once(char::is_whitespace) {
+        Some((before, last)) => (before, last),
+        None => ("", text),
+    };
+
+    let Some(inner) = last
+        .strip_prefix(':')
+        .and_then(|last| last.strip_suffix(':'))
+    else {
+        return (text, Vec::new());
+    };
+
+    let tags: Vec<&str> = inner.split(':').collect();
+
+    if tags.iter().any(|tag| tag.is_empty() || !tag.chars().all(is_tag_char)) {
+        return (text, Vec::new());
+    }
+
+    (before.trim_end(), tags)
+}
+
+/// Returns the letter or digit of a `[#A]` priority cookie at the start of
+/// `text`, along with the text after it
+fn split_priority(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("[#")?;
+    let priority = rest.get(..1).filter(|p| p.chars().all(|c| c.is_ascii_alphanumeric()))?;
+    let after = rest[1..].strip_prefix(']')?;
+
+    if !after.is_empty() && !after.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    Some((priority, after.trim_start()))
+}
+
+impl<'a> Headline<'a> {
+    /// Splits a category name into its parts. Anything that is not a TODO
+    /// keyword, priority cookie or tag group is part of the title.
+    pub fn parse(name: &'a str) -> Headline<'a> {
+        let mut rest = name.trim();
+        let mut headline = Headline::default();
+
+        let (first_word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if TODO_KEYWORDS.contains(&first_word) {
+            headline.todo_keyword = Some(first_word);
+            rest = after.trim_start();
+        }
+
+        if let Some((priority, after)) = split_priority(rest) {
+            headline.priority = Some(priority);
+            rest = after;
+        }
+
+        let (title, tags) = split_tags(rest);
+        headline.title = title;
+        headline.tags = tags;
+
+        headline
+    }
+}
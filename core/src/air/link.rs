@@ -0,0 +1,253 @@
+use std::{fmt, path::Path};
+
+/// Where a link points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkTarget<'a> {
+    /// `file:notes.org`, optionally followed by `::` and a headline to
+    /// search for within the file
+    File {
+        path: &'a str,
+        search: Option<&'a str>,
+    },
+    /// `id:abc`, pointing at the headline whose `ID` or `CUSTOM_ID`
+    /// property is `abc`
+    Id(&'a str),
+    /// `*Headline`, or any text without a scheme, pointing at the headline
+    /// with that title
+    Heading(&'a str),
+    /// Anything with a scheme, such as `https://example.com`
+    Url(&'a str),
+}
+
+impl<'a> LinkTarget<'a> {
+    pub fn parse(text: &'a str) -> LinkTarget<'a> {
+        if let Some(file) = text.strip_prefix("file:") {
+            return match file.split_once("::") {
+                Some((path, search)) => LinkTarget::File {
+                    path,
+                    search: Some(search.strip_prefix('*').unwrap_or(search)),
+                },
+                None => LinkTarget::File { path: file, search: None },
+            };
+        }
+
+        if let Some(id) = text.strip_prefix("id:") {
+            return LinkTarget::Id(id);
+        }
+
+        if let Some(heading) = text.strip_prefix('*') {
+            return LinkTarget::Heading(heading);
+        }
+
+        let has_scheme = text.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+')
+        });
+
+        if has_scheme {
+            LinkTarget::Url(text)
+        } else {
+            LinkTarget::Heading(text)
+        }
+    }
+
+    /// The name of the kind of target, as stored in the database
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LinkTarget::File { .. } => "file",
+            LinkTarget::Id(_) => "id",
+            LinkTarget::Heading(_) => "heading",
+            LinkTarget::Url(_) => "url",
+        }
+    }
+
+    /// The keys the target and its search are looked up by. Files are
+    /// looked up by the name of the file, without its directory or
+    /// extension.
+    pub fn keys(&self) -> (Option<String>, Option<String>) {
+        match self {
+            LinkTarget::File { path, search } => (
+                Path::new(path).file_stem().and_then(|stem| stem.to_str()).map(title_key),
+                search.map(title_key),
+            ),
+            LinkTarget::Id(id) => (Some(id_key(id)), None),
+            LinkTarget::Heading(title) => (Some(title_key(title)), None),
+            LinkTarget::Url(_) => (None, None),
+        }
+    }
+}
+
+/// The key a headline title or top level category name is looked up by,
+/// ignoring case and surrounding whitespace
+pub fn title_key(title: &str) -> String {
+    title.trim().to_lowercase()
+}
+
+/// The key the value of an `ID` or `CUSTOM_ID` property is looked up by
+pub fn id_key(id: &str) -> String {
+    id.trim().to_string()
+}
+
+impl fmt::Display for LinkTarget<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkTarget::File { path, search: Some(search) } => {
+                write!(f, "file:{}::*{}", path, search)
+            }
+            LinkTarget::File { path, search: None } => write!(f, "file:{}", path),
+            LinkTarget::Id(id) => write!(f, "id:{}", id),
+            LinkTarget::Heading(heading) => write!(f, "*{}", heading),
+            LinkTarget::Url(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+/// An org link, such as `[[https://orgmode.org][Org]]` or `[[*Tasks]]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link<'a> {
+    pub target: LinkTarget<'a>,
+    pub description: Option<&'a str>,
+}
+
+impl<'a> Link<'a> {
+    /// Parses a link at the start of `text`, returning it and its length in
+    /// bytes
+    pub fn parse_prefix(text: &'a str) -> Option<(Link<'a>, usize)> {
+        let rest = text.strip_prefix("[[")?;
+        let target_end = rest.find(']')?;
+        let target = &rest[..target_end];
+
+        if target.trim().is_empty() || target.contains(['[', '\n']) {
+            return None;
+        }
+
+        let after = &rest[target_end + 1..];
+
+        if after.starts_with(']') {
+            let link = Link {
+                target: LinkTarget::parse(target.trim()),
+                description: None,
+            };
+            return Some((link, 2 + target_end + 2));
+        }
+
+        let description = after.strip_prefix('[')?;
+        let description_end = description.find("]]")?;
+        let description = &description[..description_end];
+
+        if description.contains('\n') {
+            return None;
+        }
+
+        let link = Link {
+            target: LinkTarget::parse(target.trim()),
+            description: Some(description),
+        };
+
+        Some((link, 2 + target_end + 2 + description_end + 2))
+    }
+
+    /// Parses text that holds nothing but a link, ignoring surrounding
+    /// whitespace
+    pub fn parse(text: &'a str) -> Option<Link<'a>> {
+        let text = text.trim();
+        let (link, length) = Link::parse_prefix(text)?;
+
+        (length == text.len()).then_some(link)
+    }
+}
+
+impl fmt::Display for Link<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.description {
+            Some(description) => write!(f, "[[{}][{}]]", self.target, description),
+            None => write!(f, "[[{}]]", self.target),
+        }
+    }
+}
+
+/// Finds every link in `text`, along with its byte offset
+pub fn find_links(text: &str) -> Vec<(usize, Link<'_>)> {
+    let mut links = Vec::new();
+    let mut index = 0;
+
+    while let Some(offset) = text[index..].find("[[") {
+        let start = index + offset;
+
+        match Link::parse_prefix(&text[start..]) {
+            Some((link, length)) => {
+                links.push((start, link));
+                index = start + length;
+            }
+            None => index = start + 1,
+        }
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_link_targets() {
+        let targets: Vec<LinkTarget> = [
+            "file:projects/Work.org",
+            "file:Work.org::*Website",
+            "file:Work.org::Website",
+            "id:7f3a",
+            "*Weekly review",
+            "Weekly review",
+            "https://orgmode.org/manual/Hyperlinks.html",
+            "mailto:stacy@example.com",
+        ]
+        .into_iter()
+        .map(LinkTarget::parse)
+        .collect();
+
+        assert_eq!(
+            targets,
+            vec![
+                LinkTarget::File { path: "projects/Work.org", search: None },
+                LinkTarget::File { path: "Work.org", search: Some("Website") },
+                LinkTarget::File { path: "Work.org", search: Some("Website") },
+                LinkTarget::Id("7f3a"),
+                LinkTarget::Heading("Weekly review"),
+                LinkTarget::Heading("Weekly review"),
+                LinkTarget::Url("https://orgmode.org/manual/Hyperlinks.html"),
+                LinkTarget::Url("mailto:stacy@example.com"),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_links_in_text() {
+        let text = "See [[*Tasks]] and [[https://orgmode.org][the manual]], not [[]] or [[a][b.";
+        let links = find_links(text);
+
+        assert_eq!(
+            links,
+            vec![
+                (4, Link { target: LinkTarget::Heading("Tasks"), description: None }),
+                (
+                    19,
+                    Link {
+                        target: LinkTarget::Url("https://orgmode.org"),
+                        description: Some("the manual"),
+                    }
+                ),
+            ]
+        );
+
+        for (offset, link) in links {
+            let written = link.to_string();
+            assert_eq!(&text[offset..offset + written.len()], written);
+        }
+
+        assert_eq!(
+            Link::parse(" [[id:7f3a]] "),
+            Some(Link { target: LinkTarget::Id("7f3a"), description: None })
+        );
+        assert_eq!(Link::parse("[[id:7f3a]] and more"), None);
+    }
+}
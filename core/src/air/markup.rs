@@ -0,0 +1,204 @@
+use super::link::Link;
+
+/// The formatting of a span of text within a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    /// `*bold*`
+    Bold,
+    /// `/italic/`
+    Italic,
+    /// `_underline_`
+    Underline,
+    /// `=verbatim=`
+    Verbatim,
+    /// `~code~`
+    Code,
+    /// `+strike through+`
+    StrikeThrough,
+    /// `[[target][description]]`
+    Link,
+}
+
+impl Markup {
+    /// The emphasis marker written around the span, if it is emphasis
+    pub fn marker(&self) -> Option<char> {
+        match self {
+            Markup::Bold => Some('*'),
+            Markup::Italic => Some('/'),
+            Markup::Underline => Some('_'),
+            Markup::Verbatim => Some('='),
+            Markup::Code => Some('~'),
+            Markup::StrikeThrough => Some('+'),
+            Markup::Link => None,
+        }
+    }
+
+    /// The name of the formatting, as rendered to JSON
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Markup::Bold => "bold",
+            Markup::Italic => "italic",
+            Markup::Underline => "underline",
+            Markup::Verbatim => "verbatim",
+            Markup::Code => "code",
+            Markup::StrikeThrough => "strike_through",
+            Markup::Link => "link",
+        }
+    }
+
+    fn from_marker(marker: char) -> Option<Markup> {
+        match marker {
+            '*' => Some(Markup::Bold),
+            '/' => Some(Markup::Italic),
+            '_' => Some(Markup::Underline),
+            '=' => Some(Markup::Verbatim),
+            '~' => Some(Markup::Code),
+            '+' => Some(Markup::StrikeThrough),
+            _ => None,
+        }
+    }
+}
+
+/// Characters that may come before an opening emphasis marker, besides
+/// whitespace
+const PRE: &[char] = &['-', '(', '{', '\'', '"'];
+
+/// Characters that may come after a closing emphasis marker, besides
+/// whitespace
+const POST: &[char] = &['-', '.', ',', ';', ':', '!', '?', '\'', ')', '}', '[', '"', '\\'];
+
+/// Finds the end of an emphasised span opened by the marker at byte offset
+/// `start` of `text`, following org mode's rules: the opening marker comes
+/// after whitespace or `PRE`, the closing marker after anything but
+/// whitespace, and the closing marker is followed by whitespace, `POST` or
+/// the end of the text
+fn emphasis_end(text: &str, start: usize, marker: char) -> Option<usize> {
+    let before = text[..start].chars().next_back();
+
+    if before.is_some_and(|c| !c.is_whitespace() && !PRE.contains(&c)) {
+        return None;
+    }
+
+    let content_start = start + marker.len_utf8();
+    let first = text[content_start..].chars().next()?;
+
+    if first.is_whitespace() {
+        return None;
+    }
+
+    for (offset, c) in text[content_start..].char_indices().skip(1) {
+        if c == '\n' {
+            return None;
+        }
+
+        if c != marker {
+            continue;
+        }
+
+        let close = content_start + offset;
+        let inner_last = text[..close].chars().next_back();
+        let after = text[close + c.len_utf8()..].chars().next();
+
+        if inner_last.is_some_and(|c| !c.is_whitespace())
+            && after.is_none_or(|c| c.is_whitespace() || POST.contains(&c))
+        {
+            return Some(close + c.len_utf8());
+        }
+    }
+
+    None
+}
+
+/// Splits text into spans of plain text, emphasis and links, each kept as
+/// written including its markers, so joining the spans gives back the text.
+/// Emphasis does not nest, and the text of a link is never emphasised.
+pub fn split_markup(text: &str) -> Vec<(Option<Markup>, &str)> {
+    let mut spans = Vec::new();
+    let mut plain_start = 0;
+    let mut index = 0;
+
+    while let Some(c) = text[index..].chars().next() {
+        let span = if c == '[' {
+            Link::parse_prefix(&text[index..])
+                .map(|(_, length)| (Markup::Link, index + length))
+        } else {
+            Markup::from_marker(c)
+                .and_then(|markup| Some((markup, emphasis_end(text, index, c)?)))
+        };
+
+        match span {
+            Some((markup, end)) => {
+                if plain_start < index {
+                    spans.push((None, &text[plain_start..index]));
+                }
+
+                spans.push((Some(markup), &text[index..end]));
+                index = end;
+                plain_start = end;
+            }
+            None => index += c.len_utf8(),
+        }
+    }
+
+    if plain_start < text.len() || spans.is_empty() {
+        spans.push((None, &text[plain_start..]));
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_emphasis_and_links() {
+        let text = "Call *Stacy* about /the/ =v1.0= ~cargo~ _launch_, +not+ [[*Tasks][tasks]]\n";
+
+        assert_eq!(
+            split_markup(text),
+            vec![
+                (None, "Call "),
+                (Some(Markup::Bold), "*Stacy*"),
+                (None, " about "),
+                (Some(Markup::Italic), "/the/"),
+                (None, " "),
+                (Some(Markup::Verbatim), "=v1.0="),
+                (None, " "),
+                (Some(Markup::Code), "~cargo~"),
+                (None, " "),
+                (Some(Markup::Underline), "_launch_"),
+                (None, ", "),
+                (Some(Markup::StrikeThrough), "+not+"),
+                (None, " "),
+                (Some(Markup::Link), "[[*Tasks][tasks]]"),
+                (None, "\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_markers_that_are_not_emphasis() {
+        for text in [
+            "2 * 3 * 4",
+            "a*b*c and snake_case_names",
+            "https://orgmode.org/manual/",
+            "<2023-10-14 Sat +1w> and 1+1 = 2",
+            "* not closed",
+            "*spans\nlines*",
+            "",
+        ] {
+            assert_eq!(split_markup(text), vec![(None, text)], "{:?}", text);
+        }
+
+        assert_eq!(
+            split_markup("(*a*) =x*y*="),
+            vec![
+                (None, "("),
+                (Some(Markup::Bold), "*a*"),
+                (None, ") "),
+                (Some(Markup::Verbatim), "=x*y*="),
+            ]
+        );
+    }
+}
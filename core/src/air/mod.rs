@@ -1,5 +1,14 @@
+#[allow(clippy::module_inception)]
 mod air;
 mod db_io;
+mod headline;
+mod link;
+mod markup;
+mod timestamp;
 
 pub use air::*;
 pub use db_io::*;
+pub use headline::*;
+pub use link::*;
+pub use markup::*;
+pub use timestamp::*;
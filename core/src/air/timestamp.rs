@@ -0,0 +1,748 @@
+use std::{
+    fmt,
+    ops::RangeInclusive,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::Connection;
+
+/// Keywords that introduce the timestamps of a planning line, such as
+/// `SCHEDULED: <2023-10-14 Sat> DEADLINE: <2023-10-20 Fri>`
+pub const PLANNING_KEYWORDS: &[&str] = &["SCHEDULED", "DEADLINE", "CLOSED"];
+
+/// A day of the proleptic Gregorian calendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    /// Returns None if the date does not exist
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        Some(Date { year, month, day })
+    }
+
+    /// Converts a count of days since 1970-01-01 into a date, using the
+    /// algorithm from Howard Hinnant's chrono-compatible date algorithms
+    fn from_days_since_epoch(days: i64) -> Date {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+
+        let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+        let year = (year_of_era + era * 400 + i64::from(month <= 2)) as i32;
+
+        Date { year, month, day }
+    }
+
+    /// The number of days since 1970-01-01, the inverse of
+    /// `from_days_since_epoch`
+    pub fn days_since_epoch(&self) -> i64 {
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let month_index = if month > 2 { month - 3 } else { month + 9 };
+        let day_of_year = (153 * month_index + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    pub fn add_days(&self, days: i64) -> Date {
+        Date::from_days_since_epoch(self.days_since_epoch() + days)
+    }
+
+    /// Moves the date by `months`, keeping the day of the month, or using
+    /// the last day of the month when it is shorter
+    pub fn add_months(&self, months: i64) -> Date {
+        let index = i64::from(self.year) * 12 + i64::from(self.month) - 1 + months;
+        let year = index.div_euclid(12) as i32;
+        let month = index.rem_euclid(12) as u32 + 1;
+
+        Date {
+            year,
+            month,
+            day: self.day.min(days_in_month(year, month)),
+        }
+    }
+
+    /// The abbreviated English name of the day of the week, as org writes
+    /// it in timestamps
+    pub fn weekday(&self) -> &'static str {
+        // 1970-01-01 was a Thursday
+        ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"]
+            [self.days_since_epoch().rem_euclid(7) as usize]
+    }
+
+    /// The current date in the local time zone. SQLite already knows the
+    /// time zone, so it is asked for the date. Falls back to the date in
+    /// UTC should that fail.
+    pub fn today() -> Date {
+        let local = Connection::open_in_memory()
+            .and_then(|connection| {
+                connection.query_row("SELECT date('now', 'localtime')", [], |row| {
+                    row.get::<_, String>(0)
+                })
+            })
+            .ok()
+            .and_then(|date| Date::parse(&date));
+
+        local.unwrap_or_else(|| {
+            let seconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs() as i64);
+
+            Date::from_days_since_epoch(seconds.div_euclid(86_400))
+        })
+    }
+
+    /// Parses a `YYYY-MM-DD` date
+    pub fn parse(text: &str) -> Option<Date> {
+        let mut parts = text.splitn(3, '-');
+        let year = parts.next()?;
+        let month = parts.next()?;
+        let day = parts.next()?;
+
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+
+        if !(year.chars().chain(month.chars()).chain(day.chars())).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// A time of day, to the minute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl Time {
+    /// Parses a `H:MM` or `HH:MM` time
+    fn parse(text: &str) -> Option<Time> {
+        let (hour, minute) = text.split_once(':')?;
+
+        if hour.is_empty() || hour.len() > 2 || minute.len() != 2 {
+            return None;
+        }
+
+        if !hour.chars().chain(minute.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let time = Time {
+            hour: hour.parse().ok()?,
+            minute: minute.parse().ok()?,
+        };
+
+        (time.hour < 24 && time.minute < 60).then_some(time)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// A date with an optional time. Dates without a time sort before the same
+/// date with one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    pub date: Date,
+    pub time: Option<Time>,
+}
+
+impl DateTime {
+    /// Parses a string written by `to_iso`
+    pub fn from_iso(text: &str) -> Option<DateTime> {
+        let (date, time) = match text.split_once('T') {
+            Some((date, time)) => (date, Some(Time::parse(time)?)),
+            None => (text, None),
+        };
+
+        Some(DateTime {
+            date: Date::parse(date)?,
+            time,
+        })
+    }
+
+    /// Formats as ISO 8601, `2023-10-14` or `2023-10-14T10:00`, which sorts
+    /// in chronological order
+    pub fn to_iso(&self) -> String {
+        match self.time {
+            Some(time) => format!("{}T{}", self.date, time),
+            None => self.date.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeaterKind {
+    /// `+`, shifts the timestamp by the interval once
+    Cumulative,
+    /// `++`, shifts the timestamp by the interval until it is in the future
+    CatchUp,
+    /// `.+`, shifts the timestamp to the interval after today
+    Restart,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeaterUnit {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// How often a timestamp repeats, such as `+1w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repeater {
+    pub kind: RepeaterKind,
+    pub value: u32,
+    pub unit: RepeaterUnit,
+}
+
+/// Parses the value and unit of a repeater or warning delay, such as `1w`
+fn parse_interval(text: &str) -> Option<(u32, RepeaterUnit)> {
+    let unit = match text.chars().last()? {
+        'h' => RepeaterUnit::Hour,
+        'd' => RepeaterUnit::Day,
+        'w' => RepeaterUnit::Week,
+        'm' => RepeaterUnit::Month,
+        'y' => RepeaterUnit::Year,
+        _ => return None,
+    };
+    let value = &text[..text.len() - 1];
+
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some((value.parse().ok()?, unit))
+}
+
+impl Repeater {
+    /// Parses a repeater such as `+1w`, `++2d` or `.+1m`
+    pub fn parse(text: &str) -> Option<Repeater> {
+        let (kind, interval) = if let Some(interval) = text.strip_prefix("++") {
+            (RepeaterKind::CatchUp, interval)
+        } else if let Some(interval) = text.strip_prefix(".+") {
+            (RepeaterKind::Restart, interval)
+        } else {
+            (RepeaterKind::Cumulative, text.strip_prefix('+')?)
+        };
+
+        let (value, unit) = parse_interval(interval)?;
+        Some(Repeater { kind, value, unit })
+    }
+}
+
+impl fmt::Display for Repeater {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            RepeaterKind::Cumulative => "+",
+            RepeaterKind::CatchUp => "++",
+            RepeaterKind::Restart => ".+",
+        };
+        let unit = match self.unit {
+            RepeaterUnit::Hour => 'h',
+            RepeaterUnit::Day => 'd',
+            RepeaterUnit::Week => 'w',
+            RepeaterUnit::Month => 'm',
+            RepeaterUnit::Year => 'y',
+        };
+
+        write!(f, "{}{}{}", kind, self.value, unit)
+    }
+}
+
+/// An org timestamp, such as `<2023-10-14 Sat 10:00-11:30 +1w>` or
+/// `[2023-10-14]--[2023-10-16]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Active timestamps are written in `<>`, inactive ones in `[]`
+    pub active: bool,
+    pub start: DateTime,
+    /// The end of a time range or date range
+    pub end: Option<DateTime>,
+    pub repeater: Option<Repeater>,
+}
+
+impl Timestamp {
+    /// Parses a single bracketed timestamp at the start of `text`, returning
+    /// it and its length in bytes
+    fn parse_single(text: &str) -> Option<(Timestamp, usize)> {
+        let (active, close) = match text.chars().next()? {
+            '<' => (true, '>'),
+            '[' => (false, ']'),
+            _ => return None,
+        };
+
+        let length = text.find(close)? + 1;
+        let mut parts = text[1..length - 1].split_whitespace();
+
+        let date = Date::parse(parts.next()?)?;
+        let mut timestamp = Timestamp {
+            active,
+            start: DateTime { date, time: None },
+            end: None,
+            repeater: None,
+        };
+
+        for part in parts {
+            if part.starts_with(|c: char| c.is_ascii_digit()) {
+                if timestamp.start.time.is_some() {
+                    return None;
+                }
+
+                let (start, end) = match part.split_once('-') {
+                    Some((start, end)) => (start, Some(end)),
+                    None => (part, None),
+                };
+
+                timestamp.start.time = Some(Time::parse(start)?);
+                if let Some(end) = end {
+                    timestamp.end = Some(DateTime {
+                        date,
+                        time: Some(Time::parse(end)?),
+                    });
+                }
+            } else if part.starts_with(['+', '.']) {
+                timestamp.repeater = Some(Repeater::parse(part)?);
+            } else if let Some(delay) = part.strip_prefix('-') {
+                // Warning delays only affect the agenda in org, so they are
+                // checked but not kept
+                parse_interval(delay.strip_prefix('-').unwrap_or(delay))?;
+            } else if !part.chars().all(|c| c.is_alphabetic() || c == '.') {
+                // Anything else must be the name of the day
+                return None;
+            }
+        }
+
+        Some((timestamp, length))
+    }
+
+    /// Parses a timestamp or `<start>--<end>` range at the start of `text`,
+    /// returning it and its length in bytes
+    pub fn parse_prefix(text: &str) -> Option<(Timestamp, usize)> {
+        let (mut timestamp, length) = Timestamp::parse_single(text)?;
+
+        if let Some(rest) = text[length..].strip_prefix("--") {
+            if let Some((end, end_length)) = Timestamp::parse_single(rest) {
+                if end.active == timestamp.active && timestamp.end.is_none() {
+                    timestamp.end = Some(end.start);
+                    return Some((timestamp, length + 2 + end_length));
+                }
+            }
+        }
+
+        Some((timestamp, length))
+    }
+
+    /// Parses text that holds nothing but a timestamp, ignoring surrounding
+    /// whitespace
+    pub fn parse(text: &str) -> Option<Timestamp> {
+        let text = text.trim();
+        let (timestamp, length) = Timestamp::parse_prefix(text)?;
+
+        (length == text.len()).then_some(timestamp)
+    }
+
+    /// Moves the timestamp by `count` intervals of `repeater`
+    fn repeat(&self, repeater: Repeater, count: i64) -> Timestamp {
+        let value = i64::from(repeater.value) * count;
+
+        let shift = |date_time: DateTime| match repeater.unit {
+            RepeaterUnit::Hour => {
+                let time = date_time.time.unwrap_or(Time { hour: 0, minute: 0 });
+                let minutes = i64::from(time.hour * 60 + time.minute) + value * 60;
+
+                DateTime {
+                    date: date_time.date.add_days(minutes.div_euclid(1_440)),
+                    time: date_time.time.map(|_| Time {
+                        hour: (minutes.rem_euclid(1_440) / 60) as u32,
+                        minute: minutes.rem_euclid(60) as u32,
+                    }),
+                }
+            }
+            RepeaterUnit::Day => DateTime {
+                date: date_time.date.add_days(value),
+                ..date_time
+            },
+            RepeaterUnit::Week => DateTime {
+                date: date_time.date.add_days(value * 7),
+                ..date_time
+            },
+            RepeaterUnit::Month => DateTime {
+                date: date_time.date.add_months(value),
+                ..date_time
+            },
+            RepeaterUnit::Year => DateTime {
+                date: date_time.date.add_months(value * 12),
+                ..date_time
+            },
+        };
+
+        Timestamp {
+            start: shift(self.start),
+            end: self.end.map(shift),
+            ..*self
+        }
+    }
+
+    /// Every occurrence of the timestamp starting within `range`. A
+    /// repeating timestamp occurs on its date and after every interval
+    /// since, whichever kind of repeater it has, as the kinds only differ
+    /// in how org moves the date once the headline is done.
+    pub fn occurrences(&self, range: &RangeInclusive<Date>) -> Vec<Timestamp> {
+        let Some(repeater) = self.repeater.filter(|repeater| repeater.value > 0) else {
+            return if range.contains(&self.start.date) { vec![*self] } else { Vec::new() };
+        };
+
+        // Skips the intervals that certainly end before the range, taking
+        // months and years at their longest
+        let gap = range.start().days_since_epoch() - self.start.date.days_since_epoch();
+        let value = i64::from(repeater.value);
+        let mut count = match repeater.unit {
+            _ if gap <= 0 => 0,
+            RepeaterUnit::Hour => (gap - 1) * 24 / value,
+            RepeaterUnit::Day => gap / value,
+            RepeaterUnit::Week => gap / (value * 7),
+            RepeaterUnit::Month => gap / (value * 31),
+            RepeaterUnit::Year => gap / (value * 366),
+        };
+
+        let mut occurrences = Vec::new();
+
+        loop {
+            let occurrence = self.repeat(repeater, count);
+
+            if occurrence.start.date > *range.end() {
+                return occurrences;
+            }
+            if occurrence.start.date >= *range.start() {
+                occurrences.push(occurrence);
+            }
+
+            count += 1;
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Writes the timestamp as org would, with the day of the week
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (open, close) = if self.active { ('<', '>') } else { ('[', ']') };
+
+        let write_single = |f: &mut fmt::Formatter, date_time: &DateTime, end: Option<Time>| {
+            write!(f, "{}{} {}", open, date_time.date, date_time.date.weekday())?;
+            if let Some(time) = date_time.time {
+                write!(f, " {}", time)?;
+            }
+            if let Some(end) = end {
+                write!(f, "-{}", end)?;
+            }
+            if let Some(repeater) = self.repeater {
+                write!(f, " {}", repeater)?;
+            }
+            write!(f, "{}", close)
+        };
+
+        match self.end {
+            // Times on the same day are written as a single time range
+            Some(end) if end.date == self.start.date && self.start.time.is_some() => {
+                write_single(f, &self.start, end.time)
+            }
+            Some(end) => {
+                write_single(f, &self.start, None)?;
+                write!(f, "--")?;
+                write_single(f, &end, None)
+            }
+            None => write_single(f, &self.start, None),
+        }
+    }
+}
+
+/// Finds every timestamp in `text`, along with its byte offset
+pub fn find_timestamps(text: &str) -> Vec<(usize, Timestamp)> {
+    let mut timestamps = Vec::new();
+    let mut index = 0;
+
+    while let Some(offset) = text[index..].find(['<', '[']) {
+        let start = index + offset;
+
+        match Timestamp::parse_prefix(&text[start..]) {
+            Some((timestamp, length)) => {
+                timestamps.push((start, timestamp));
+                index = start + length;
+            }
+            None => index = start + 1,
+        }
+    }
+
+    timestamps
+}
+
+/// The timestamps of a planning line, which follows a headline to say when
+/// it is scheduled, due or was closed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Planning {
+    pub scheduled: Option<Timestamp>,
+    pub deadline: Option<Timestamp>,
+    pub closed: Option<Timestamp>,
+}
+
+impl Planning {
+    /// Parses a line made up only of planning keywords, each followed by a
+    /// timestamp
+    pub fn parse(line: &str) -> Option<Planning> {
+        let mut planning = Planning::default();
+        let mut rest = line.trim();
+
+        if rest.is_empty() {
+            return None;
+        }
+
+        while !rest.is_empty() {
+            let (keyword, after) = rest.split_once(':')?;
+            let (timestamp, length) = Timestamp::parse_prefix(after.trim_start())?;
+
+            let slot = match keyword {
+                "SCHEDULED" => &mut planning.scheduled,
+                "DEADLINE" => &mut planning.deadline,
+                "CLOSED" => &mut planning.closed,
+                _ => return None,
+            };
+            *slot = Some(timestamp);
+
+            rest = after.trim_start()[length..].trim_start();
+        }
+
+        Some(planning)
+    }
+}
+
+/// Returns the planning keyword that introduces the timestamp at `offset`
+/// in `text`, if there is one
+pub fn planning_keyword(text: &str, offset: usize) -> Option<&'static str> {
+    let before = text[..offset].trim_end().strip_suffix(':')?;
+
+    PLANNING_KEYWORDS
+        .iter()
+        .find(|keyword| before.ends_with(*keyword))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date_time(year: i32, month: u32, day: u32, time: Option<(u32, u32)>) -> DateTime {
+        DateTime {
+            date: Date::new(year, month, day).unwrap(),
+            time: time.map(|(hour, minute)| Time { hour, minute }),
+        }
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        assert_eq!(
+            Timestamp::parse("<2023-10-14>"),
+            Some(Timestamp {
+                active: true,
+                start: date_time(2023, 10, 14, None),
+                end: None,
+                repeater: None,
+            })
+        );
+
+        assert_eq!(
+            Timestamp::parse("[2023-10-14 Sat 9:05-11:30 .+1w -2d]"),
+            Some(Timestamp {
+                active: false,
+                start: date_time(2023, 10, 14, Some((9, 5))),
+                end: Some(date_time(2023, 10, 14, Some((11, 30)))),
+                repeater: Some(Repeater {
+                    kind: RepeaterKind::Restart,
+                    value: 1,
+                    unit: RepeaterUnit::Week,
+                }),
+            })
+        );
+
+        let range = Timestamp::parse("<2023-10-14 Sat>--<2023-10-16 Mon 18:00>").unwrap();
+        assert_eq!(range.end, Some(date_time(2023, 10, 16, Some((18, 0)))));
+
+        for invalid in [
+            "<2023-02-29>",
+            "<2023-10-14 25:00>",
+            "<2023-10-14 +1x>",
+            "<2023-10-14",
+            "2023-10-14",
+            "<2023-10-14> trailing",
+        ] {
+            assert_eq!(Timestamp::parse(invalid), None, "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn format_sortable_iso_strings() {
+        let mut values = [
+            date_time(2024, 1, 2, None),
+            date_time(2023, 10, 14, Some((9, 5))),
+            date_time(2023, 10, 14, None),
+        ];
+        values.sort();
+
+        let iso: Vec<String> = values.iter().map(DateTime::to_iso).collect();
+        let mut sorted = iso.clone();
+        sorted.sort();
+
+        assert_eq!(iso, vec!["2023-10-14", "2023-10-14T09:05", "2024-01-02"]);
+        assert_eq!(iso, sorted);
+
+        let repeater = Timestamp::parse("<2023-10-14 ++2m>").unwrap().repeater.unwrap();
+        assert_eq!(repeater.to_string(), "++2m");
+    }
+
+    #[test]
+    fn convert_days_since_epoch() {
+        assert_eq!(Date::from_days_since_epoch(0), Date::new(1970, 1, 1).unwrap());
+        assert_eq!(Date::from_days_since_epoch(19_644), Date::new(2023, 10, 14).unwrap());
+        assert_eq!(Date::from_days_since_epoch(11_016), Date::new(2000, 2, 29).unwrap());
+        assert_eq!(Date::from_days_since_epoch(-1), Date::new(1969, 12, 31).unwrap());
+    }
+
+    #[test]
+    fn count_days_and_weekdays() {
+        let date = Date::new(2023, 10, 14).unwrap();
+
+        assert_eq!(date.days_since_epoch(), 19_644);
+        assert_eq!(date.weekday(), "Sat");
+        assert_eq!(date.add_days(18), Date::new(2023, 11, 1).unwrap());
+        assert_eq!(date.add_days(-19_644).weekday(), "Thu");
+        assert_eq!(Date::new(2024, 2, 29).unwrap().add_days(365).to_string(), "2025-02-28");
+    }
+
+    #[test]
+    fn repeat_timestamps_within_range() {
+        let dates = |text: &str, start: &str, end: &str| -> Vec<String> {
+            let range = Date::parse(start).unwrap()..=Date::parse(end).unwrap();
+
+            Timestamp::parse(text)
+                .unwrap()
+                .occurrences(&range)
+                .iter()
+                .map(|timestamp| timestamp.to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            dates("<2029-12-30 +3d>", "2030-01-01", "2030-01-10"),
+            vec!["<2030-01-02 Wed +3d>", "<2030-01-05 Sat +3d>", "<2030-01-08 Tue +3d>"]
+        );
+        assert_eq!(
+            dates("<2024-01-31 Wed 09:00-10:00 ++1m>", "2024-02-01", "2024-04-30"),
+            vec![
+                "<2024-02-29 Thu 09:00-10:00 ++1m>",
+                "<2024-03-31 Sun 09:00-10:00 ++1m>",
+                "<2024-04-30 Tue 09:00-10:00 ++1m>",
+            ]
+        );
+        assert_eq!(
+            dates("<2023-10-14 Sat 22:00 .+12h>", "2030-01-01", "2030-01-01"),
+            vec!["<2030-01-01 Tue 10:00 .+12h>", "<2030-01-01 Tue 22:00 .+12h>"]
+        );
+        assert_eq!(
+            dates("<2020-02-29 +1y>", "2021-01-01", "2021-12-31"),
+            vec!["<2021-02-28 Sun +1y>"]
+        );
+        assert_eq!(dates("<2030-01-05>", "2030-01-01", "2030-01-10"), vec!["<2030-01-05 Sat>"]);
+        assert!(dates("<2030-01-05 +1w>", "2030-01-01", "2030-01-04").is_empty());
+    }
+
+    #[test]
+    fn today_is_within_a_day_of_utc() {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let utc = seconds.div_euclid(86_400);
+
+        assert!((Date::today().days_since_epoch() - utc).abs() <= 1);
+    }
+
+    #[test]
+    fn write_timestamps_as_org() {
+        for text in [
+            "<2023-10-14 Sat>",
+            "[2023-10-14 Sat 09:05-11:30 .+1w]",
+            "<2023-10-14 Sat>--<2023-10-16 Mon 18:00>",
+        ] {
+            assert_eq!(Timestamp::parse(text).unwrap().to_string(), text);
+        }
+
+        let stored = DateTime::from_iso("2023-10-14T09:05").unwrap();
+        assert_eq!(stored, date_time(2023, 10, 14, Some((9, 5))));
+        assert_eq!(DateTime::from_iso(&stored.to_iso()), Some(stored));
+    }
+
+    #[test]
+    fn parse_planning_lines() {
+        let planning =
+            Planning::parse("  SCHEDULED: <2023-10-14 Sat> DEADLINE: <2023-10-20 Fri 17:00>\n")
+                .unwrap();
+
+        assert_eq!(planning.scheduled.unwrap().start, date_time(2023, 10, 14, None));
+        assert_eq!(
+            planning.deadline.unwrap().start,
+            date_time(2023, 10, 20, Some((17, 0)))
+        );
+        assert_eq!(planning.closed, None);
+
+        assert_eq!(Planning::parse("Meeting: <2023-10-14>"), None);
+        assert_eq!(Planning::parse("DEADLINE: soon"), None);
+
+        let line = "Call Stacy DEADLINE: <2023-10-20> about [2023-10-01]";
+        let keywords: Vec<Option<&str>> = find_timestamps(line)
+            .into_iter()
+            .map(|(offset, _)| planning_keyword(line, offset))
+            .collect();
+        assert_eq!(keywords, vec![Some("DEADLINE"), None]);
+    }
+}
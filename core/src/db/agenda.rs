@@ -0,0 +1,384 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{anyhow, Context};
+use bumpalo::Bump;
+
+use crate::air::{Block, CategoryBlock, Date, DateTime, PropertyBlock, Repeater, Timestamp};
+
+use super::AmbleDB;
+
+/// A headline with a scheduled, deadline or due date
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgendaItem {
+    /// Id of the top level category the headline was found in
+    pub category_id: i64,
+    pub category_name: String,
+    /// Headlines from beneath the top level category down to the headline
+    pub path: Vec<String>,
+    /// The full text of the headline
+    pub headline: String,
+    /// What the date means for the headline, such as `DEADLINE`, or the
+    /// key of the property it was found in
+    pub label: String,
+    pub timestamp: Timestamp,
+    /// The headline is still open, though its date has passed
+    pub overdue: bool,
+}
+
+impl AgendaItem {
+    pub fn date(&self) -> Date {
+        self.timestamp.start.date
+    }
+}
+
+/// Reads a timestamp back from the columns it was stored in
+fn stored_timestamp(
+    active: bool,
+    starts_at: &str,
+    ends_at: Option<&str>,
+    repeater: Option<&str>,
+) -> Option<Timestamp> {
+    Some(Timestamp {
+        active,
+        start: DateTime::from_iso(starts_at)?,
+        end: match ends_at {
+            Some(ends_at) => Some(DateTime::from_iso(ends_at)?),
+            None => None,
+        },
+        repeater: match repeater {
+            Some(repeater) => Some(Repeater::parse(repeater)?),
+            None => None,
+        },
+    })
+}
+
+impl AmbleDB {
+    /// Lists every headline scheduled, due or with a deadline within
+    /// `range`, ordered by date. When the range includes today, open TODO
+    /// items whose dates passed before the range are listed too. Items
+    /// still open after their date are flagged as overdue. Repeating dates
+    /// are listed on each day they repeat on within the range instead.
+    pub fn agenda_items(
+        &self,
+        range: RangeInclusive<Date>,
+    ) -> Result<Vec<AgendaItem>, anyhow::Error> {
+        let today = Date::today();
+
+        let mut stmt = self
+            .connection
+            .prepare_cached(
+                "
+            SELECT cb.id, cb.todo_keyword, t.label, t.active, t.starts_at, t.ends_at, t.repeater
+            FROM timestamps t
+            LEFT JOIN text_blocks tb ON tb.id = t.text_block_id
+            LEFT JOIN rich_text_blocks rtb ON rtb.id = tb.parent_rich_text_block_id
+            LEFT JOIN properties p ON p.id = t.property_id
+            LEFT JOIN drawers d ON d.id = p.parent_drawer_id
+            INNER JOIN category_blocks cb
+                ON cb.id = COALESCE(
+                    tb.parent_category_id, rtb.parent_category_id, p.parent_category_id,
+                    d.parent_category_id
+                )
+            WHERE upper(t.label) IN ('SCHEDULED', 'DEADLINE', 'DUE')
+                AND substr(t.starts_at, 1, 10) <= ?2
+                AND (substr(t.starts_at, 1, 10) >= ?1 OR t.repeater IS NOT NULL
+                    OR (?3 AND cb.todo_keyword IS NOT NULL AND cb.todo_keyword != 'DONE'))
+            ORDER BY t.starts_at, cb.id",
+            )
+            .context("Could not prepare agenda statement")?;
+
+        let rows = stmt
+            .query_map(
+                (
+                    range.start().to_string(),
+                    range.end().to_string(),
+                    range.contains(&today),
+                ),
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, bool>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, Option<String>>(5)?,
+                        row.get::<_, Option<String>>(6)?,
+                    ))
+                },
+            )
+            .context("Could not query agenda")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Could not read agenda item")?;
+
+        let mut items: Vec<(i64, AgendaItem)> = Vec::new();
+
+        for (headline_id, todo_keyword, label, active, starts_at, ends_at, repeater) in rows {
+            let timestamp =
+                stored_timestamp(active, &starts_at, ends_at.as_deref(), repeater.as_deref())
+                    .ok_or_else(|| anyhow!("Stored timestamp {} is malformed", starts_at))?;
+
+            // Dates without a repeater were already picked by the query
+            let occurrences = match timestamp.repeater {
+                Some(_) => timestamp.occurrences(&range),
+                None => vec![timestamp],
+            };
+
+            if occurrences.is_empty() {
+                continue;
+            }
+
+            let Some(location) = self.locate(Some(headline_id))? else {
+                continue;
+            };
+
+            // The headline itself ends the path, unless it is the top level
+            // category
+            let mut path = location.path;
+            let headline = path.pop().unwrap_or_else(|| location.category_name.clone());
+
+            let open = todo_keyword.is_some_and(|keyword| keyword != "DONE");
+
+            for timestamp in occurrences {
+                items.push((
+                    headline_id,
+                    AgendaItem {
+                        category_id: location.category_id,
+                        category_name: location.category_name.clone(),
+                        path: path.clone(),
+                        headline: headline.clone(),
+                        label: label.clone(),
+                        overdue: open && timestamp.start.date < today,
+                        timestamp,
+                    },
+                ));
+            }
+        }
+
+        // Repeated dates no longer follow the order of the query
+        items.sort_by_key(|(headline_id, item)| (item.timestamp.start, *headline_id));
+
+        Ok(items.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// Builds the agenda for `range` as AIR, with a category for each day
+    /// holding the headlines on that day. Each headline lists the date that
+    /// put it on the agenda, the top level category it was found in, and
+    /// how long it has been overdue. Text is allocated in `bump`.
+    pub fn agenda<'a>(
+        &self,
+        bump: &'a Bump,
+        range: RangeInclusive<Date>,
+    ) -> Result<Vec<Block<'a>>, anyhow::Error> {
+        let today = Date::today();
+        let mut days: Vec<Block<'a>> = Vec::new();
+
+        for item in self.agenda_items(range)? {
+            let date = item.date();
+            let day_name: &'a str = bump.alloc_str(&format!("{} {}\n", date, date.weekday()));
+
+            let property = |key: &str, value: String| {
+                Block::Property(PropertyBlock::new(bump.alloc_str(key), bump.alloc_str(&value)))
+            };
+
+            let mut children = vec![
+                property(&item.label, item.timestamp.to_string()),
+                property("Category", item.category_name),
+            ];
+
+            if item.overdue {
+                let days_overdue = today.days_since_epoch() - date.days_since_epoch();
+                let unit = if days_overdue == 1 { "day" } else { "days" };
+                children.push(property("Overdue", format!("{} {}", days_overdue, unit)));
+            }
+
+            let headline = CategoryBlock::new(
+                bump.alloc_str(&format!("{}\n", item.headline)),
+                2,
+                children,
+            );
+
+            match days.last_mut() {
+                Some(Block::Category(day)) if day.name == day_name => {
+                    day.children.push(Block::Category(headline))
+                }
+                _ => days.push(Block::Category(CategoryBlock::new(
+                    day_name,
+                    1,
+                    vec![Block::Category(headline)],
+                ))),
+            }
+        }
+
+        Ok(days)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+
+    use crate::{
+        air::Date,
+        db::{save_document, AmbleDB},
+        rendering::render_to_org,
+    };
+
+    fn day(date: Date) -> String {
+        format!("{} {}", date, date.weekday())
+    }
+
+    #[test]
+    fn list_items_by_day() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+        let today = Date::today();
+        let tomorrow = today.add_days(1);
+        let last_week = today.add_days(-7);
+
+        let work = save_document(
+            &mut db,
+            None,
+            "Work",
+            &format!(
+                "* Website
+** TODO Ship the homepage
+DEADLINE: <{}>
+** DONE Write the brief
+SCHEDULED: <{}>
+** TODO Review the designs
+:Due: <{} 10:00>
+* Meeting on <{}>
+",
+                day(tomorrow),
+                day(last_week),
+                day(today),
+                day(today),
+            ),
+        )
+        .category_id;
+        save_document(
+            &mut db,
+            None,
+            "Home",
+            &format!(
+                "* TODO Call the bank
+SCHEDULED: <{}>
+* TODO Someday
+DEADLINE: <{}>
+",
+                day(last_week),
+                day(today.add_days(30)),
+            ),
+        );
+
+        let items = db.agenda_items(today..=today.add_days(7)).unwrap();
+        let summary: Vec<(&str, &str, Date, bool)> = items
+            .iter()
+            .map(|item| (item.headline.as_str(), item.label.as_str(), item.date(), item.overdue))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("TODO Call the bank", "SCHEDULED", last_week, true),
+                ("TODO Review the designs", "Due", today, false),
+                ("TODO Ship the homepage", "DEADLINE", tomorrow, false),
+            ]
+        );
+        assert_eq!(items[2].category_id, work);
+        assert_eq!(items[2].path, vec!["Website"]);
+
+        // Past ranges do not pull in overdue items from before them
+        let past = db.agenda_items(last_week..=last_week).unwrap();
+        assert_eq!(past.len(), 2);
+        assert!(past.iter().all(|item| item.date() == last_week));
+
+        let bump = Bump::new();
+        let rendered: String = db
+            .agenda(&bump, today..=tomorrow)
+            .unwrap()
+            .into_iter()
+            .map(render_to_org)
+            .collect();
+
+        assert_eq!(
+            rendered,
+            format!(
+                "* {last_week}
+** TODO Call the bank
+:SCHEDULED: <{last_week}>
+:Category: Home
+:Overdue: 7 days
+* {today}
+** TODO Review the designs
+:Due: <{today} 10:00>
+:Category: Work
+* {tomorrow}
+** TODO Ship the homepage
+:DEADLINE: <{tomorrow}>
+:Category: Work
+",
+                last_week = day(last_week),
+                today = day(today),
+                tomorrow = day(tomorrow),
+            )
+        );
+
+        assert!(db.agenda(&bump, tomorrow..=today).unwrap().is_empty());
+    }
+
+    #[test]
+    fn repeat_items_within_range() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+        let today = Date::today();
+        let last_week = today.add_days(-7);
+
+        save_document(
+            &mut db,
+            None,
+            "Home",
+            &format!(
+                "* TODO Water the plants
+SCHEDULED: <{} +1d>
+* TODO Pay the rent
+DEADLINE: <2029-11-05 Mon ++1m>
+* TODO Clean the gutters
+SCHEDULED: <2029-12-25 Tue .+1w>
+",
+                day(last_week),
+            ),
+        );
+
+        // Daily items come back each day rather than staying overdue
+        let items = db.agenda_items(today..=today.add_days(1)).unwrap();
+        let summary: Vec<(&str, Date, bool)> = items
+            .iter()
+            .map(|item| (item.headline.as_str(), item.date(), item.overdue))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("TODO Water the plants", today, false),
+                ("TODO Water the plants", today.add_days(1), false),
+            ]
+        );
+        assert_eq!(items[1].timestamp.to_string(), format!("<{} +1d>", day(today.add_days(1))));
+
+        let start = Date::new(2030, 1, 1).unwrap();
+        let items = db.agenda_items(start..=start.add_days(9)).unwrap();
+        let summary: Vec<(&str, String)> = items
+            .iter()
+            .filter(|item| !item.headline.contains("Water"))
+            .map(|item| (item.headline.as_str(), item.date().to_string()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("TODO Clean the gutters", "2030-01-01".to_string()),
+                ("TODO Pay the rent", "2030-01-05".to_string()),
+                ("TODO Clean the gutters", "2030-01-08".to_string()),
+            ]
+        );
+    }
+}
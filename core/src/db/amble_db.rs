@@ -1,32 +1,57 @@
-use std::env;
+use std::{collections::HashMap, env, path::Path};
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use rusqlite::Connection;
 
-use crate::{air::CategoryBlock, air::DbCategoryBlock};
-
-use super::schema::AMBLE_DB_SCHEMA;
+use crate::{
+    air::{
+        CategoryBlock, DbCategoryBlock, DbIO, DbListItemBlock, DbPropertyBlock, DbTableCellBlock,
+        DbTextBlock,
+    },
+    matching::{CaptureEdit, CaptureSource},
+};
+
+use super::{
+    diff::{save_category, ChangeSummary},
+    links::{last_link_id, resolve_changed_links},
+    migrations::migrate,
+    DbBlockMatrix,
+};
 
 pub struct AmbleDB {
     pub connection: Connection,
 }
 
-impl<'a> AmbleDB {
+impl AmbleDB {
+    /// Opens the database at `filename`, creating it if it does not exist,
+    /// and migrates it to the latest schema. Relative paths are resolved
+    /// against the current directory, and `:memory:` opens a fresh
+    /// in-memory database.
     pub fn new(filename: &str) -> Result<Self, anyhow::Error> {
-        let current_dir = match env::current_dir() {
-            Ok(path) => path,
-            Err(error) => panic!("Could not get current directory {error:?}"),
+        if filename == ":memory:" {
+            let mut connection =
+                Connection::open_in_memory().context("Could not open in memory database")?;
+            migrate(&mut connection).context("Could not migrate database")?;
+
+            return Ok(AmbleDB { connection });
+        }
+
+        let path = Path::new(filename);
+        let db_path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            env::current_dir()
+                .context("Could not get current directory")?
+                .join(path)
         };
-        let db_path = current_dir.join(filename);
-        let db_existed = db_path.exists();
 
-        let connection = Connection::open(db_path)?;
+        let mut connection = Connection::open(&db_path)
+            .context(format!("Could not open database at {}", db_path.display()))?;
 
-        if !db_existed {
-            connection.execute_batch(AMBLE_DB_SCHEMA)?;
-        }
+        migrate(&mut connection)
+            .context(format!("Could not migrate database at {}", db_path.display()))?;
 
-        return Ok(AmbleDB { connection });
+        Ok(AmbleDB { connection })
     }
 
     pub fn get_top_level_categories(&mut self) -> Result<Vec<DbCategoryBlock>, anyhow::Error> {
@@ -48,6 +73,7 @@ impl<'a> AmbleDB {
                     id: row.get(0)?,
                     name: row.get(1)?,
                     parent_category_id: None,
+                    position: 0,
                 })
             })
             .context("Could not prepare select statement query map")?;
@@ -57,31 +83,170 @@ impl<'a> AmbleDB {
             categories.push(category);
         }
 
-        return Ok(categories);
+        Ok(categories)
     }
 
+    /// Saves a top level category. When the category already exists, the
+    /// new tree is diffed against the stored one and only the blocks that
+    /// were added, changed, moved or removed are written, so blocks keep
+    /// their ids across saves.
     pub fn write_top_level_category(
         &mut self,
         category: &CategoryBlock,
-    ) -> Result<i64, anyhow::Error> {
+    ) -> Result<ChangeSummary, anyhow::Error> {
         // NOTE: Synchronous off is used to significantly increase our write
         // speeds as far as this program is concerned. Has some safety/data
         // integrity implications
 
         self.connection.execute("PRAGMA synchronous = OFF", ())?;
 
+        let stored = match category.id {
+            Some(id) => DbBlockMatrix::new(&self.connection, id)
+                .context(format!("Could not load stored category with id {}", id))?
+                .produce_flat_db_block_vec()
+                .context("Could not produce flat vec of stored blocks")?,
+            None => Vec::new(),
+        };
+
         let tx = self
             .connection
             .transaction()
             .context("Could not create a new transaction")?;
 
-        category
-            .write_to_db(&tx, None)
-            .context("Should be able to write categroy to db")?;
+        let last_link_id = last_link_id(&tx)?;
+        let summary =
+            save_category(&tx, category, stored).context("Could not save category to db")?;
+
+        resolve_changed_links(&tx, last_link_id).context("Could not resolve links")?;
 
-        let last_id = tx.last_insert_rowid();
         tx.commit().context("Could not commit transaction")?;
 
-        Ok(last_id)
+        Ok(summary)
     }
+
+    /// Writes edits made to the results of a fold back to the rows the
+    /// edited values were captured from, in a single transaction. Returns
+    /// the number of rows that were rewritten.
+    pub fn write_capture_edits(&mut self, edits: &[CaptureEdit]) -> Result<usize, anyhow::Error> {
+        let mut edits_by_source: HashMap<CaptureSource, Vec<&CaptureEdit>> = HashMap::new();
+
+        for edit in edits {
+            edits_by_source.entry(edit.source).or_default().push(edit);
+        }
+
+        let tx = self
+            .connection
+            .transaction()
+            .context("Could not create a new transaction")?;
+
+        let last_link_id = last_link_id(&tx)?;
+
+        for (source, source_edits) in &mut edits_by_source {
+            // Splice from the end of the text so earlier spans stay valid
+            source_edits.sort_by_key(|edit| std::cmp::Reverse(edit.span.0));
+
+            match source {
+                CaptureSource::Category(id) => {
+                    let mut db_category = DbCategoryBlock::zero();
+                    db_category
+                        .select(&tx, *id)
+                        .context(format!("Could not select category block with id {}", id))?;
+
+                    if db_category.id != Some(*id) {
+                        return Err(anyhow!("Category block with id {} no longer exists", id));
+                    }
+
+                    db_category.name = apply_edits(&db_category.name, source_edits)
+                        .context(format!("Could not edit category block with id {}", id))?;
+                    db_category.write(&tx)?;
+                }
+                CaptureSource::Text(id) => {
+                    let mut db_text = DbTextBlock::zero();
+                    db_text
+                        .select(&tx, *id)
+                        .context(format!("Could not select text block with id {}", id))?;
+
+                    if db_text.id != Some(*id) {
+                        return Err(anyhow!("Text block with id {} no longer exists", id));
+                    }
+
+                    db_text.content = apply_edits(&db_text.content, source_edits)
+                        .context(format!("Could not edit text block with id {}", id))?;
+                    db_text.write(&tx)?;
+                }
+                CaptureSource::Property(id) => {
+                    let mut db_property = DbPropertyBlock::zero();
+                    db_property
+                        .select(&tx, *id)
+                        .context(format!("Could not select property with id {}", id))?;
+
+                    if db_property.id != Some(*id) {
+                        return Err(anyhow!("Property with id {} no longer exists", id));
+                    }
+
+                    db_property.value = apply_edits(&db_property.value, source_edits)
+                        .context(format!("Could not edit property with id {}", id))?;
+                    db_property.write(&tx)?;
+                }
+                CaptureSource::ListItem(id) => {
+                    let mut db_list_item = DbListItemBlock::zero();
+                    db_list_item
+                        .select(&tx, *id)
+                        .context(format!("Could not select list item with id {}", id))?;
+
+                    if db_list_item.id != Some(*id) {
+                        return Err(anyhow!("List item with id {} no longer exists", id));
+                    }
+
+                    db_list_item.content = apply_edits(&db_list_item.content, source_edits)
+                        .context(format!("Could not edit list item with id {}", id))?;
+                    db_list_item.write(&tx)?;
+                }
+                CaptureSource::TableCell(id) => {
+                    let mut db_table_cell = DbTableCellBlock::zero();
+                    db_table_cell
+                        .select(&tx, *id)
+                        .context(format!("Could not select table cell with id {}", id))?;
+
+                    if db_table_cell.id != Some(*id) {
+                        return Err(anyhow!("Table cell with id {} no longer exists", id));
+                    }
+
+                    db_table_cell.content = apply_edits(&db_table_cell.content, source_edits)
+                        .context(format!("Could not edit table cell with id {}", id))?;
+                    db_table_cell.write(&tx)?;
+                }
+            }
+        }
+
+        resolve_changed_links(&tx, last_link_id).context("Could not resolve links")?;
+
+        tx.commit().context("Could not commit transaction")?;
+
+        Ok(edits_by_source.len())
+    }
+}
+
+/// Replaces the span of each edit in `text`. Edits must be ordered from the
+/// end of the text to the start, and fail if the text has changed since the
+/// value was captured.
+fn apply_edits(text: &str, edits: &[&CaptureEdit]) -> Result<String, anyhow::Error> {
+    let mut out = text.to_string();
+
+    for edit in edits {
+        let (start, end) = edit.span;
+
+        if out.get(start..end) != Some(edit.old_value.as_str()) {
+            return Err(anyhow!(
+                "Expected {:?} at {}..{}, source has changed since the fold was evaluated",
+                edit.old_value,
+                start,
+                end
+            ));
+        }
+
+        out.replace_range(start..end, &edit.new_value);
+    }
+
+    Ok(out)
 }
@@ -0,0 +1,1117 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Context};
+use rusqlite::Transaction;
+
+use crate::air::{
+    Block, CategoryBlock, DbBlock, DbCategoryBlock, DbDrawerBlock, DbIO, DbKeywordBlock,
+    DbListBlock, DbListItemBlock, DbPropertyBlock, DbRichTextBlock, DbTableBlock,
+    DbTableCellBlock, DbTableRowBlock, DbTextBlock, DbVerbatimBlock, ListBlock, ListKind,
+    PropertyBlock, TableBlock,
+};
+
+/// The rows touched while saving a category
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeSummary {
+    /// Id of the saved top level category
+    pub category_id: i64,
+    pub inserted: usize,
+    /// Blocks whose content, parent or position changed
+    pub updated: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Category,
+    RichText,
+    Text,
+    Property,
+    Drawer,
+    List,
+    ListItem,
+    Table,
+    TableRow,
+    TableCell,
+    Verbatim,
+    Keyword,
+}
+
+/// The kind and id of a stored block
+type BlockRef = (Kind, i64);
+
+/// Where a block sits in the tree: its kind, parent and position
+type Place = (Kind, Option<BlockRef>, i64);
+
+fn kind(row: &DbBlock) -> Kind {
+    match row {
+        DbBlock::Category(_) => Kind::Category,
+        DbBlock::RichText(_) => Kind::RichText,
+        DbBlock::Text(_) => Kind::Text,
+        DbBlock::Property(_) => Kind::Property,
+        DbBlock::Drawer(_) => Kind::Drawer,
+        DbBlock::List(_) => Kind::List,
+        DbBlock::ListItem(_) => Kind::ListItem,
+        DbBlock::Table(_) => Kind::Table,
+        DbBlock::TableRow(_) => Kind::TableRow,
+        DbBlock::TableCell(_) => Kind::TableCell,
+        DbBlock::Verbatim(_) => Kind::Verbatim,
+        DbBlock::Keyword(_) => Kind::Keyword,
+    }
+}
+
+fn id(row: &DbBlock) -> Option<i64> {
+    match row {
+        DbBlock::Category(category) => category.id,
+        DbBlock::RichText(rich_text) => rich_text.id,
+        DbBlock::Text(text) => text.id,
+        DbBlock::Property(property) => property.id,
+        DbBlock::Drawer(drawer) => drawer.id,
+        DbBlock::List(list) => list.id,
+        DbBlock::ListItem(item) => item.id,
+        DbBlock::Table(table) => table.id,
+        DbBlock::TableRow(row) => row.id,
+        DbBlock::TableCell(cell) => cell.id,
+        DbBlock::Verbatim(verbatim) => verbatim.id,
+        DbBlock::Keyword(keyword) => keyword.id,
+    }
+}
+
+fn set_id(row: &mut DbBlock, id: Option<i64>) {
+    match row {
+        DbBlock::Category(category) => category.id = id,
+        DbBlock::RichText(rich_text) => rich_text.id = id,
+        DbBlock::Text(text) => text.id = id,
+        DbBlock::Property(property) => property.id = id,
+        DbBlock::Drawer(drawer) => drawer.id = id,
+        DbBlock::List(list) => list.id = id,
+        DbBlock::ListItem(item) => item.id = id,
+        DbBlock::Table(table) => table.id = id,
+        DbBlock::TableRow(row) => row.id = id,
+        DbBlock::TableCell(cell) => cell.id = id,
+        DbBlock::Verbatim(verbatim) => verbatim.id = id,
+        DbBlock::Keyword(keyword) => keyword.id = id,
+    }
+}
+
+fn position(row: &DbBlock) -> i64 {
+    match row {
+        DbBlock::Category(category) => category.position,
+        DbBlock::RichText(rich_text) => rich_text.position,
+        DbBlock::Text(text) => text.position,
+        DbBlock::Property(property) => property.position,
+        DbBlock::Drawer(drawer) => drawer.position,
+        DbBlock::List(list) => list.position,
+        DbBlock::ListItem(item) => item.position,
+        DbBlock::Table(table) => table.position,
+        DbBlock::TableRow(row) => row.position,
+        DbBlock::TableCell(cell) => cell.position,
+        DbBlock::Verbatim(verbatim) => verbatim.position,
+        DbBlock::Keyword(keyword) => keyword.position,
+    }
+}
+
+/// Returns the kind and id of the block containing `row`
+fn parent(row: &DbBlock) -> Option<BlockRef> {
+    match row {
+        DbBlock::Text(DbTextBlock {
+            parent_rich_text_block_id: Some(id),
+            ..
+        }) => Some((Kind::RichText, *id)),
+        DbBlock::List(DbListBlock {
+            parent_list_item_id: Some(id),
+            ..
+        }) => Some((Kind::ListItem, *id)),
+        DbBlock::ListItem(DbListItemBlock { parent_list_id, .. }) => {
+            parent_list_id.map(|id| (Kind::List, id))
+        }
+        DbBlock::TableRow(DbTableRowBlock {
+            parent_table_id, ..
+        }) => parent_table_id.map(|id| (Kind::Table, id)),
+        DbBlock::TableCell(DbTableCellBlock {
+            parent_table_row_id,
+            ..
+        }) => parent_table_row_id.map(|id| (Kind::TableRow, id)),
+        DbBlock::Property(DbPropertyBlock {
+            parent_drawer_id: Some(id),
+            ..
+        }) => Some((Kind::Drawer, *id)),
+        DbBlock::Category(DbCategoryBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::RichText(DbRichTextBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::Text(DbTextBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::Property(DbPropertyBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::Drawer(DbDrawerBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::List(DbListBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::Table(DbTableBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::Verbatim(DbVerbatimBlock {
+            parent_category_id, ..
+        })
+        | DbBlock::Keyword(DbKeywordBlock {
+            parent_category_id, ..
+        }) => parent_category_id.map(|id| (Kind::Category, id)),
+    }
+}
+
+fn set_parent(row: &mut DbBlock, parent: Option<BlockRef>) {
+    let category_id = match parent {
+        Some((Kind::Category, id)) => Some(id),
+        _ => None,
+    };
+
+    match row {
+        DbBlock::Category(category) => category.parent_category_id = category_id,
+        DbBlock::RichText(rich_text) => rich_text.parent_category_id = category_id,
+        DbBlock::Property(property) => {
+            property.parent_category_id = category_id;
+            property.parent_drawer_id = match parent {
+                Some((Kind::Drawer, id)) => Some(id),
+                _ => None,
+            };
+        }
+        DbBlock::Drawer(drawer) => drawer.parent_category_id = category_id,
+        DbBlock::Text(text) => {
+            text.parent_category_id = category_id;
+            text.parent_rich_text_block_id = match parent {
+                Some((Kind::RichText, id)) => Some(id),
+                _ => None,
+            };
+        }
+        DbBlock::List(list) => {
+            list.parent_category_id = category_id;
+            list.parent_list_item_id = match parent {
+                Some((Kind::ListItem, id)) => Some(id),
+                _ => None,
+            };
+        }
+        DbBlock::ListItem(item) => {
+            item.parent_list_id = match parent {
+                Some((Kind::List, id)) => Some(id),
+                _ => None,
+            };
+        }
+        DbBlock::Table(table) => table.parent_category_id = category_id,
+        DbBlock::Verbatim(verbatim) => verbatim.parent_category_id = category_id,
+        DbBlock::Keyword(keyword) => keyword.parent_category_id = category_id,
+        DbBlock::TableRow(row) => {
+            row.parent_table_id = match parent {
+                Some((Kind::Table, id)) => Some(id),
+                _ => None,
+            };
+        }
+        DbBlock::TableCell(cell) => {
+            cell.parent_table_row_id = match parent {
+                Some((Kind::TableRow, id)) => Some(id),
+                _ => None,
+            };
+        }
+    }
+}
+
+fn write(row: &DbBlock, tx: &Transaction) -> Result<i64, anyhow::Error> {
+    match row {
+        DbBlock::Category(category) => category.write(tx),
+        DbBlock::RichText(rich_text) => rich_text.write(tx),
+        DbBlock::Text(text) => text.write(tx),
+        DbBlock::Property(property) => property.write(tx),
+        DbBlock::Drawer(drawer) => drawer.write(tx),
+        DbBlock::List(list) => list.write(tx),
+        DbBlock::ListItem(item) => item.write(tx),
+        DbBlock::Table(table) => table.write(tx),
+        DbBlock::TableRow(row) => row.write(tx),
+        DbBlock::TableCell(cell) => cell.write(tx),
+        DbBlock::Verbatim(verbatim) => verbatim.write(tx),
+        DbBlock::Keyword(keyword) => keyword.write(tx),
+    }
+}
+
+fn delete(row: &DbBlock, tx: &Transaction) -> Result<usize, anyhow::Error> {
+    match row {
+        DbBlock::Category(category) => category.delete(tx),
+        DbBlock::RichText(rich_text) => rich_text.delete(tx),
+        DbBlock::Text(text) => text.delete(tx),
+        DbBlock::Property(property) => property.delete(tx),
+        DbBlock::Drawer(drawer) => drawer.delete(tx),
+        DbBlock::List(list) => list.delete(tx),
+        DbBlock::ListItem(item) => item.delete(tx),
+        DbBlock::Table(table) => table.delete(tx),
+        DbBlock::TableRow(row) => row.delete(tx),
+        DbBlock::TableCell(cell) => cell.delete(tx),
+        DbBlock::Verbatim(verbatim) => verbatim.delete(tx),
+        DbBlock::Keyword(keyword) => keyword.delete(tx),
+    }
+}
+
+/// A block of the tree being saved or of the stored tree, in document order
+struct FlatBlock {
+    row: DbBlock,
+    /// What the block is recognised by when it has moved: the name of a
+    /// category, content of a text block, list item, table cell or verbatim
+    /// block, key of a property or keyword, or the joined content of the
+    /// lines in a rich text block, items of a list, cells of a table row,
+    /// rows of a table or keys of the properties in a drawer
+    key: String,
+    /// Index of the containing block, for blocks of the tree being saved
+    parent_index: Option<usize>,
+}
+
+fn flatten_category(
+    category: &CategoryBlock,
+    parent_index: Option<usize>,
+    position: i64,
+    out: &mut Vec<FlatBlock>,
+) -> Result<(), anyhow::Error> {
+    let index = out.len();
+
+    out.push(FlatBlock {
+        row: DbBlock::Category(DbCategoryBlock {
+            id: category.id,
+            name: category.name.to_string(),
+            parent_category_id: None,
+            position,
+        }),
+        key: category.name.to_string(),
+        parent_index,
+    });
+
+    for (child_index, child) in category.children.iter().enumerate() {
+        let position = child_index as i64;
+
+        match child {
+            Block::Category(child_category) => {
+                flatten_category(child_category, Some(index), position, out)?
+            }
+            Block::RichText(rich_text) => {
+                let rich_text_index = out.len();
+                let mut key = String::new();
+
+                out.push(FlatBlock {
+                    row: DbBlock::RichText(DbRichTextBlock {
+                        id: None,
+                        parent_category_id: None,
+                        position,
+                    }),
+                    key: String::new(),
+                    parent_index: Some(index),
+                });
+
+                if !rich_text.children.iter().all(|child| matches!(child, Block::Text(_))) {
+                    return Err(anyhow!("Rich text blocks can only contain text blocks"));
+                }
+
+                // Spans are stored joined into the lines they make up
+                for (line_index, line) in rich_text.lines().into_iter().enumerate() {
+                    key += &line.content;
+                    out.push(FlatBlock {
+                        row: DbBlock::Text(DbTextBlock {
+                            id: None,
+                            content: line.content.to_string(),
+                            parent_category_id: None,
+                            parent_rich_text_block_id: None,
+                            position: line_index as i64,
+                        }),
+                        key: line.content.into_owned(),
+                        parent_index: Some(rich_text_index),
+                    });
+                }
+
+                out[rich_text_index].key = key;
+            }
+            Block::Text(text) => out.push(FlatBlock {
+                row: DbBlock::Text(DbTextBlock {
+                    id: None,
+                    content: text.content.to_string(),
+                    parent_category_id: None,
+                    parent_rich_text_block_id: None,
+                    position,
+                }),
+                key: text.content.to_string(),
+                parent_index: Some(index),
+            }),
+            Block::Property(property) => flatten_property(property, index, position, out),
+            Block::Drawer(drawer) => {
+                let drawer_index = out.len();
+
+                out.push(FlatBlock {
+                    row: DbBlock::Drawer(DbDrawerBlock {
+                        id: None,
+                        begin: drawer.begin.to_string(),
+                        end: drawer.end.map(str::to_string),
+                        parent_category_id: None,
+                        position,
+                    }),
+                    key: drawer_key(drawer.properties.iter().map(|property| property.key)),
+                    parent_index: Some(index),
+                });
+
+                for (property_index, property) in drawer.properties.iter().enumerate() {
+                    flatten_property(property, drawer_index, property_index as i64, out);
+                }
+            }
+            Block::List(list) => flatten_list(list, index, position, out)?,
+            Block::ListItem(_) => {
+                return Err(anyhow!("Categories cannot contain list items directly"))
+            }
+            Block::Table(table) => flatten_table(table, index, position, out),
+            Block::Verbatim(verbatim) => out.push(FlatBlock {
+                row: DbBlock::Verbatim(DbVerbatimBlock {
+                    id: None,
+                    begin: verbatim.begin.to_string(),
+                    content: verbatim.content.to_string(),
+                    end: verbatim.end.to_string(),
+                    end_line_break: verbatim.end_line_break,
+                    parent_category_id: None,
+                    position,
+                }),
+                key: verbatim.content.to_string(),
+                parent_index: Some(index),
+            }),
+            Block::Keyword(keyword) => out.push(FlatBlock {
+                row: DbBlock::Keyword(DbKeywordBlock {
+                    id: None,
+                    key: keyword.key.to_string(),
+                    value: keyword.value.to_string(),
+                    indent: keyword.indent.to_string(),
+                    separator: keyword.separator.to_string(),
+                    trailing: keyword.trailing.to_string(),
+                    parent_category_id: None,
+                    position,
+                }),
+                key: keyword.key.to_string(),
+                parent_index: Some(index),
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+fn flatten_property(
+    property: &PropertyBlock,
+    parent_index: usize,
+    position: i64,
+    out: &mut Vec<FlatBlock>,
+) {
+    out.push(FlatBlock {
+        row: DbBlock::Property(DbPropertyBlock {
+            id: None,
+            key: property.key.to_string(),
+            value: property.value.to_string(),
+            indent: property.indent.to_string(),
+            separator: property.separator.to_string(),
+            trailing: property.trailing.to_string(),
+            parent_category_id: None,
+            parent_drawer_id: None,
+            position,
+        }),
+        key: property.key.to_string(),
+        parent_index: Some(parent_index),
+    });
+}
+
+/// The key of a drawer, which is the keys of its properties separated by
+/// `:`
+fn drawer_key<'a>(keys: impl Iterator<Item = &'a str>) -> String {
+    keys.collect::<Vec<&str>>().join(":")
+}
+
+fn flatten_list(
+    list: &ListBlock,
+    parent_index: usize,
+    position: i64,
+    out: &mut Vec<FlatBlock>,
+) -> Result<(), anyhow::Error> {
+    let list_index = out.len();
+    let mut key = String::new();
+
+    out.push(FlatBlock {
+        row: DbBlock::List(DbListBlock {
+            id: None,
+            ordered: list.kind == ListKind::Ordered,
+            parent_category_id: None,
+            parent_list_item_id: None,
+            position,
+        }),
+        key: String::new(),
+        parent_index: Some(parent_index),
+    });
+
+    for (item_index, child) in list.children.iter().enumerate() {
+        let Block::ListItem(item) = child else {
+            return Err(anyhow!("Lists can only contain list items"));
+        };
+
+        let index = out.len();
+        key += item.content;
+        out.push(FlatBlock {
+            row: DbBlock::ListItem(DbListItemBlock {
+                id: None,
+                bullet: item.bullet.to_string(),
+                checkbox: item.checkbox.map(str::to_string),
+                content: item.content.to_string(),
+                parent_list_id: None,
+                position: item_index as i64,
+            }),
+            key: item.content.to_string(),
+            parent_index: Some(list_index),
+        });
+
+        for (nested_index, nested) in item.children.iter().enumerate() {
+            let Block::List(nested_list) = nested else {
+                return Err(anyhow!("List items can only contain lists"));
+            };
+
+            flatten_list(nested_list, index, nested_index as i64, out)?;
+        }
+    }
+
+    out[list_index].key = key;
+
+    Ok(())
+}
+
+/// The key of a table row, which is its cells separated by `|`, or `-`
+/// for a rule
+fn row_key(rule: bool, cells: &[&str]) -> String {
+    if rule {
+        "-".to_string()
+    } else {
+        cells.join("|")
+    }
+}
+
+fn flatten_table(
+    table: &TableBlock,
+    parent_index: usize,
+    position: i64,
+    out: &mut Vec<FlatBlock>,
+) {
+    let table_index = out.len();
+    let mut key = String::new();
+
+    out.push(FlatBlock {
+        row: DbBlock::Table(DbTableBlock {
+            id: None,
+            parent_category_id: None,
+            position,
+        }),
+        key: String::new(),
+        parent_index: Some(parent_index),
+    });
+
+    for (row_index, row) in table.rows.iter().enumerate() {
+        let index = out.len();
+        let cells: Vec<&str> = row.cells.iter().map(|cell| cell.content).collect();
+        let row_key = row_key(row.rule, &cells);
+
+        key += &row_key;
+        key += "\n";
+        out.push(FlatBlock {
+            row: DbBlock::TableRow(DbTableRowBlock {
+                id: None,
+                indent: row.indent.to_string(),
+                rule: row.rule,
+                parent_table_id: None,
+                position: row_index as i64,
+            }),
+            key: row_key,
+            parent_index: Some(table_index),
+        });
+
+        for (cell_index, cell) in row.cells.iter().enumerate() {
+            out.push(FlatBlock {
+                row: DbBlock::TableCell(DbTableCellBlock {
+                    id: None,
+                    content: cell.content.to_string(),
+                    parent_table_row_id: None,
+                    position: cell_index as i64,
+                }),
+                key: cell.content.to_string(),
+                parent_index: Some(index),
+            });
+        }
+    }
+
+    out[table_index].key = key;
+}
+
+fn flatten_stored(rows: Vec<DbBlock>) -> Vec<FlatBlock> {
+    let mut rich_text_keys: HashMap<i64, String> = HashMap::new();
+    let mut list_keys: HashMap<i64, String> = HashMap::new();
+    let mut row_cells: HashMap<i64, Vec<&str>> = HashMap::new();
+    let mut table_keys: HashMap<i64, String> = HashMap::new();
+    let mut drawer_properties: HashMap<i64, Vec<&str>> = HashMap::new();
+
+    for row in &rows {
+        if let DbBlock::Property(DbPropertyBlock {
+            key,
+            parent_drawer_id: Some(id),
+            ..
+        }) = row
+        {
+            drawer_properties.entry(*id).or_default().push(key);
+        }
+    }
+
+    let mut drawer_keys: HashMap<i64, String> = drawer_properties
+        .into_iter()
+        .map(|(id, keys)| (id, drawer_key(keys.into_iter())))
+        .collect();
+
+    for row in &rows {
+        if let DbBlock::TableCell(DbTableCellBlock {
+            content,
+            parent_table_row_id: Some(id),
+            ..
+        }) = row
+        {
+            row_cells.entry(*id).or_default().push(content);
+        }
+    }
+
+    let mut row_keys: HashMap<i64, String> = HashMap::new();
+
+    for row in &rows {
+        if let DbBlock::TableRow(DbTableRowBlock {
+            id: Some(id),
+            rule,
+            parent_table_id: Some(table_id),
+            ..
+        }) = row
+        {
+            let key = row_key(*rule, row_cells.get(id).map_or(&[], |cells| cells.as_slice()));
+
+            let table_key = table_keys.entry(*table_id).or_default();
+            table_key.push_str(&key);
+            table_key.push('\n');
+
+            row_keys.insert(*id, key);
+        }
+    }
+
+    for row in &rows {
+        match row {
+            DbBlock::Text(DbTextBlock {
+                content,
+                parent_rich_text_block_id: Some(id),
+                ..
+            }) => rich_text_keys.entry(*id).or_default().push_str(content),
+            DbBlock::ListItem(DbListItemBlock {
+                content,
+                parent_list_id: Some(id),
+                ..
+            }) => list_keys.entry(*id).or_default().push_str(content),
+            _ => {}
+        }
+    }
+
+    rows.into_iter()
+        .map(|row| {
+            let key = match &row {
+                DbBlock::Category(category) => category.name.clone(),
+                DbBlock::RichText(rich_text) => rich_text
+                    .id
+                    .and_then(|id| rich_text_keys.remove(&id))
+                    .unwrap_or_default(),
+                DbBlock::Text(text) => text.content.clone(),
+                DbBlock::Property(property) => property.key.clone(),
+                DbBlock::Drawer(drawer) => drawer
+                    .id
+                    .and_then(|id| drawer_keys.remove(&id))
+                    .unwrap_or_default(),
+                DbBlock::List(list) => list
+                    .id
+                    .and_then(|id| list_keys.remove(&id))
+                    .unwrap_or_default(),
+                DbBlock::ListItem(item) => item.content.clone(),
+                DbBlock::Table(table) => table
+                    .id
+                    .and_then(|id| table_keys.remove(&id))
+                    .unwrap_or_default(),
+                DbBlock::TableRow(table_row) => table_row
+                    .id
+                    .and_then(|id| row_keys.remove(&id))
+                    .unwrap_or_default(),
+                DbBlock::TableCell(cell) => cell.content.clone(),
+                DbBlock::Verbatim(verbatim) => verbatim.content.clone(),
+                DbBlock::Keyword(keyword) => keyword.key.clone(),
+            };
+
+            FlatBlock {
+                row,
+                key,
+                parent_index: None,
+            }
+        })
+        .collect()
+}
+
+/// Stored blocks that share something, in document order
+#[derive(Default)]
+struct Candidates {
+    indices: Vec<usize>,
+    /// Every candidate before this one has been paired already
+    next: usize,
+}
+
+impl Candidates {
+    /// Takes the first candidate that has not been paired yet
+    fn take(&mut self, used: &mut HashSet<usize>) -> Option<usize> {
+        while let Some(index) = self.indices.get(self.next) {
+            self.next += 1;
+
+            if used.insert(*index) {
+                return Some(*index);
+            }
+        }
+
+        None
+    }
+}
+
+/// Pairs the blocks of the tree being saved with stored blocks, returning
+/// the index of the stored block each one takes the id of. Blocks are
+/// first paired with a stored block with the same content, preferring one
+/// with the same parent, so unchanged and moved blocks keep their ids.
+/// Remaining blocks take the id of an unpaired stored block of the same
+/// kind at the same place in the tree, so edited blocks keep theirs too.
+fn pair_blocks(new_blocks: &[FlatBlock], stored: &[FlatBlock]) -> Vec<Option<usize>> {
+    let mut pairs: Vec<Option<usize>> = vec![None; new_blocks.len()];
+    let mut used: HashSet<usize> = HashSet::new();
+
+    let mut by_key: HashMap<(Kind, &str), Candidates> = HashMap::new();
+    let mut by_key_and_parent: HashMap<(Kind, &str, Option<BlockRef>), Candidates> =
+        HashMap::new();
+    let mut by_place: HashMap<Place, Candidates> = HashMap::new();
+
+    for (index, block) in stored.iter().enumerate() {
+        let block_kind = kind(&block.row);
+        let key = block.key.as_str();
+
+        by_key
+            .entry((block_kind, key))
+            .or_default()
+            .indices
+            .push(index);
+        by_key_and_parent
+            .entry((block_kind, key, parent(&block.row)))
+            .or_default()
+            .indices
+            .push(index);
+        by_place
+            .entry((block_kind, parent(&block.row), position(&block.row)))
+            .or_default()
+            .indices
+            .push(index);
+    }
+
+    // The saved category always keeps the id it was saved with
+    if let (Some(root), Some(stored_root)) = (new_blocks.first(), stored.first()) {
+        if id(&root.row) == id(&stored_root.row) {
+            pairs[0] = Some(0);
+            used.insert(0);
+        }
+    }
+
+    let stored_parent = |pairs: &[Option<usize>], block: &FlatBlock| {
+        let parent_index = block.parent_index?;
+        let stored_index = pairs[parent_index]?;
+        let stored_row = &stored[stored_index].row;
+        Some((kind(stored_row), id(stored_row)?))
+    };
+
+    for (index, block) in new_blocks.iter().enumerate().skip(1) {
+        let block_kind = kind(&block.row);
+        let key = block.key.as_str();
+
+        let same_parent = stored_parent(&pairs, block).and_then(|wanted_parent| {
+            by_key_and_parent
+                .get_mut(&(block_kind, key, Some(wanted_parent)))?
+                .take(&mut used)
+        });
+
+        pairs[index] = same_parent.or_else(|| by_key.get_mut(&(block_kind, key))?.take(&mut used));
+    }
+
+    for (index, block) in new_blocks.iter().enumerate().skip(1) {
+        if pairs[index].is_some() {
+            continue;
+        }
+
+        let Some(wanted_parent) = stored_parent(&pairs, block) else {
+            continue;
+        };
+
+        let place = (kind(&block.row), Some(wanted_parent), position(&block.row));
+        pairs[index] = by_place
+            .get_mut(&place)
+            .and_then(|candidates| candidates.take(&mut used));
+    }
+
+    pairs
+}
+
+/// Saves `category` over `stored`, the rows currently stored for it in
+/// document order, issuing only the writes needed to turn one into the
+/// other
+pub(super) fn save_category(
+    tx: &Transaction,
+    category: &CategoryBlock,
+    stored: Vec<DbBlock>,
+) -> Result<ChangeSummary, anyhow::Error> {
+    let mut new_blocks: Vec<FlatBlock> = Vec::new();
+    flatten_category(category, None, 0, &mut new_blocks)
+        .context("Could not flatten category")?;
+
+    let stored = flatten_stored(stored);
+    let pairs = pair_blocks(&new_blocks, &stored);
+
+    let mut summary = ChangeSummary::default();
+    let mut ids: Vec<i64> = Vec::with_capacity(new_blocks.len());
+
+    // Parents come before their children, so their ids are always known
+    for index in 0..new_blocks.len() {
+        let parent = new_blocks[index]
+            .parent_index
+            .map(|parent_index| (kind(&new_blocks[parent_index].row), ids[parent_index]));
+        let stored_block = pairs[index].map(|stored_index| &stored[stored_index]);
+
+        let row = &mut new_blocks[index].row;
+
+        if index > 0 {
+            set_id(row, stored_block.and_then(|block| id(&block.row)));
+        }
+        set_parent(row, parent);
+
+        let row_id = match stored_block {
+            Some(block) if block.row == *row => {
+                summary.unchanged += 1;
+                id(row).context("Stored block did not have an id")?
+            }
+            Some(_) => {
+                summary.updated += 1;
+                write(row, tx).context("Could not update block")?
+            }
+            None => {
+                summary.inserted += 1;
+                write(row, tx).context("Could not insert block")?
+            }
+        };
+
+        ids.push(row_id);
+    }
+
+    let paired: HashSet<usize> = pairs.into_iter().flatten().collect();
+
+    // Children are deleted before their parents so each delete removes
+    // exactly one row
+    for (index, block) in stored.iter().enumerate().rev() {
+        if !paired.contains(&index) {
+            delete(&block.row, tx).context("Could not delete block")?;
+            summary.deleted += 1;
+        }
+    }
+
+    summary.category_id = *ids.first().context("Category was not written")?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        air::Block,
+        db::{save_document, AmbleDB, DbBlockMatrix},
+        rendering::render_to_org,
+    };
+
+    use super::ChangeSummary;
+
+    fn render(db: &AmbleDB, id: i64) -> String {
+        let matrix = DbBlockMatrix::new(&db.connection, id).expect("Should create matrix");
+        let flat_blocks = matrix
+            .produce_flat_db_block_vec()
+            .expect("Should produce db blocks");
+        let category = matrix
+            .form_category_block_tree(&flat_blocks)
+            .expect("Should form category tree");
+
+        render_to_org(Block::Category(category))
+    }
+
+    fn ids(db: &AmbleDB, table: &str) -> Vec<i64> {
+        let mut stmt = db
+            .connection
+            .prepare(&format!("SELECT id FROM {} ORDER BY id", table))
+            .unwrap();
+
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn id_of(db: &AmbleDB, table: &str, column: &str, value: &str) -> i64 {
+        db.connection
+            .query_row(
+                &format!("SELECT id FROM {} WHERE {} = ?1", table, column),
+                [value],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    const DOCUMENT: &str = "* Plans
+:Owner: Sam
+First line
+Second line
+** Child
+Child text
+* Other
+";
+
+    #[test]
+    fn unchanged_save_writes_nothing() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let first = save_document(&mut db, None, "Notes", DOCUMENT);
+        assert_eq!(first.updated + first.deleted + first.unchanged, 0);
+
+        let second = save_document(&mut db, Some(first.category_id), "Notes", DOCUMENT);
+        assert_eq!(
+            second,
+            ChangeSummary {
+                category_id: first.category_id,
+                inserted: 0,
+                updated: 0,
+                deleted: 0,
+                unchanged: first.inserted,
+            }
+        );
+        assert_eq!(render(&db, first.category_id), DOCUMENT);
+    }
+
+    #[test]
+    fn edits_keep_ids() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+        let category_id = save_document(&mut db, None, "Notes", DOCUMENT).category_id;
+
+        let text_ids = ids(&db, "text_blocks");
+        let property_id = id_of(&db, "properties", "key", "Owner");
+
+        let edited = DOCUMENT
+            .replace("Second line", "Second line, edited")
+            .replace(":Owner: Sam", ":Owner: Alex");
+        let summary = save_document(&mut db, Some(category_id), "Notes", &edited);
+
+        assert_eq!(summary.inserted, 0);
+        assert_eq!(summary.updated, 2);
+        assert_eq!(summary.deleted, 0);
+
+        assert_eq!(ids(&db, "text_blocks"), text_ids);
+        assert_eq!(id_of(&db, "properties", "value", "Alex"), property_id);
+        assert_eq!(render(&db, category_id), edited);
+    }
+
+    #[test]
+    fn moved_headline_keeps_id() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+        let category_id = save_document(&mut db, None, "Notes", DOCUMENT).category_id;
+
+        let child_id = id_of(&db, "category_blocks", "name", "Child\n");
+        let child_text_id = id_of(&db, "text_blocks", "content", "Child text\n");
+
+        let moved = "* Plans
+:Owner: Sam
+First line
+Second line
+* Other
+** Child
+Child text
+";
+        let summary = save_document(&mut db, Some(category_id), "Notes", moved);
+
+        assert_eq!(summary.inserted, 0);
+        assert_eq!(summary.deleted, 0);
+        assert_eq!(summary.updated, 1);
+
+        assert_eq!(id_of(&db, "category_blocks", "name", "Child\n"), child_id);
+        assert_eq!(
+            id_of(&db, "text_blocks", "content", "Child text\n"),
+            child_text_id
+        );
+        assert_eq!(render(&db, category_id), moved);
+    }
+
+    #[test]
+    fn removed_headline_deletes_subtree() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+        let category_id = save_document(&mut db, None, "Notes", DOCUMENT).category_id;
+
+        let removed = DOCUMENT.replace("** Child\nChild text\n", "");
+        let summary = save_document(&mut db, Some(category_id), "Notes", &removed);
+
+        assert_eq!(summary.inserted, 0);
+        assert_eq!(summary.updated, 0);
+        assert_eq!(summary.deleted, 3);
+
+        assert_eq!(ids(&db, "category_blocks").len(), 3);
+        assert_eq!(render(&db, category_id), removed);
+    }
+
+    #[test]
+    fn added_headline_is_inserted() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+        let category_id = save_document(&mut db, None, "Notes", DOCUMENT).category_id;
+
+        let added = format!("{}* Another\nWith text\n", DOCUMENT);
+        let summary = save_document(&mut db, Some(category_id), "Notes", &added);
+
+        assert_eq!(summary.inserted, 3);
+        assert_eq!(summary.updated, 0);
+        assert_eq!(summary.deleted, 0);
+        assert_eq!(render(&db, category_id), added);
+    }
+
+    #[test]
+    fn checking_an_item_keeps_list_ids() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let document = "* Packing
+- [ ] Tent
+  - [ ] Poles
+  - [ ] Pegs
+- [ ] Stove
+";
+        let category_id = save_document(&mut db, None, "Notes", document).category_id;
+        let list_ids = ids(&db, "lists");
+        let item_ids = ids(&db, "list_items");
+        assert_eq!((list_ids.len(), item_ids.len()), (2, 4));
+
+        let checked = document.replace("[ ] Pegs", "[X] Pegs");
+        let summary = save_document(&mut db, Some(category_id), "Notes", &checked);
+
+        assert_eq!((summary.inserted, summary.updated, summary.deleted), (0, 1, 0));
+        assert_eq!(ids(&db, "lists"), list_ids);
+        assert_eq!(ids(&db, "list_items"), item_ids);
+        assert_eq!(render(&db, category_id), checked);
+
+        // Checkboxes are kept as they were written
+        let lowercase = checked.replace("[X] Pegs", "[x] Pegs");
+        let summary = save_document(&mut db, Some(category_id), "Notes", &lowercase);
+
+        assert_eq!((summary.inserted, summary.updated, summary.deleted), (0, 1, 0));
+        assert_eq!(render(&db, category_id), lowercase);
+
+        let removed = checked.replace("  - [ ] Poles\n  - [X] Pegs\n", "");
+        let summary = save_document(&mut db, Some(category_id), "Notes", &removed);
+
+        assert_eq!(summary.deleted, 3);
+        assert_eq!(ids(&db, "lists").len(), 1);
+        assert_eq!(ids(&db, "list_items").len(), 2);
+        assert_eq!(render(&db, category_id), removed);
+    }
+
+    #[test]
+    fn editing_a_cell_keeps_table_ids() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let document = "* Tasks
+| Task   | Owner |
+|--------+-------|
+| Design | Stacy |
+| Copy   | Sam   |
+";
+        let category_id = save_document(&mut db, None, "Notes", document).category_id;
+        let row_ids = ids(&db, "table_rows");
+        let cell_ids = ids(&db, "table_cells");
+        assert_eq!((row_ids.len(), cell_ids.len()), (4, 6));
+
+        let edited = document.replace("| Sam   |", "| Alex  |");
+        let summary = save_document(&mut db, Some(category_id), "Notes", &edited);
+
+        assert_eq!((summary.inserted, summary.updated, summary.deleted), (0, 1, 0));
+        assert_eq!(ids(&db, "table_rows"), row_ids);
+        assert_eq!(ids(&db, "table_cells"), cell_ids);
+        assert_eq!(render(&db, category_id), edited);
+
+        let removed = edited.replace("| Design | Stacy |\n", "");
+        let summary = save_document(&mut db, Some(category_id), "Notes", &removed);
+
+        // Columns are realigned to the remaining rows on render
+        assert_eq!(summary.deleted, 3);
+        assert_eq!(
+            render(&db, category_id),
+            "* Tasks\n| Task | Owner |\n|------+-------|\n| Copy | Alex  |\n"
+        );
+    }
+
+    /// Describes each stored timestamp by its non null columns
+    fn timestamps(db: &AmbleDB) -> Vec<String> {
+        let mut stmt = db
+            .connection
+            .prepare(
+                "SELECT label, starts_at, ends_at, repeater FROM timestamps ORDER BY starts_at",
+            )
+            .unwrap();
+
+        stmt.query_map([], |row| {
+            let columns: Vec<Option<String>> =
+                vec![row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?];
+            Ok(columns.into_iter().flatten().collect::<Vec<String>>().join(" "))
+        })
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+    }
+
+    #[test]
+    fn store_timestamps_as_iso_strings() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let document = "* TODO Report
+DEADLINE: <2023-10-20 Fri 17:00> SCHEDULED: <2023-10-14 Sat +1w>
+:Due: <2023-10-18>
+Review on [2023-10-16 Mon 9:00-10:30]
+";
+        let category_id = save_document(&mut db, None, "Notes", document).category_id;
+
+        assert_eq!(
+            timestamps(&db),
+            vec![
+                "SCHEDULED 2023-10-14 +1w",
+                "2023-10-16T09:00 2023-10-16T10:30",
+                "Due 2023-10-18",
+                "DEADLINE 2023-10-20T17:00",
+            ]
+        );
+
+        let edited = document
+            .replace("<2023-10-18>", "<2023-11-01>")
+            .replace("Review on [2023-10-16 Mon 9:00-10:30]\n", "");
+        save_document(&mut db, Some(category_id), "Notes", &edited);
+
+        assert_eq!(
+            timestamps(&db),
+            vec![
+                "SCHEDULED 2023-10-14 +1w",
+                "DEADLINE 2023-10-20T17:00",
+                "Due 2023-11-01",
+            ]
+        );
+    }
+}
@@ -1,18 +1,271 @@
-use std::collections::HashSet;
-
 use anyhow::{anyhow, Context};
-use rusqlite::Connection;
+use rusqlite::{Connection, Row};
 
 use crate::air::{
-    Block, CategoryBlock, DbBlock, DbCategoryBlock, DbIO, DbRichTextBlock, DbTextBlock,
-    RichTextBlock, TextBlock,
+    Block, CategoryBlock, DbBlock, DbCategoryBlock, DbDrawerBlock, DbKeywordBlock,
+    DbListBlock, DbListItemBlock, DbPropertyBlock, DbRichTextBlock, DbTableBlock,
+    DbTableCellBlock, DbTableRowBlock, DbTextBlock, DbVerbatimBlock, DrawerBlock, KeywordBlock,
+    ListBlock, ListItemBlock, PropertyBlock, RichTextBlock, TableBlock, TableCell, TableRow,
+    TextBlock, VerbatimBlock,
 };
 
+/// Finds every category beneath the requested one, and every list beneath
+/// those, along with the key their blocks sort by. The key of a block is
+/// made of the positions of its ancestors and itself, so sorting by it
+/// visits parents before their children and siblings in the order they
+/// were written. Lists are found recursively, as they can be nested in the
+/// items of other lists.
+const BLOCK_TREE: &str = "
+    WITH RECURSIVE child_categories(id, name, parent_category_id, position, sort_key) AS (
+        SELECT id, name, parent_category_id, position, '' FROM category_blocks
+        WHERE id = ?1
+        UNION ALL
+        SELECT cb.id, cb.name, cb.parent_category_id, cb.position,
+            cc.sort_key || printf('/%010d', cb.position)
+        FROM category_blocks cb
+        INNER JOIN child_categories cc ON cb.parent_category_id = cc.id
+    ),
+    list_tree(id, ordered, parent_category_id, parent_list_item_id, position, sort_key) AS (
+        SELECT l.id, l.ordered, l.parent_category_id, l.parent_list_item_id, l.position,
+            cc.sort_key || printf('/%010d', l.position)
+        FROM lists l
+        INNER JOIN child_categories cc ON l.parent_category_id = cc.id
+        UNION ALL
+        SELECT l.id, l.ordered, l.parent_category_id, l.parent_list_item_id, l.position,
+            lt.sort_key || printf('/%010d/%010d', li.position, l.position)
+        FROM lists l
+        INNER JOIN list_items li ON l.parent_list_item_id = li.id
+        INNER JOIN list_tree lt ON li.parent_list_id = lt.id
+    )";
+
+/// Selects the blocks of one kind beneath the requested category. The
+/// query follows `BLOCK_TREE` and names its columns, one of which must be
+/// `sort_key`.
+struct BlockQuery {
+    sql: &'static str,
+    read: fn(&Row) -> rusqlite::Result<DbBlock>,
+}
+
+const BLOCK_QUERIES: &[BlockQuery] = &[
+    BlockQuery {
+        sql: "
+            SELECT id, name, parent_category_id, position, sort_key
+            FROM child_categories",
+        read: |row| {
+            Ok(DbBlock::Category(DbCategoryBlock {
+                id: row.get("id")?,
+                name: row.get("name")?,
+                parent_category_id: row.get("parent_category_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT p.id, p.key, p.value, p.indent, p.separator, p.trailing,
+                p.parent_category_id, p.parent_drawer_id, p.position,
+                cc.sort_key || printf('/%010d', p.position) AS sort_key
+            FROM properties p
+            INNER JOIN child_categories cc ON p.parent_category_id = cc.id
+            UNION ALL
+            SELECT p.id, p.key, p.value, p.indent, p.separator, p.trailing,
+                p.parent_category_id, p.parent_drawer_id, p.position,
+                cc.sort_key || printf('/%010d/%010d', d.position, p.position)
+            FROM properties p
+            INNER JOIN drawers d ON p.parent_drawer_id = d.id
+            INNER JOIN child_categories cc ON d.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::Property(DbPropertyBlock {
+                id: row.get("id")?,
+                key: row.get("key")?,
+                value: row.get("value")?,
+                indent: row.get("indent")?,
+                separator: row.get("separator")?,
+                trailing: row.get("trailing")?,
+                parent_category_id: row.get("parent_category_id")?,
+                parent_drawer_id: row.get("parent_drawer_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT d.id, d.begin_line, d.end_line, d.parent_category_id, d.position,
+                cc.sort_key || printf('/%010d', d.position) AS sort_key
+            FROM drawers d
+            INNER JOIN child_categories cc ON d.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::Drawer(DbDrawerBlock {
+                id: row.get("id")?,
+                begin: row.get("begin_line")?,
+                end: row.get("end_line")?,
+                parent_category_id: row.get("parent_category_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT rtb.id, rtb.parent_category_id, rtb.position,
+                cc.sort_key || printf('/%010d', rtb.position) AS sort_key
+            FROM rich_text_blocks rtb
+            INNER JOIN child_categories cc ON rtb.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::RichText(DbRichTextBlock {
+                id: row.get("id")?,
+                parent_category_id: row.get("parent_category_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT tb.id, tb.content, tb.parent_category_id, tb.parent_rich_text_block_id,
+                tb.position,
+                cc.sort_key || printf('/%010d/%010d', rtb.position, tb.position) AS sort_key
+            FROM text_blocks tb
+            INNER JOIN rich_text_blocks rtb ON tb.parent_rich_text_block_id = rtb.id
+            INNER JOIN child_categories cc ON rtb.parent_category_id = cc.id
+            UNION ALL
+            SELECT tb.id, tb.content, tb.parent_category_id, tb.parent_rich_text_block_id,
+                tb.position, cc.sort_key || printf('/%010d', tb.position)
+            FROM text_blocks tb
+            INNER JOIN child_categories cc ON tb.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::Text(DbTextBlock {
+                id: row.get("id")?,
+                content: row.get("content")?,
+                parent_category_id: row.get("parent_category_id")?,
+                parent_rich_text_block_id: row.get("parent_rich_text_block_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT id, ordered, parent_category_id, parent_list_item_id, position, sort_key
+            FROM list_tree",
+        read: |row| {
+            Ok(DbBlock::List(DbListBlock {
+                id: row.get("id")?,
+                ordered: row.get("ordered")?,
+                parent_category_id: row.get("parent_category_id")?,
+                parent_list_item_id: row.get("parent_list_item_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT li.id, li.bullet, li.checkbox, li.content, li.parent_list_id, li.position,
+                lt.sort_key || printf('/%010d', li.position) AS sort_key
+            FROM list_items li
+            INNER JOIN list_tree lt ON li.parent_list_id = lt.id",
+        read: |row| {
+            Ok(DbBlock::ListItem(DbListItemBlock {
+                id: row.get("id")?,
+                bullet: row.get("bullet")?,
+                checkbox: row.get("checkbox")?,
+                content: row.get("content")?,
+                parent_list_id: row.get("parent_list_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT t.id, t.parent_category_id, t.position,
+                cc.sort_key || printf('/%010d', t.position) AS sort_key
+            FROM tables t
+            INNER JOIN child_categories cc ON t.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::Table(DbTableBlock {
+                id: row.get("id")?,
+                parent_category_id: row.get("parent_category_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT tr.id, tr.indent, tr.rule, tr.parent_table_id, tr.position,
+                cc.sort_key || printf('/%010d/%010d', t.position, tr.position) AS sort_key
+            FROM table_rows tr
+            INNER JOIN tables t ON tr.parent_table_id = t.id
+            INNER JOIN child_categories cc ON t.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::TableRow(DbTableRowBlock {
+                id: row.get("id")?,
+                indent: row.get("indent")?,
+                rule: row.get("rule")?,
+                parent_table_id: row.get("parent_table_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT tc.id, tc.content, tc.parent_table_row_id, tc.position,
+                cc.sort_key || printf('/%010d/%010d/%010d', t.position, tr.position, tc.position)
+                    AS sort_key
+            FROM table_cells tc
+            INNER JOIN table_rows tr ON tc.parent_table_row_id = tr.id
+            INNER JOIN tables t ON tr.parent_table_id = t.id
+            INNER JOIN child_categories cc ON t.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::TableCell(DbTableCellBlock {
+                id: row.get("id")?,
+                content: row.get("content")?,
+                parent_table_row_id: row.get("parent_table_row_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT vb.id, vb.begin_line, vb.content, vb.end_line, vb.end_line_break,
+                vb.parent_category_id, vb.position,
+                cc.sort_key || printf('/%010d', vb.position) AS sort_key
+            FROM verbatim_blocks vb
+            INNER JOIN child_categories cc ON vb.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::Verbatim(DbVerbatimBlock {
+                id: row.get("id")?,
+                begin: row.get("begin_line")?,
+                content: row.get("content")?,
+                end: row.get("end_line")?,
+                end_line_break: row.get("end_line_break")?,
+                parent_category_id: row.get("parent_category_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+    BlockQuery {
+        sql: "
+            SELECT k.id, k.key, k.value, k.indent, k.separator, k.trailing, k.parent_category_id,
+                k.position, cc.sort_key || printf('/%010d', k.position) AS sort_key
+            FROM keywords k
+            INNER JOIN child_categories cc ON k.parent_category_id = cc.id",
+        read: |row| {
+            Ok(DbBlock::Keyword(DbKeywordBlock {
+                id: row.get("id")?,
+                key: row.get("key")?,
+                value: row.get("value")?,
+                indent: row.get("indent")?,
+                separator: row.get("separator")?,
+                trailing: row.get("trailing")?,
+                parent_category_id: row.get("parent_category_id")?,
+                position: row.get("position")?,
+            }))
+        },
+    },
+];
+
+/// A single block beneath the requested category
 #[derive(Debug)]
 struct DbBlockMatrixRow {
-    category_block_id: i64,
-    rich_text_block_id: Option<i64>,
-    text_block_id: Option<i64>,
+    sort_key: String,
+    block: DbBlock,
 }
 
 #[derive(Debug)]
@@ -20,101 +273,40 @@ pub struct DbBlockMatrix {
     rows: Vec<DbBlockMatrixRow>,
 }
 
-// TODO: This approach to querying the database for a category
-// and its children is very scary to look at. Should spend some
-// making all this logic clearer
-
 impl DbBlockMatrix {
+    /// Loads every block beneath the category with `category_id`, in
+    /// document order. Each kind of block is selected by its own query in
+    /// `BLOCK_QUERIES`, and the results are merged by their sort keys.
     pub fn new(connection: &Connection, category_id: i64) -> Result<DbBlockMatrix, anyhow::Error> {
-        let mut stmt = connection.prepare(
-            "
-            WITH RECURSIVE child_categories(id) AS (
-                SELECT id FROM category_blocks 
-                WHERE id = ?1
-                UNION ALL
-                SELECT cb.id 
-                FROM category_blocks cb
-                INNER JOIN child_categories cc ON cb.parent_category_id = cc.id
-            )
-            SELECT 
-                cb.id AS category_block_id, 
-                rtb.id AS rich_text_block_id, 
-                tb.id AS text_block_id 
-            FROM category_blocks cb
-            LEFT JOIN rich_text_blocks rtb ON rtb.parent_category_id = cb.id
-            LEFT JOIN text_blocks tb 
-                ON rtb.id = tb.parent_rich_text_block_id 
-                OR tb.parent_category_id = cb.id
-            INNER JOIN child_categories cc ON cb.id = cc.id
-            ",
-        )?;
-
-        let row_iter = stmt.query_map([category_id], |row| {
-            Ok(DbBlockMatrixRow {
-                category_block_id: row.get(0)?,
-                rich_text_block_id: row.get(1)?,
-                text_block_id: row.get(2)?,
-            })
-        })?;
-
         let mut block_matrix = DbBlockMatrix { rows: Vec::new() };
 
-        for row in row_iter {
-            block_matrix.rows.push(row?)
-        }
-
-        Ok(block_matrix)
-    }
-
-    pub fn produce_flat_db_block_vec(
-        &self,
-        connection: &Connection,
-    ) -> Result<Vec<DbBlock>, anyhow::Error> {
-        let mut db_blocks: Vec<DbBlock> = Vec::new();
+        for query in BLOCK_QUERIES {
+            let mut stmt = connection
+                .prepare_cached(&format!("{}{}", BLOCK_TREE, query.sql))
+                .context("Could not prepare block matrix query")?;
 
-        let mut category_block_ids_so_far: HashSet<i64> = HashSet::new();
-        let mut rich_text_block_ids_so_far: HashSet<i64> = HashSet::new();
-        let mut text_block_ids_so_far: HashSet<i64> = HashSet::new();
+            let row_iter = stmt
+                .query_map([category_id], |row| {
+                    Ok(DbBlockMatrixRow {
+                        sort_key: row.get("sort_key")?,
+                        block: (query.read)(row)?,
+                    })
+                })
+                .context("Could not query block matrix")?;
 
-        for row in &self.rows {
-            let mut db_category = DbCategoryBlock::zero();
-
-            if !category_block_ids_so_far.contains(&row.category_block_id) {
-                db_category
-                    .select(connection, row.category_block_id)
-                    .context(format!(
-                        "Could not select db category block with id {}",
-                        row.category_block_id
-                    ))?;
-                db_blocks.push(DbBlock::Category(db_category));
-                category_block_ids_so_far.insert(row.category_block_id);
+            for row in row_iter {
+                block_matrix.rows.push(row.context("Could not read block matrix row")?)
             }
+        }
 
-            if let Some(rt_id) = row.rich_text_block_id {
-                if !rich_text_block_ids_so_far.contains(&rt_id) {
-                    let mut db_rich_text = DbRichTextBlock::zero();
-                    db_rich_text.select(connection, rt_id).context(format!(
-                        "Could not select db rich text block with id {}",
-                        rt_id
-                    ))?;
-                    db_blocks.push(DbBlock::RichText(db_rich_text));
-                    rich_text_block_ids_so_far.insert(rt_id);
-                }
-            }
+        block_matrix.rows.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
 
-            if let Some(t_id) = row.text_block_id {
-                if !text_block_ids_so_far.contains(&t_id) {
-                    let mut db_text = DbTextBlock::zero();
-                    db_text
-                        .select(connection, t_id)
-                        .context(format!("Could not select db text block with id {}", t_id))?;
-                    db_blocks.push(DbBlock::Text(db_text));
-                    text_block_ids_so_far.insert(t_id);
-                }
-            }
-        }
+        Ok(block_matrix)
+    }
 
-        Ok(db_blocks)
+    /// Converts the rows of the matrix into db blocks, in document order
+    pub fn produce_flat_db_block_vec(&self) -> Result<Vec<DbBlock>, anyhow::Error> {
+        Ok(self.rows.iter().map(|row| row.block.clone()).collect())
     }
 
     fn get_child_blocks<'a>(
@@ -123,8 +315,8 @@ impl DbBlockMatrix {
         parent_block: &DbBlock,
         start_index: usize,
         level: usize,
-    ) -> Result<(Vec<Block>, usize), anyhow::Error> {
-        let mut children: Vec<Block> = Vec::new();
+    ) -> Result<(Vec<Block<'a>>, usize), anyhow::Error> {
+        let mut children: Vec<Block<'a>> = Vec::new();
         let mut index = start_index;
 
         while index < db_blocks.len() {
@@ -200,6 +392,126 @@ impl DbBlockMatrix {
                             }
                         }
                     }
+                    DbBlock::Property(db_p) => {
+                        if let Some(parent_category_id) = db_p.parent_category_id {
+                            if parent_category_id
+                                == db_category_parent
+                                    .id
+                                    .context("Id was not present on db category")?
+                            {
+                                let child_p = PropertyBlock::from_db_type(db_p);
+                                children.push(Block::Property(child_p));
+                            } else {
+                                return Ok((children, index - 1))
+                            }
+                        }
+                    }
+                    DbBlock::Verbatim(db_vb) => {
+                        if let Some(parent_category_id) = db_vb.parent_category_id {
+                            if parent_category_id
+                                == db_category_parent
+                                    .id
+                                    .context("Id was not present on db category")?
+                            {
+                                let child_vb = VerbatimBlock::from_db_type(db_vb);
+                                children.push(Block::Verbatim(child_vb));
+                            } else {
+                                return Ok((children, index - 1))
+                            }
+                        }
+                    }
+                    DbBlock::Keyword(db_k) => {
+                        if let Some(parent_category_id) = db_k.parent_category_id {
+                            if parent_category_id
+                                == db_category_parent
+                                    .id
+                                    .context("Id was not present on db category")?
+                            {
+                                let child_k = KeywordBlock::from_db_type(db_k);
+                                children.push(Block::Keyword(child_k));
+                            } else {
+                                return Ok((children, index - 1))
+                            }
+                        }
+                    }
+                    DbBlock::List(db_l) => {
+                        let parent_category_id = db_category_parent
+                            .id
+                            .context("Id was not present on db category")?;
+
+                        if db_l.parent_category_id != Some(parent_category_id) {
+                            return Ok((children, index - 1));
+                        }
+
+                        let (list, new_index) = self
+                            .get_list(db_blocks, db_l, index)
+                            .context("Failed to get items of list")?;
+
+                        index = new_index;
+                        children.push(Block::List(list));
+                    }
+                    DbBlock::Table(db_t) => {
+                        let parent_category_id = db_category_parent
+                            .id
+                            .context("Id was not present on db category")?;
+
+                        if db_t.parent_category_id != Some(parent_category_id) {
+                            return Ok((children, index - 1));
+                        }
+
+                        let (table, new_index) = self.get_table(db_blocks, db_t, index);
+
+                        index = new_index;
+                        children.push(Block::Table(table));
+                    }
+                    DbBlock::Drawer(db_d) => {
+                        let parent_category_id = db_category_parent
+                            .id
+                            .context("Id was not present on db category")?;
+
+                        if db_d.parent_category_id != Some(parent_category_id) {
+                            return Ok((children, index - 1));
+                        }
+
+                        let (drawer, new_index) = self.get_drawer(db_blocks, db_d, index);
+
+                        index = new_index;
+                        children.push(Block::Drawer(drawer));
+                    }
+                    DbBlock::ListItem(_) | DbBlock::TableRow(_) | DbBlock::TableCell(_) => {
+                        return Ok((children, index - 1))
+                    }
+                },
+                DbBlock::List(db_list_parent) => match db_block {
+                    DbBlock::ListItem(db_li) if db_li.parent_list_id == db_list_parent.id => {
+                        let mut child_li = ListItemBlock::from_db_type(db_li);
+
+                        let (new_children, new_index) = self
+                            .get_child_blocks(
+                                db_blocks,
+                                &DbBlock::ListItem(db_li.clone()),
+                                index,
+                                level,
+                            )
+                            .context("Failed to get lists nested in list item")?;
+
+                        index = new_index;
+                        child_li.children = new_children;
+
+                        children.push(Block::ListItem(child_li));
+                    }
+                    _ => return Ok((children, index - 1)),
+                },
+                DbBlock::ListItem(db_list_item_parent) => match db_block {
+                    DbBlock::List(db_l) if db_l.parent_list_item_id == db_list_item_parent.id => {
+                        let (list, new_index) = self
+                            .get_list(db_blocks, db_l, index)
+                            .context("Failed to get items of nested list")?;
+
+                        index = new_index;
+                        children.push(Block::List(list));
+                    }
+                    _ => return Ok((children, index - 1)),
                 },
                 DbBlock::RichText(db_rich_text_parent) => match db_block {
                     DbBlock::Text(db_t) => {
@@ -209,8 +521,8 @@ impl DbBlockMatrix {
                                     .id
                                     .context("Could not get id of rich text parent")?
                             {
-                                let child_t = TextBlock::from_db_type(db_t);
-                                children.push(Block::Text(child_t));
+                                let spans = TextBlock::spans(db_t.id, &db_t.content);
+                                children.extend(spans.into_iter().map(Block::Text));
                             } else {
                                 return Ok((children, index - 1))
                             }
@@ -225,17 +537,94 @@ impl DbBlockMatrix {
         Ok((children, index))
     }
 
+    /// Forms the table described by `db_table` from the rows and cells
+    /// following it
+    fn get_table<'a>(
+        &'a self,
+        db_blocks: &'a [DbBlock],
+        db_table: &DbTableBlock,
+        start_index: usize,
+    ) -> (TableBlock<'a>, usize) {
+        let mut table = TableBlock::from_db_type(db_table);
+        let mut index = start_index;
+
+        while let Some(db_block) = db_blocks.get(index) {
+            match db_block {
+                DbBlock::TableRow(db_tr) if db_tr.parent_table_id == db_table.id => {
+                    table.rows.push(TableRow::from_db_type(db_tr));
+                }
+                DbBlock::TableCell(db_tc) => match table.rows.last_mut() {
+                    Some(row) if db_tc.parent_table_row_id == row.id => {
+                        row.cells.push(TableCell::from_db_type(db_tc))
+                    }
+                    _ => break,
+                },
+                _ => break,
+            }
+
+            index += 1;
+        }
+
+        (table, index)
+    }
+
+    /// Forms the drawer described by `db_drawer` from the properties
+    /// following it
+    fn get_drawer<'a>(
+        &'a self,
+        db_blocks: &'a [DbBlock],
+        db_drawer: &'a DbDrawerBlock,
+        start_index: usize,
+    ) -> (DrawerBlock<'a>, usize) {
+        let mut drawer = DrawerBlock::from_db_type(db_drawer);
+        let mut index = start_index;
+
+        while let Some(DbBlock::Property(db_p)) = db_blocks.get(index) {
+            if db_p.parent_drawer_id.is_none() || db_p.parent_drawer_id != db_drawer.id {
+                break;
+            }
+
+            drawer.properties.push(PropertyBlock::from_db_type(db_p));
+            index += 1;
+        }
+
+        (drawer, index)
+    }
+
+    /// Forms the list described by `db_list` and the items following it
+    fn get_list<'a>(
+        &'a self,
+        db_blocks: &'a Vec<DbBlock>,
+        db_list: &'a DbListBlock,
+        start_index: usize,
+    ) -> Result<(ListBlock<'a>, usize), anyhow::Error> {
+        let mut list = ListBlock::from_db_type(db_list);
+
+        let (items, index) = self.get_child_blocks(
+            db_blocks,
+            &DbBlock::List(db_list.clone()),
+            start_index,
+            0,
+        )?;
+
+        list.children = items;
+
+        Ok((list, index))
+    }
+
     pub fn form_category_block_tree<'a>(
         &'a self,
         db_blocks: &'a Vec<DbBlock>
-    ) -> Result<CategoryBlock, anyhow::Error> {
-        let db_block = &db_blocks[0];
+    ) -> Result<CategoryBlock<'a>, anyhow::Error> {
+        let db_block = db_blocks
+            .first()
+            .context("Category does not exist")?;
 
         if let DbBlock::Category(db_cat_block) = db_block {
-            let mut cat_block = CategoryBlock::from_db_type(&db_cat_block, 0);
+            let mut cat_block = CategoryBlock::from_db_type(db_cat_block, 0);
 
             let (new_children, _) = self
-                .get_child_blocks(&db_blocks, &db_block, 1, 1)
+                .get_child_blocks(db_blocks, db_block, 1, 1)
                 .context("Could not get child blocks in get_category_blocks")?;
 
             cat_block.children = new_children;
@@ -252,7 +641,7 @@ mod tests {
     use anyhow::Context;
     use rusqlite::Connection;
 
-    use crate::{air::*, db::AmbleDB, parsing::Parser};
+    use crate::{air::*, db::AmbleDB, parsing::Parser, rendering::render_to_org};
 
     use core::panic;
     use std::{collections::HashSet, fs};
@@ -260,33 +649,23 @@ mod tests {
     use super::DbBlockMatrix;
 
     fn create_test_matrix() -> Result<(DbBlockMatrix, Connection), anyhow::Error> {
-        let document = fs::read_to_string("../test/org-files/mapreduce.org")
+        let document = fs::read_to_string("test/org/mapreduce.org")
             .context("Should be able to open file")?;
 
         let parser = Parser::new(&document);
         let blocks = parser.parse();
 
 
-        let mut db = AmbleDB::new("amble-test.sqlite").context("Could not create db")?;
+        let mut db = AmbleDB::new(":memory:").context("Could not create db")?;
 
         let test_category = CategoryBlock {
             id: Some(1),
-            name: "Test Category",
-            level: 0,
-            children: blocks,
+            ..CategoryBlock::new("Test Category", 0, blocks)
         };
 
-        let tx = db
-            .connection
-            .transaction()
-            .context("Should be able to create transaction")?;
-
-        test_category
-            .write_to_db(&tx, None)
+        db.write_top_level_category(&test_category)
             .context("Should be able to write test category to db")?;
 
-        tx.commit().unwrap();
-
         let matrix = DbBlockMatrix::new(&db.connection, 1)
             .context("Should be able to create block matrix")?;
 
@@ -301,17 +680,24 @@ mod tests {
         let mut last_rtb_id: i64 = -1;
         let mut last_tb_id: i64 = -1;
 
+        assert!(
+            matrix.rows.windows(2).all(|pair| pair[0].sort_key < pair[1].sort_key),
+            "Rows are not ordered by unique sort keys"
+        );
+
         for row in matrix.rows {
-            if row.category_block_id >= last_category_id {
-                last_category_id = row.category_block_id;
-            } else {
-                panic!(
-                    "Categories are not ordered, {} is not greater than {}",
-                    row.category_block_id, last_category_id
-                );
+            if let DbBlock::Category(DbCategoryBlock { id: Some(id), .. }) = row.block {
+                if id >= last_category_id {
+                    last_category_id = id;
+                } else {
+                    panic!(
+                        "Categories are not ordered, {} is not greater than {}",
+                        id, last_category_id
+                    );
+                }
             }
 
-            if let Some(rtb_id) = row.rich_text_block_id {
+            if let DbBlock::RichText(DbRichTextBlock { id: Some(rtb_id), .. }) = row.block {
                 if rtb_id >= last_rtb_id {
                     last_rtb_id = rtb_id;
                 } else {
@@ -322,7 +708,7 @@ mod tests {
                 }
             }
 
-            if let Some(tb_id) = row.text_block_id {
+            if let DbBlock::Text(DbTextBlock { id: Some(tb_id), .. }) = row.block {
                 if tb_id >= last_tb_id {
                     last_tb_id = tb_id;
                 } else {
@@ -337,18 +723,21 @@ mod tests {
 
     #[test]
     fn ensure_ordering_of_db_blocks() {
-        let (matrix, connection) =
-            create_test_matrix().expect("Should be able to create test matrix");
+        let (matrix, _) = create_test_matrix().expect("Should be able to create test matrix");
         let blocks = matrix
-            .produce_flat_db_block_vec(&connection)
+            .produce_flat_db_block_vec()
             .expect("Should be able to get db blocks");
 
         let mut category_block_ids_so_far: HashSet<i64> = HashSet::new();
         let mut rich_text_block_ids_so_far: HashSet<i64> = HashSet::new();
         let mut text_block_ids_so_far: HashSet<i64> = HashSet::new();
+        let mut list_ids_so_far: HashSet<i64> = HashSet::new();
+        let mut list_item_ids_so_far: HashSet<i64> = HashSet::new();
+        let mut table_ids_so_far: HashSet<i64> = HashSet::new();
+        let mut table_row_ids_so_far: HashSet<i64> = HashSet::new();
+        let mut drawer_ids_so_far: HashSet<i64> = HashSet::new();
 
         for block in blocks {
-            dbg!(&block);
             match block {
                 DbBlock::Category(cat) => match cat.id {
                     Some(id) => {
@@ -356,9 +745,8 @@ mod tests {
                             panic!("Duplicate db category block {}", id)
                         }
                         if let Some(parent_cat) = cat.parent_category_id {
-                            match category_block_ids_so_far.get(&parent_cat) {
-                                None => panic!("Parent category {} of category {} not in category blocks so far", parent_cat, id),
-                                _ => ()
+                            if !category_block_ids_so_far.contains(&parent_cat) {
+                                panic!("Parent category {} of category {} not in category blocks so far", parent_cat, id)
                             }
                         }
                     }
@@ -370,9 +758,8 @@ mod tests {
                             panic!("Duplicate db rich text block {}", id)
                         }
                         if let Some(parent_cat) = rt.parent_category_id {
-                            match category_block_ids_so_far.get(&parent_cat) {
-                                None => panic!("Parent category {} of rich text block {} not in category blocks so far", parent_cat, id),
-                                _ => ()
+                            if !category_block_ids_so_far.contains(&parent_cat) {
+                                panic!("Parent category {} of rich text block {} not in category blocks so far", parent_cat, id)
                             }
                         }
                     }
@@ -384,21 +771,376 @@ mod tests {
                             panic!("Duplicate db rich text block {}", id)
                         }
                         if let Some(parent_cat) = t.parent_category_id {
-                            match category_block_ids_so_far.get(&parent_cat) {
-                                None => panic!("Parent category {} of text block {} not in category blocks so far", parent_cat, id),
-                                _ => ()
+                            if !category_block_ids_so_far.contains(&parent_cat) {
+                                panic!("Parent category {} of text block {} not in category blocks so far", parent_cat, id)
                             }
                         }
                         if let Some(parent_rt) = t.parent_rich_text_block_id {
-                            match rich_text_block_ids_so_far.get(&parent_rt) {
-                                None => panic!("Parent rich text_block {} of text block {} not in category blocks so far", parent_rt, id),
-                                _ => ()
+                            if !rich_text_block_ids_so_far.contains(&parent_rt) {
+                                panic!("Parent rich text_block {} of text block {} not in category blocks so far", parent_rt, id)
                             }
                         }
                     }
                     None => panic!("Text block does not have an id"),
                 },
+                DbBlock::Property(p) => match p.id {
+                    Some(id) => {
+                        if let Some(parent_cat) = p.parent_category_id {
+                            if !category_block_ids_so_far.contains(&parent_cat) {
+                                panic!("Parent category {} of property {} not in category blocks so far", parent_cat, id)
+                            }
+                        }
+                        if let Some(parent_drawer) = p.parent_drawer_id {
+                            if !drawer_ids_so_far.contains(&parent_drawer) {
+                                panic!("Parent drawer {} of property {} not in drawers so far", parent_drawer, id)
+                            }
+                        }
+                    }
+                    None => panic!("Property does not have an id"),
+                },
+                DbBlock::Drawer(d) => {
+                    let id = d.id.expect("Drawer does not have an id");
+                    let parent_cat = d.parent_category_id.expect("Drawer does not have a parent");
+                    assert!(drawer_ids_so_far.insert(id), "Duplicate db drawer {}", id);
+                    assert!(
+                        category_block_ids_so_far.contains(&parent_cat),
+                        "Parent category {} of drawer {} not in category blocks so far",
+                        parent_cat,
+                        id
+                    );
+                }
+                DbBlock::List(l) => match l.id {
+                    Some(id) => {
+                        if !list_ids_so_far.insert(id) {
+                            panic!("Duplicate db list {}", id)
+                        }
+                        if let Some(parent_item) = l.parent_list_item_id {
+                            if !list_item_ids_so_far.contains(&parent_item) {
+                                panic!("Parent list item {} of list {} not in list items so far", parent_item, id)
+                            }
+                        }
+                    }
+                    None => panic!("List does not have an id"),
+                },
+                DbBlock::ListItem(li) => match li.id {
+                    Some(id) => {
+                        if !list_item_ids_so_far.insert(id) {
+                            panic!("Duplicate db list item {}", id)
+                        }
+                        if let Some(parent_list) = li.parent_list_id {
+                            if !list_ids_so_far.contains(&parent_list) {
+                                panic!("Parent list {} of list item {} not in lists so far", parent_list, id)
+                            }
+                        }
+                    }
+                    None => panic!("List item does not have an id"),
+                },
+                DbBlock::Table(t) => {
+                    let id = t.id.expect("Table does not have an id");
+                    let parent_cat = t.parent_category_id.expect("Table does not have a parent");
+                    assert!(table_ids_so_far.insert(id), "Duplicate db table {}", id);
+                    assert!(
+                        category_block_ids_so_far.contains(&parent_cat),
+                        "Parent category {} of table {} not in category blocks so far",
+                        parent_cat,
+                        id
+                    );
+                }
+                DbBlock::TableRow(tr) => {
+                    let id = tr.id.expect("Table row does not have an id");
+                    assert!(table_row_ids_so_far.insert(id), "Duplicate db table row {}", id);
+                    assert!(
+                        tr.parent_table_id.is_some_and(|t| table_ids_so_far.contains(&t)),
+                        "Parent table of table row {} not in tables so far",
+                        id
+                    );
+                }
+                DbBlock::TableCell(tc) => {
+                    assert!(
+                        tc.parent_table_row_id.is_some_and(|tr| table_row_ids_so_far.contains(&tr)),
+                        "Parent row of table cell {:?} not in table rows so far",
+                        tc.id
+                    );
+                }
+                DbBlock::Verbatim(DbVerbatimBlock { id, parent_category_id, .. })
+                | DbBlock::Keyword(DbKeywordBlock { id, parent_category_id, .. }) => {
+                    assert!(
+                        parent_category_id.is_some_and(|c| category_block_ids_so_far.contains(&c)),
+                        "Parent category of verbatim block or keyword {:?} not in categories so far",
+                        id
+                    );
+                }
             }
         }
     }
+
+    #[test]
+    fn round_trip_properties() {
+        let document = "* TODO Design homepage layout
+:PROPERTIES:
+:ID: d775182b
+:END:
+:Project: Website Redesign
+Some notes about the layout
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        let category_id = db
+            .write_top_level_category(&CategoryBlock::new(
+                "Properties",
+                0,
+                Parser::new(document).parse(),
+            ))
+            .expect("Should be able to write category")
+            .category_id;
+
+        let matrix = DbBlockMatrix::new(&db.connection, category_id)
+            .expect("Should be able to create block matrix");
+        let flat_blocks = matrix
+            .produce_flat_db_block_vec()
+            .expect("Should be able to produce db blocks");
+        let category = matrix
+            .form_category_block_tree(&flat_blocks)
+            .expect("Should be able to form category tree");
+
+        assert_eq!(render_to_org(Block::Category(category)), document);
+    }
+
+    #[test]
+    fn round_trip_property_layout() {
+        let document = "* Design homepage layout
+  :PROPERTIES:
+  :ID:       d775182b
+  :END:
+  :Project:  Website Redesign
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        assert_eq!(write_and_read_back(&mut db, None, document), document);
+
+        // Realigning the drawer is saved too
+        let realigned = document.replace(":ID:       ", ":ID: ");
+        assert_eq!(write_and_read_back(&mut db, Some(1), &realigned), realigned);
+
+        // So is whitespace after a value
+        let trailing = realigned.replace("Redesign\n", "Redesign  \n");
+        assert_eq!(write_and_read_back(&mut db, Some(1), &trailing), trailing);
+    }
+
+    #[test]
+    fn round_trip_drawers_as_written() {
+        for document in [
+            "* Empty drawer\n:PROPERTIES:\n:END:\nbody\n",
+            "* Outdented end\n  :PROPERTIES:\n  :ID: d775182b\n:END:\n",
+        ] {
+            let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+            assert_eq!(write_and_read_back(&mut db, None, document), document);
+        }
+
+        let unclosed = "* Unclosed drawer\n  :PROPERTIES:\n  :ID: d775182b\nbody\n";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+        assert_eq!(write_and_read_back(&mut db, None, unclosed), unclosed);
+
+        // Closing the drawer is saved too
+        let closed = unclosed.replace("body", "  :END:\nbody");
+        assert_eq!(write_and_read_back(&mut db, Some(1), &closed), closed);
+    }
+
+    #[test]
+    fn round_trip_indented_tables() {
+        let document = "* Tasks
+  | Task   | Owner |
+  |--------+-------|
+  | Design | Stacy |
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        assert_eq!(write_and_read_back(&mut db, None, document), document);
+
+        // Outdenting the table is saved too
+        let outdented = document.replace("\n  |", "\n|");
+        assert_eq!(write_and_read_back(&mut db, Some(1), &outdented), outdented);
+    }
+
+    fn write_and_read_back(db: &mut AmbleDB, id: Option<i64>, document: &str) -> String {
+        let category_id = db
+            .write_top_level_category(&CategoryBlock {
+                id,
+                ..CategoryBlock::new("Ordering", 0, Parser::new(document).parse())
+            })
+            .expect("Should be able to write category")
+            .category_id;
+
+        read_back(db, category_id)
+    }
+
+    fn read_back(db: &AmbleDB, category_id: i64) -> String {
+        let matrix = DbBlockMatrix::new(&db.connection, category_id)
+            .expect("Should be able to create block matrix");
+        let flat_blocks = matrix
+            .produce_flat_db_block_vec()
+            .expect("Should be able to produce db blocks");
+        let category = matrix
+            .form_category_block_tree(&flat_blocks)
+            .expect("Should be able to form category tree");
+
+        render_to_org(Block::Category(category))
+    }
+
+    #[test]
+    fn round_trip_preserves_document_order() {
+        let document = "Text before the first headline
+* First
+:Effort: 2h
+Text under first
+** Nested
+:PROPERTIES:
+:ID: 1234
+:END:
+Text under nested
+*** Deeper
+** Second nested
+* Second
+More text
+
+Text after a blank line
+* Third
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        assert_eq!(write_and_read_back(&mut db, None, document), document);
+
+        // Saving over an existing category keeps the same order
+        assert_eq!(write_and_read_back(&mut db, Some(1), document), document);
+
+        for path in ["test/org/multilingual.org", "test/org/cjk_and_emoji.org"] {
+            let fixture = fs::read_to_string(path).expect("Should be able to open fixture");
+            assert_eq!(write_and_read_back(&mut db, None, &fixture), fixture);
+        }
+    }
+
+    #[test]
+    fn store_inline_markup_as_lines() {
+        let document = "* Launch\nAsk *Stacy* about ~cargo~\n";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        assert_eq!(write_and_read_back(&mut db, None, document), document);
+
+        let lines: Vec<String> = db
+            .connection
+            .prepare("SELECT content FROM text_blocks")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lines, vec!["Ask *Stacy* about ~cargo~\n"]);
+
+        let matrix = DbBlockMatrix::new(&db.connection, 1).expect("Should create block matrix");
+        let flat_blocks = matrix.produce_flat_db_block_vec().expect("Should produce db blocks");
+        let category = matrix
+            .form_category_block_tree(&flat_blocks)
+            .expect("Should form category tree");
+
+        let Block::Category(launch) = &category.children[0] else {
+            panic!("Expected a category");
+        };
+        let Block::RichText(line) = &launch.children[0] else {
+            panic!("Expected rich text");
+        };
+        let markups: Vec<Option<Markup>> = line
+            .children
+            .iter()
+            .filter_map(|span| match span {
+                Block::Text(text) => Some(text.markup),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(markups, vec![None, Some(Markup::Bold), None, Some(Markup::Code), None]);
+    }
+
+    #[test]
+    fn round_trip_verbatim_blocks_and_keywords() {
+        let document = "  #+TITLE:   Build notes
+#+STARTUP:
+* Script
+#+BEGIN_SRC sh :results none
+* not a headline
+#+END_SRC
+Run it daily
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        assert_eq!(write_and_read_back(&mut db, None, document), document);
+
+        let stored = |db: &AmbleDB| -> (i64, String) {
+            db.connection
+                .query_row("SELECT id, language FROM verbatim_blocks", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .expect("Should be able to select verbatim block")
+        };
+        let (verbatim_id, language) = stored(&db);
+        assert_eq!(language, "sh");
+
+        // Editing the block updates it in place
+        let edited = document.replace("* not a headline", "echo done");
+        assert_eq!(write_and_read_back(&mut db, Some(1), &edited), edited);
+        assert_eq!(stored(&db).0, verbatim_id);
+
+        // Respacing a keyword is saved too
+        let respaced = edited.replace("  #+TITLE:   ", "#+TITLE: ");
+        assert_eq!(write_and_read_back(&mut db, Some(1), &respaced), respaced);
+
+        // As are whitespace after a keyword and a block ending the document
+        let trailing = respaced
+            .replace("notes\n", "notes  \n")
+            .replace("#+END_SRC\nRun it daily\n", "#+END_SRC");
+        assert_eq!(write_and_read_back(&mut db, Some(1), &trailing), trailing);
+    }
+
+    #[test]
+    fn order_siblings_by_position() {
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        let category_id = db
+            .write_top_level_category(&CategoryBlock::new(
+                "Ordering",
+                0,
+                Parser::new("* First\n* Second\n").parse(),
+            ))
+            .expect("Should be able to write category")
+            .category_id;
+
+        db.connection
+            .execute(
+                "UPDATE category_blocks SET position = 1 - position WHERE parent_category_id = ?1",
+                [category_id],
+            )
+            .expect("Should be able to swap positions");
+
+        assert_eq!(read_back(&db, category_id), "* Second\n* First\n");
+    }
+
+    #[test]
+    fn missing_category_is_an_error() {
+        let db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        let matrix = DbBlockMatrix::new(&db.connection, 42)
+            .expect("Should be able to create block matrix");
+        let flat_blocks = matrix
+            .produce_flat_db_block_vec()
+            .expect("Should be able to produce db blocks");
+
+        assert!(flat_blocks.is_empty());
+        assert!(matrix.form_category_block_tree(&flat_blocks).is_err());
+    }
 }
@@ -0,0 +1,481 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use rusqlite::{Params, Transaction};
+
+use crate::air::{id_key, title_key, LinkTarget};
+
+use super::AmbleDB;
+
+/// A text block, list item or table cell linking to a category
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backlink {
+    /// Id of the top level category the link was found in
+    pub category_id: i64,
+    pub category_name: String,
+    /// Headlines from beneath the top level category down to the headline
+    /// holding the link
+    pub path: Vec<String>,
+    /// The text the link was found in
+    pub content: String,
+    pub description: Option<String>,
+    /// Id of the category the link points at
+    pub target_category_id: i64,
+}
+
+/// The categories links can point at, loaded once per resolution
+struct LinkTargets {
+    /// Id, top level category id and lowercased title of every category,
+    /// ordered by id
+    categories: Vec<(i64, i64, String)>,
+    /// Ids of top level categories by their lowercased name
+    top_level: HashMap<String, i64>,
+    /// Ids of categories by the value of their `ID` or `CUSTOM_ID` property
+    ids: HashMap<String, i64>,
+}
+
+impl LinkTargets {
+    fn load(tx: &Transaction) -> Result<LinkTargets, anyhow::Error> {
+        let mut stmt = tx
+            .prepare_cached(
+                "
+            WITH RECURSIVE roots(id, root_id, name) AS (
+                SELECT id, id, name FROM category_blocks WHERE parent_category_id IS NULL
+                UNION ALL
+                SELECT cb.id, r.root_id, cb.name
+                FROM category_blocks cb
+                INNER JOIN roots r ON cb.parent_category_id = r.id
+            )
+            SELECT r.id, r.root_id, r.name, cb.title
+            FROM roots r
+            INNER JOIN category_blocks cb ON cb.id = r.id
+            ORDER BY r.id",
+            )
+            .context("Could not prepare link target statement")?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .context("Could not query link targets")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Could not read link target")?;
+
+        let mut targets = LinkTargets {
+            categories: Vec::with_capacity(rows.len()),
+            top_level: HashMap::new(),
+            ids: HashMap::new(),
+        };
+
+        for (id, root_id, name, title) in rows {
+            if id == root_id {
+                targets.top_level.entry(title_key(&name)).or_insert(id);
+            }
+            targets.categories.push((id, root_id, title_key(&title)));
+        }
+
+        let mut stmt = tx
+            .prepare_cached(
+                "
+            SELECT p.value, COALESCE(p.parent_category_id, d.parent_category_id) AS category_id
+            FROM properties p
+            LEFT JOIN drawers d ON d.id = p.parent_drawer_id
+            WHERE upper(p.key) IN ('ID', 'CUSTOM_ID') AND category_id IS NOT NULL
+            ORDER BY p.id",
+            )
+            .context("Could not prepare id property statement")?;
+
+        let ids = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))
+            .context("Could not query id properties")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Could not read id property")?;
+
+        for (value, category_id) in ids {
+            targets.ids.entry(id_key(&value)).or_insert(category_id);
+        }
+
+        Ok(targets)
+    }
+
+    /// Finds the headline titled `title`, preferring one beneath `root_id`
+    fn heading(&self, title: &str, root_id: Option<i64>) -> Option<i64> {
+        let title = title_key(title);
+        let mut matches = self.categories.iter().filter(|(_, _, t)| *t == title);
+
+        let first = matches.clone().next()?;
+
+        matches
+            .find(|(_, root, _)| Some(*root) == root_id)
+            .or(Some(first))
+            .map(|(id, _, _)| *id)
+    }
+
+    fn root_of(&self, category_id: i64) -> Option<i64> {
+        self.categories
+            .binary_search_by_key(&category_id, |(id, _, _)| *id)
+            .ok()
+            .map(|index| self.categories[index].1)
+    }
+
+    /// Finds the category a link points at. Headlines are looked for in the
+    /// same top level category as the link first. Files are top level
+    /// categories named after the file, without its directory or extension.
+    fn resolve(
+        &self,
+        kind: &str,
+        target: &str,
+        search: Option<&str>,
+        source_category_id: Option<i64>,
+    ) -> Option<i64> {
+        match kind {
+            "id" => self.ids.get(&id_key(target)).copied(),
+            "heading" => self.heading(target, source_category_id.and_then(|id| self.root_of(id))),
+            "file" => {
+                let (stem, _) = LinkTarget::File { path: target, search: None }.keys();
+                let root_id = *self.top_level.get(&stem?)?;
+
+                let heading = search.and_then(|search| {
+                    self.heading(search, Some(root_id))
+                        .filter(|id| self.root_of(*id) == Some(root_id))
+                });
+
+                Some(heading.unwrap_or(root_id))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Points the non-url links matching `condition` at the categories they
+/// currently link to, or at nothing when no category matches
+fn resolve_links_where(
+    tx: &Transaction,
+    condition: &str,
+    params: impl Params,
+) -> Result<(), anyhow::Error> {
+    let targets = LinkTargets::load(tx)?;
+
+    let mut stmt = tx
+        .prepare_cached(&format!(
+            "
+        SELECT l.id, l.kind, l.target, l.search, l.target_category_id, ls.category_id
+        FROM links l
+        INNER JOIN link_sources ls ON ls.link_id = l.id
+        WHERE l.kind != 'url' AND ({})",
+            condition
+        ))
+        .context("Could not prepare link statement")?;
+
+    let links = stmt
+        .query_map(params, |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+                row.get::<_, Option<i64>>(5)?,
+            ))
+        })
+        .context("Could not query links")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Could not read link")?;
+
+    for (id, kind, target, search, stored, source_category_id) in links {
+        let resolved = targets.resolve(&kind, &target, search.as_deref(), source_category_id);
+
+        if resolved != stored {
+            tx.execute(
+                "UPDATE links SET target_category_id = ?1 WHERE id = ?2",
+                (resolved, id),
+            )
+            .context(format!("Could not resolve link with id {}", id))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Points every stored link at the category it currently links to, or at
+/// nothing when no category matches
+pub(crate) fn resolve_links(tx: &Transaction) -> Result<(), anyhow::Error> {
+    tx.execute("DELETE FROM link_target_changes", [])
+        .context("Could not clear link target changes")?;
+
+    resolve_links_where(tx, "TRUE", [])
+}
+
+/// The id of the last link written, which links written later are newer
+/// than
+pub(crate) fn last_link_id(tx: &Transaction) -> Result<i64, anyhow::Error> {
+    tx.query_row("SELECT COALESCE(MAX(id), 0) FROM links", [], |row| row.get(0))
+        .context("Could not find the last link")
+}
+
+/// Resolves the links that may point somewhere else after a save: those
+/// written after the link with `last_link_id`, and those looked up by a
+/// title, name or id that was added, changed or removed since the last
+/// resolution
+pub(crate) fn resolve_changed_links(
+    tx: &Transaction,
+    last_link_id: i64,
+) -> Result<(), anyhow::Error> {
+    let mut stmt = tx
+        .prepare_cached("SELECT DISTINCT kind, value FROM link_target_changes")
+        .context("Could not prepare link target change statement")?;
+
+    let changes = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .context("Could not query link target changes")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Could not read link target change")?;
+
+    tx.execute("DELETE FROM link_target_changes", [])
+        .context("Could not clear link target changes")?;
+
+    let mut keys: HashSet<(&str, String)> = HashSet::new();
+
+    for (kind, value) in &changes {
+        let key = match kind.as_str() {
+            "id" => id_key(value),
+            _ => title_key(value),
+        };
+
+        if !key.is_empty() {
+            keys.insert((kind.as_str(), key));
+        }
+    }
+
+    let mut link_ids: Vec<i64> = Vec::new();
+
+    for (kind, key) in &keys {
+        // Titles are searched for by heading links and within files, names
+        // are the files themselves
+        let sql = match *kind {
+            "title" => {
+                "SELECT id FROM links WHERE kind = 'heading' AND target_key = ?1
+                UNION SELECT id FROM links WHERE search_key = ?1"
+            }
+            "name" => "SELECT id FROM links WHERE kind = 'file' AND target_key = ?1",
+            _ => "SELECT id FROM links WHERE kind = 'id' AND target_key = ?1",
+        };
+
+        let mut stmt = tx
+            .prepare_cached(sql)
+            .context("Could not prepare changed link statement")?;
+
+        for id in stmt
+            .query_map([key], |row| row.get::<_, i64>(0))
+            .context("Could not query changed links")?
+        {
+            link_ids.push(id.context("Could not read changed link")?);
+        }
+    }
+
+    let written = tx
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM links WHERE id > ?1 AND kind != 'url')",
+            [last_link_id],
+            |row| row.get::<_, bool>(0),
+        )
+        .context("Could not query written links")?;
+
+    if link_ids.is_empty() && !written {
+        return Ok(());
+    }
+
+    link_ids.sort_unstable();
+    link_ids.dedup();
+
+    let link_ids = serde_json::to_string(&link_ids).context("Could not list changed links")?;
+
+    resolve_links_where(
+        tx,
+        "l.id > ?1 OR l.id IN (SELECT value FROM json_each(?2))",
+        (last_link_id, link_ids),
+    )
+}
+
+impl AmbleDB {
+    /// Lists the links pointing at the category with `category_id` or at
+    /// any headline beneath it, in the order they were written
+    pub fn backlinks(&self, category_id: i64) -> Result<Vec<Backlink>, anyhow::Error> {
+        let mut stmt = self
+            .connection
+            .prepare_cached(
+                "
+            WITH RECURSIVE descendants(id) AS (
+                SELECT ?1
+                UNION ALL
+                SELECT cb.id FROM category_blocks cb
+                INNER JOIN descendants d ON cb.parent_category_id = d.id
+            )
+            SELECT ls.category_id, ls.content, l.description, l.target_category_id
+            FROM links l
+            INNER JOIN descendants d ON d.id = l.target_category_id
+            INNER JOIN link_sources ls ON ls.link_id = l.id
+            ORDER BY l.id",
+            )
+            .context("Could not prepare backlink statement")?;
+
+        let rows = stmt
+            .query_map([category_id], |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })
+            .context(format!("Could not query backlinks of {}", category_id))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Could not read backlink")?;
+
+        let mut backlinks: Vec<Backlink> = Vec::new();
+
+        for (source_category_id, content, description, target_category_id) in rows {
+            let Some(location) = self.locate(source_category_id)? else {
+                continue;
+            };
+
+            backlinks.push(Backlink {
+                category_id: location.category_id,
+                category_name: location.category_name,
+                path: location.path,
+                content: content.trim().to_string(),
+                description,
+                target_category_id,
+            });
+        }
+
+        Ok(backlinks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{save_document, AmbleDB};
+
+    fn child_id(db: &AmbleDB, title: &str) -> i64 {
+        db.connection
+            .query_row("SELECT id FROM category_blocks WHERE title = ?1", [title], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    /// Describes each backlink by the headline and text holding it
+    fn sources(db: &AmbleDB, category_id: i64) -> Vec<String> {
+        db.backlinks(category_id)
+            .unwrap()
+            .into_iter()
+            .map(|backlink| format!("{}: {}", backlink.path.join(" > "), backlink.content))
+            .collect()
+    }
+
+    #[test]
+    fn resolve_links_between_categories() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        // Links may point at categories that have not been saved yet
+        let journal = save_document(
+            &mut db,
+            None,
+            "Journal",
+            "* Monday
+Planned the [[file:notes/Work.org::*Website][website]] launch
+- Ask about [[id:budget-2024]]
+* Tuesday
+| Read | [[https://orgmode.org][the manual]] |
+See [[file:Work.org]] and [[*Monday]]
+",
+        )
+        .category_id;
+        let work = save_document(
+            &mut db,
+            None,
+            "Work",
+            "* Website
+** Budget
+:PROPERTIES:
+:ID: budget-2024
+:END:
+* Monday
+",
+        )
+        .category_id;
+
+        assert_eq!(
+            sources(&db, work),
+            vec![
+                "Monday: Planned the [[file:notes/Work.org::*Website][website]] launch",
+                "Monday: Ask about [[id:budget-2024]]",
+                "Tuesday: See [[file:Work.org]] and [[*Monday]]",
+            ]
+        );
+        assert_eq!(sources(&db, child_id(&db, "Budget")).len(), 1);
+
+        // Headlines are looked for in the note holding the link first
+        let journal_monday = db.backlinks(journal).unwrap();
+        assert_eq!(journal_monday.len(), 1);
+        assert_ne!(journal_monday[0].target_category_id, work);
+
+        // Removing the target leaves the link unresolved
+        save_document(&mut db, Some(work), "Work", "* Website\n* Monday\n");
+        assert_eq!(sources(&db, work).len(), 2);
+
+        // Editing the link points it elsewhere
+        save_document(&mut db, Some(journal), "Journal", "* Monday\nNothing planned\n");
+        assert!(sources(&db, work).is_empty());
+        assert!(db.backlinks(journal).unwrap().is_empty());
+    }
+
+    #[test]
+    fn resolve_only_links_affected_by_a_save() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let journal = save_document(
+            &mut db,
+            None,
+            "Journal",
+            "* Monday\nSee [[*Website]] and [[id:budget-2024]]\n",
+        )
+        .category_id;
+        let work = save_document(&mut db, None, "Work", "* Website\n* Budget\n").category_id;
+
+        let targets = |db: &AmbleDB| -> Vec<Option<i64>> {
+            let mut stmt = db
+                .connection
+                .prepare("SELECT target_category_id FROM links ORDER BY id")
+                .unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+        };
+        assert_eq!(targets(&db), vec![Some(child_id(&db, "Website")), None]);
+
+        // Links are left alone by saves that cannot change where they point
+        db.connection
+            .execute("UPDATE links SET target_category_id = ?1", [journal])
+            .unwrap();
+        save_document(&mut db, Some(work), "Work", "* Website\nDesigns\n* Budget\n");
+        assert_eq!(targets(&db), vec![Some(journal), Some(journal)]);
+
+        // Adding an id only resolves the links to it
+        save_document(
+            &mut db,
+            Some(work),
+            "Work",
+            "* Website\nDesigns\n* Budget\n:PROPERTIES:\n:ID: budget-2024\n:END:\n",
+        );
+        assert_eq!(targets(&db), vec![Some(journal), Some(child_id(&db, "Budget"))]);
+
+        // Renaming the headline and removing the id resolves the links to them
+        save_document(&mut db, Some(work), "Work", "* Site\n* Budget\n");
+        assert_eq!(targets(&db), vec![None, None]);
+    }
+}
@@ -0,0 +1,929 @@
+use anyhow::{anyhow, Context};
+use rusqlite::{Connection, OptionalExtension, Transaction};
+
+use crate::air::{write_headline, write_links, write_property_timestamps, write_text_timestamps};
+
+use super::links::resolve_links;
+
+/// A numbered change to the schema. A database at version n has had the
+/// first n migrations applied, in order.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// The version of the data derived from stored text: the parts of
+/// headlines, timestamps and links. Migrations only change the schema, so
+/// replaying them never depends on how text is parsed today. Bump this
+/// whenever the parsing behind that data changes, and databases indexed at
+/// an older version are reindexed when they are next opened.
+pub const INDEX_VERSION: u32 = 2;
+
+fn query_ids_and_text(
+    tx: &Transaction,
+    sql: &str,
+) -> Result<Vec<(i64, String)>, anyhow::Error> {
+    let mut stmt = tx
+        .prepare(sql)
+        .context(format!("Could not prepare statement {}", sql))?;
+
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+        .context(format!("Could not query {}", sql))?
+        .collect::<Result<Vec<_>, _>>()
+        .context(format!("Could not read row of {}", sql))?;
+
+    Ok(rows)
+}
+
+/// Derives the parts of every headline, the timestamps in every text block
+/// and property, and the links in every text block, list item and table
+/// cell, along with the categories they point at
+fn reindex(tx: &Transaction) -> Result<(), anyhow::Error> {
+    for (id, name) in query_ids_and_text(tx, "SELECT id, name FROM category_blocks")? {
+        write_headline(tx, id, &name)?;
+    }
+
+    for (id, content) in query_ids_and_text(tx, "SELECT id, content FROM text_blocks")? {
+        write_text_timestamps(tx, id, &content)?;
+    }
+
+    let mut stmt = tx
+        .prepare("SELECT id, key, value FROM properties")
+        .context("Could not prepare property statement")?;
+
+    let properties = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .context("Could not query properties")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Could not read property")?;
+
+    for (id, key, value) in properties {
+        write_property_timestamps(tx, id, &key, &value)?;
+    }
+
+    for (table, owner_column) in [
+        ("text_blocks", "text_block_id"),
+        ("list_items", "list_item_id"),
+        ("table_cells", "table_cell_id"),
+    ] {
+        let sql = format!("SELECT id, content FROM {}", table);
+
+        for (id, content) in query_ids_and_text(tx, &sql)? {
+            write_links(tx, owner_column, id, &content)?;
+        }
+    }
+
+    resolve_links(tx)
+}
+
+/// Every migration, ordered by version. New migrations are appended here,
+/// existing ones must never be edited once released. Migrations are plain
+/// SQL, data derived from text is filled in by `reindex` once they have all
+/// been applied.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create category, rich text and text blocks",
+        sql: "
+            CREATE TABLE category_blocks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE
+            );
+            CREATE TABLE rich_text_blocks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE
+            );
+            CREATE TABLE text_blocks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                content TEXT,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+                parent_rich_text_block_id INTEGER NULL REFERENCES rich_text_blocks(id) ON DELETE CASCADE
+            );",
+    },
+    Migration {
+        version: 2,
+        description: "Create properties",
+        sql: "
+            CREATE TABLE properties (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key TEXT,
+                value TEXT,
+                in_drawer INTEGER NOT NULL DEFAULT 0,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE
+            );",
+    },
+    Migration {
+        version: 3,
+        description: "Order siblings by an explicit position",
+        // Existing categories were loaded with their properties first, then
+        // their text, then their subcategories, each in id order. Positions
+        // are numbered within each parent in that order.
+        sql: "
+            ALTER TABLE category_blocks ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE rich_text_blocks ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE text_blocks ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE properties ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+            CREATE TEMP TABLE sibling_positions AS
+            SELECT kind, id,
+                ROW_NUMBER() OVER (PARTITION BY parent ORDER BY rank, id) - 1 AS position
+            FROM (
+                SELECT 'property' AS kind, id, parent_category_id AS parent, 0 AS rank
+                FROM properties
+                UNION ALL
+                SELECT 'rich_text', id, parent_category_id, 1 FROM rich_text_blocks
+                UNION ALL
+                SELECT 'text', id, parent_category_id, 1 FROM text_blocks
+                WHERE parent_rich_text_block_id IS NULL
+                UNION ALL
+                SELECT 'category', id, parent_category_id, 2 FROM category_blocks
+            )
+            UNION ALL
+            SELECT 'text', id,
+                ROW_NUMBER() OVER (PARTITION BY parent_rich_text_block_id ORDER BY id) - 1
+            FROM text_blocks
+            WHERE parent_rich_text_block_id IS NOT NULL;
+
+            UPDATE category_blocks SET position = (
+                SELECT position FROM sibling_positions s
+                WHERE s.kind = 'category' AND s.id = category_blocks.id
+            );
+            UPDATE rich_text_blocks SET position = (
+                SELECT position FROM sibling_positions s
+                WHERE s.kind = 'rich_text' AND s.id = rich_text_blocks.id
+            );
+            UPDATE text_blocks SET position = (
+                SELECT position FROM sibling_positions s
+                WHERE s.kind = 'text' AND s.id = text_blocks.id
+            );
+            UPDATE properties SET position = (
+                SELECT position FROM sibling_positions s
+                WHERE s.kind = 'property' AND s.id = properties.id
+            );
+
+            DROP TABLE sibling_positions;",
+    },
+    Migration {
+        version: 4,
+        description: "Index parent pointers",
+        sql: "
+            CREATE INDEX category_blocks_parent ON category_blocks(parent_category_id);
+            CREATE INDEX rich_text_blocks_parent ON rich_text_blocks(parent_category_id);
+            CREATE INDEX text_blocks_parent ON text_blocks(parent_category_id);
+            CREATE INDEX text_blocks_rich_text_parent ON text_blocks(parent_rich_text_block_id);
+            CREATE INDEX properties_parent ON properties(parent_category_id);",
+    },
+    Migration {
+        version: 5,
+        description: "Index text and headlines for full text search",
+        // External content tables, kept in sync with their source tables by
+        // triggers. Rows removed by cascading deletes fire the triggers too.
+        sql: "
+            CREATE VIRTUAL TABLE text_search USING fts5(
+                content, content = 'text_blocks', content_rowid = 'id'
+            );
+            CREATE TRIGGER text_search_insert AFTER INSERT ON text_blocks BEGIN
+                INSERT INTO text_search (rowid, content) VALUES (new.id, new.content);
+            END;
+            CREATE TRIGGER text_search_delete AFTER DELETE ON text_blocks BEGIN
+                INSERT INTO text_search (text_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+            END;
+            CREATE TRIGGER text_search_update AFTER UPDATE OF content ON text_blocks BEGIN
+                INSERT INTO text_search (text_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+                INSERT INTO text_search (rowid, content) VALUES (new.id, new.content);
+            END;
+            INSERT INTO text_search (text_search) VALUES ('rebuild');
+
+            CREATE VIRTUAL TABLE category_search USING fts5(
+                name, content = 'category_blocks', content_rowid = 'id'
+            );
+            CREATE TRIGGER category_search_insert AFTER INSERT ON category_blocks BEGIN
+                INSERT INTO category_search (rowid, name) VALUES (new.id, new.name);
+            END;
+            CREATE TRIGGER category_search_delete AFTER DELETE ON category_blocks BEGIN
+                INSERT INTO category_search (category_search, rowid, name)
+                VALUES ('delete', old.id, old.name);
+            END;
+            CREATE TRIGGER category_search_update AFTER UPDATE OF name ON category_blocks BEGIN
+                INSERT INTO category_search (category_search, rowid, name)
+                VALUES ('delete', old.id, old.name);
+                INSERT INTO category_search (rowid, name) VALUES (new.id, new.name);
+            END;
+            INSERT INTO category_search (category_search) VALUES ('rebuild');",
+    },
+    Migration {
+        version: 6,
+        description: "Store the TODO keyword, priority, title and tags of headlines",
+        sql: "
+            ALTER TABLE category_blocks ADD COLUMN todo_keyword TEXT NULL;
+            ALTER TABLE category_blocks ADD COLUMN priority TEXT NULL;
+            ALTER TABLE category_blocks ADD COLUMN title TEXT NOT NULL DEFAULT '';
+            CREATE TABLE category_tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                category_id INTEGER NOT NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+                tag TEXT NOT NULL,
+                position INTEGER NOT NULL
+            );
+            CREATE INDEX category_tags_category ON category_tags(category_id);
+            CREATE INDEX category_tags_tag ON category_tags(tag);",
+    },
+    Migration {
+        version: 7,
+        description: "Store timestamps found in text and properties",
+        // A timestamp belongs to either a text block or a property. Starts
+        // and ends are ISO 8601 strings, which sort chronologically.
+        sql: "
+            CREATE TABLE timestamps (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                text_block_id INTEGER NULL REFERENCES text_blocks(id) ON DELETE CASCADE,
+                property_id INTEGER NULL REFERENCES properties(id) ON DELETE CASCADE,
+                label TEXT NULL,
+                active INTEGER NOT NULL,
+                starts_at TEXT NOT NULL,
+                ends_at TEXT NULL,
+                repeater TEXT NULL
+            );
+            CREATE INDEX timestamps_text_block ON timestamps(text_block_id);
+            CREATE INDEX timestamps_property ON timestamps(property_id);
+            CREATE INDEX timestamps_starts_at ON timestamps(starts_at);",
+    },
+    Migration {
+        version: 8,
+        description: "Store lists and their items",
+        // Lists belong to a category, or to the list item they are nested
+        // in. Items are indexed for search like text blocks.
+        sql: "
+            CREATE TABLE lists (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ordered INTEGER NOT NULL DEFAULT 0,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+                parent_list_item_id INTEGER NULL REFERENCES list_items(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE list_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                bullet TEXT NOT NULL,
+                checkbox TEXT NULL,
+                content TEXT NOT NULL,
+                parent_list_id INTEGER NOT NULL REFERENCES lists(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX lists_parent ON lists(parent_category_id);
+            CREATE INDEX lists_list_item_parent ON lists(parent_list_item_id);
+            CREATE INDEX list_items_parent ON list_items(parent_list_id);
+
+            CREATE VIRTUAL TABLE list_item_search USING fts5(
+                content, content = 'list_items', content_rowid = 'id'
+            );
+            CREATE TRIGGER list_item_search_insert AFTER INSERT ON list_items BEGIN
+                INSERT INTO list_item_search (rowid, content) VALUES (new.id, new.content);
+            END;
+            CREATE TRIGGER list_item_search_delete AFTER DELETE ON list_items BEGIN
+                INSERT INTO list_item_search (list_item_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+            END;
+            CREATE TRIGGER list_item_search_update AFTER UPDATE OF content ON list_items BEGIN
+                INSERT INTO list_item_search (list_item_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+                INSERT INTO list_item_search (rowid, content) VALUES (new.id, new.content);
+            END;",
+    },
+    Migration {
+        version: 9,
+        description: "Store tables as rows of cells",
+        // Rules separating rows are stored as rows without cells. Cells are
+        // indexed for search like text blocks.
+        sql: "
+            CREATE TABLE tables (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE table_rows (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                rule INTEGER NOT NULL DEFAULT 0,
+                parent_table_id INTEGER NOT NULL REFERENCES tables(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE table_cells (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                content TEXT NOT NULL,
+                parent_table_row_id INTEGER NOT NULL REFERENCES table_rows(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX tables_parent ON tables(parent_category_id);
+            CREATE INDEX table_rows_parent ON table_rows(parent_table_id);
+            CREATE INDEX table_cells_parent ON table_cells(parent_table_row_id);
+
+            CREATE VIRTUAL TABLE table_cell_search USING fts5(
+                content, content = 'table_cells', content_rowid = 'id'
+            );
+            CREATE TRIGGER table_cell_search_insert AFTER INSERT ON table_cells BEGIN
+                INSERT INTO table_cell_search (rowid, content) VALUES (new.id, new.content);
+            END;
+            CREATE TRIGGER table_cell_search_delete AFTER DELETE ON table_cells BEGIN
+                INSERT INTO table_cell_search (table_cell_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+            END;
+            CREATE TRIGGER table_cell_search_update AFTER UPDATE OF content ON table_cells BEGIN
+                INSERT INTO table_cell_search (table_cell_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+                INSERT INTO table_cell_search (rowid, content) VALUES (new.id, new.content);
+            END;",
+    },
+    Migration {
+        version: 10,
+        description: "Store links and the categories they point at",
+        // A link belongs to a text block, list item or table cell. The view
+        // finds the category holding each link and the text it was found in.
+        sql: "
+            CREATE TABLE links (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                text_block_id INTEGER NULL REFERENCES text_blocks(id) ON DELETE CASCADE,
+                list_item_id INTEGER NULL REFERENCES list_items(id) ON DELETE CASCADE,
+                table_cell_id INTEGER NULL REFERENCES table_cells(id) ON DELETE CASCADE,
+                kind TEXT NOT NULL,
+                target TEXT NOT NULL,
+                search TEXT NULL,
+                description TEXT NULL,
+                target_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE SET NULL
+            );
+            CREATE INDEX links_text_block ON links(text_block_id);
+            CREATE INDEX links_list_item ON links(list_item_id);
+            CREATE INDEX links_table_cell ON links(table_cell_id);
+            CREATE INDEX links_target_category ON links(target_category_id);
+
+            CREATE VIEW link_sources(link_id, category_id, content) AS
+            WITH RECURSIVE list_categories(id, category_id) AS (
+                SELECT id, parent_category_id FROM lists
+                WHERE parent_category_id IS NOT NULL
+                UNION ALL
+                SELECT l.id, lc.category_id
+                FROM lists l
+                INNER JOIN list_items li ON l.parent_list_item_id = li.id
+                INNER JOIN list_categories lc ON li.parent_list_id = lc.id
+            )
+            SELECT l.id,
+                COALESCE(
+                    tb.parent_category_id, rtb.parent_category_id, lc.category_id,
+                    t.parent_category_id
+                ),
+                COALESCE(tb.content, li.content, tc.content)
+            FROM links l
+            LEFT JOIN text_blocks tb ON tb.id = l.text_block_id
+            LEFT JOIN rich_text_blocks rtb ON rtb.id = tb.parent_rich_text_block_id
+            LEFT JOIN list_items li ON li.id = l.list_item_id
+            LEFT JOIN list_categories lc ON lc.id = li.parent_list_id
+            LEFT JOIN table_cells tc ON tc.id = l.table_cell_id
+            LEFT JOIN table_rows tr ON tr.id = tc.parent_table_row_id
+            LEFT JOIN tables t ON t.id = tr.parent_table_id;",
+    },
+    Migration {
+        version: 11,
+        description: "Store verbatim blocks and keywords",
+        // The begin and end lines of a block are kept as written. Its kind,
+        // language and parameters are parsed from the begin line on write.
+        sql: "
+            CREATE TABLE verbatim_blocks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                language TEXT NULL,
+                parameters TEXT NOT NULL DEFAULT '',
+                begin_line TEXT NOT NULL,
+                content TEXT NOT NULL,
+                end_line TEXT NOT NULL,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE keywords (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX verbatim_blocks_parent ON verbatim_blocks(parent_category_id);
+            CREATE INDEX verbatim_blocks_language ON verbatim_blocks(language);
+            CREATE INDEX keywords_parent ON keywords(parent_category_id);
+            CREATE INDEX keywords_key ON keywords(key);",
+    },
+    Migration {
+        version: 12,
+        description: "Track the version of data derived from text",
+        // Every database starts out unindexed, so the data derived from its
+        // text is filled in right after migrating
+        sql: "
+            CREATE TABLE index_version (version INTEGER NOT NULL);
+            INSERT INTO index_version (version) VALUES (0);",
+    },
+    Migration {
+        version: 13,
+        description: "Keep the indentation and alignment of properties",
+        sql: "
+            ALTER TABLE properties ADD COLUMN indent TEXT NOT NULL DEFAULT '';
+            ALTER TABLE properties ADD COLUMN separator TEXT NOT NULL DEFAULT '';
+            UPDATE properties SET separator = ' ' WHERE value != '';",
+    },
+    Migration {
+        version: 14,
+        description: "Keep the indentation of table rows",
+        sql: "ALTER TABLE table_rows ADD COLUMN indent TEXT NOT NULL DEFAULT '';",
+    },
+    Migration {
+        version: 15,
+        description: "Resolve only the links that may point somewhere else after a save",
+        // The titles, names and ids links may be looked up by are recorded
+        // as they are written, so a save only resolves the links matching
+        // them
+        sql: "
+            ALTER TABLE links ADD COLUMN target_key TEXT NULL;
+            ALTER TABLE links ADD COLUMN search_key TEXT NULL;
+            CREATE INDEX links_target_key ON links(kind, target_key);
+            CREATE INDEX links_search_key ON links(search_key);
+
+            CREATE TABLE link_target_changes (
+                kind TEXT NOT NULL,
+                value TEXT NOT NULL
+            );
+
+            CREATE TRIGGER category_link_targets_insert AFTER INSERT ON category_blocks BEGIN
+                INSERT INTO link_target_changes (kind, value)
+                VALUES ('title', new.title), ('name', new.name);
+            END;
+            CREATE TRIGGER category_link_targets_delete AFTER DELETE ON category_blocks BEGIN
+                INSERT INTO link_target_changes (kind, value)
+                VALUES ('title', old.title), ('name', old.name);
+            END;
+            CREATE TRIGGER category_link_targets_update
+            AFTER UPDATE OF name, title, parent_category_id ON category_blocks
+            WHEN old.name IS NOT new.name OR old.title IS NOT new.title
+                OR old.parent_category_id IS NOT new.parent_category_id
+            BEGIN
+                INSERT INTO link_target_changes (kind, value)
+                VALUES ('title', old.title), ('name', old.name),
+                    ('title', new.title), ('name', new.name);
+            END;
+
+            CREATE TRIGGER property_link_targets_insert AFTER INSERT ON properties
+            WHEN upper(new.key) IN ('ID', 'CUSTOM_ID')
+            BEGIN
+                INSERT INTO link_target_changes (kind, value) VALUES ('id', new.value);
+            END;
+            CREATE TRIGGER property_link_targets_delete AFTER DELETE ON properties
+            WHEN upper(old.key) IN ('ID', 'CUSTOM_ID')
+            BEGIN
+                INSERT INTO link_target_changes (kind, value) VALUES ('id', old.value);
+            END;
+            CREATE TRIGGER property_link_targets_update
+            AFTER UPDATE OF key, value, parent_category_id ON properties
+            WHEN (upper(old.key) IN ('ID', 'CUSTOM_ID') OR upper(new.key) IN ('ID', 'CUSTOM_ID'))
+                AND (old.key IS NOT new.key OR old.value IS NOT new.value
+                    OR old.parent_category_id IS NOT new.parent_category_id)
+            BEGIN
+                INSERT INTO link_target_changes (kind, value)
+                VALUES ('id', old.value), ('id', new.value);
+            END;",
+    },
+    Migration {
+        version: 16,
+        description: "Keep the indentation and spacing of keywords",
+        sql: "
+            ALTER TABLE keywords ADD COLUMN indent TEXT NOT NULL DEFAULT '';
+            ALTER TABLE keywords ADD COLUMN separator TEXT NOT NULL DEFAULT '';
+            UPDATE keywords SET separator = ' ' WHERE value != '';",
+    },
+    Migration {
+        version: 17,
+        description: "Index verbatim blocks for full text search",
+        sql: "
+            CREATE VIRTUAL TABLE verbatim_search USING fts5(
+                content, content = 'verbatim_blocks', content_rowid = 'id'
+            );
+            CREATE TRIGGER verbatim_search_insert AFTER INSERT ON verbatim_blocks BEGIN
+                INSERT INTO verbatim_search (rowid, content) VALUES (new.id, new.content);
+            END;
+            CREATE TRIGGER verbatim_search_delete AFTER DELETE ON verbatim_blocks BEGIN
+                INSERT INTO verbatim_search (verbatim_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+            END;
+            CREATE TRIGGER verbatim_search_update AFTER UPDATE OF content ON verbatim_blocks
+            BEGIN
+                INSERT INTO verbatim_search (verbatim_search, rowid, content)
+                VALUES ('delete', old.id, old.content);
+                INSERT INTO verbatim_search (rowid, content) VALUES (new.id, new.content);
+            END;
+            INSERT INTO verbatim_search (verbatim_search) VALUES ('rebuild');",
+    },
+    Migration {
+        version: 18,
+        description: "Keep the whitespace after property values",
+        sql: "ALTER TABLE properties ADD COLUMN trailing TEXT NOT NULL DEFAULT '';",
+    },
+    Migration {
+        version: 19,
+        description: "Keep property drawers as written",
+        // Properties that were in a drawer were written one after another,
+        // so each category gets one drawer at the position of its first
+        // such property, indented like it
+        sql: "
+            CREATE TABLE drawers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                begin_line TEXT NOT NULL,
+                end_line TEXT NULL,
+                parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX drawers_parent ON drawers(parent_category_id);
+
+            ALTER TABLE properties
+                ADD COLUMN parent_drawer_id INTEGER NULL REFERENCES drawers(id) ON DELETE CASCADE;
+            CREATE INDEX properties_parent_drawer ON properties(parent_drawer_id);
+
+            INSERT INTO drawers (begin_line, end_line, parent_category_id, position)
+            SELECT p.indent || ':PROPERTIES:', p.indent || ':END:', p.parent_category_id,
+                p.position
+            FROM properties p
+            WHERE p.in_drawer AND p.position = (
+                SELECT MIN(first.position) FROM properties first
+                WHERE first.in_drawer AND first.parent_category_id = p.parent_category_id
+            );
+
+            UPDATE properties SET
+                parent_drawer_id = (
+                    SELECT d.id FROM drawers d
+                    WHERE d.parent_category_id = properties.parent_category_id
+                ),
+                position = position - (
+                    SELECT d.position FROM drawers d
+                    WHERE d.parent_category_id = properties.parent_category_id
+                ),
+                parent_category_id = NULL
+            WHERE in_drawer;
+
+            ALTER TABLE properties DROP COLUMN in_drawer;
+
+            DROP TRIGGER property_link_targets_update;
+            CREATE TRIGGER property_link_targets_update
+            AFTER UPDATE OF key, value, parent_category_id, parent_drawer_id ON properties
+            WHEN (upper(old.key) IN ('ID', 'CUSTOM_ID') OR upper(new.key) IN ('ID', 'CUSTOM_ID'))
+                AND (old.key IS NOT new.key OR old.value IS NOT new.value
+                    OR old.parent_category_id IS NOT new.parent_category_id
+                    OR old.parent_drawer_id IS NOT new.parent_drawer_id)
+            BEGIN
+                INSERT INTO link_target_changes (kind, value)
+                VALUES ('id', old.value), ('id', new.value);
+            END;
+            CREATE TRIGGER drawer_link_targets_update
+            AFTER UPDATE OF parent_category_id ON drawers
+            WHEN old.parent_category_id IS NOT new.parent_category_id
+            BEGIN
+                INSERT INTO link_target_changes (kind, value)
+                SELECT 'id', value FROM properties
+                WHERE parent_drawer_id = new.id AND upper(key) IN ('ID', 'CUSTOM_ID');
+            END;",
+    },
+    Migration {
+        version: 20,
+        description: "Keep the whitespace after keywords and the end of a final verbatim block",
+        sql: "
+            ALTER TABLE keywords ADD COLUMN trailing TEXT NOT NULL DEFAULT '';
+            ALTER TABLE verbatim_blocks ADD COLUMN end_line_break INTEGER NOT NULL DEFAULT 1;",
+    },
+];
+
+/// The version a database is at once every migration has been applied
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+fn table_exists(connection: &Connection, table: &str) -> Result<bool, anyhow::Error> {
+    let exists = connection
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |_| Ok(()),
+        )
+        .optional()
+        .context(format!("Could not check whether table {} exists", table))?;
+
+    Ok(exists.is_some())
+}
+
+/// Returns the version recorded in `schema_version`. Databases created
+/// before versioning was introduced have no such table, so their version
+/// is inferred from the tables they contain.
+pub fn schema_version(connection: &Connection) -> Result<u32, anyhow::Error> {
+    if table_exists(connection, "schema_version")? {
+        return connection
+            .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+            .context("Could not read schema version");
+    }
+
+    if !table_exists(connection, "category_blocks")? {
+        Ok(0)
+    } else if !table_exists(connection, "properties")? {
+        Ok(1)
+    } else {
+        Ok(2)
+    }
+}
+
+/// Brings the database up to the latest version, applying each pending
+/// migration in its own transaction so a failure leaves the database at the
+/// last version that succeeded. Returns the version the database is now at.
+pub fn migrate(connection: &mut Connection) -> Result<u32, anyhow::Error> {
+    let current = schema_version(connection)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(anyhow!(
+            "Database is at schema version {} but this version of Amble only supports up to {}",
+            current,
+            latest
+        ));
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = connection
+            .transaction()
+            .context("Could not create a new transaction")?;
+
+        tx.execute_batch(migration.sql).context(format!(
+            "Could not apply migration {} ({})",
+            migration.version, migration.description
+        ))?;
+
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL);
+             DELETE FROM schema_version;",
+        )
+        .context("Could not reset schema version")?;
+
+        tx.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            [migration.version],
+        )
+        .context("Could not record schema version")?;
+
+        tx.commit()
+            .context(format!("Could not commit migration {}", migration.version))?;
+    }
+
+    let indexed: u32 = connection
+        .query_row("SELECT version FROM index_version", [], |row| row.get(0))
+        .context("Could not read index version")?;
+
+    if indexed < INDEX_VERSION {
+        let tx = connection
+            .transaction()
+            .context("Could not create a new transaction")?;
+
+        reindex(&tx).context("Could not reindex database")?;
+
+        tx.execute("UPDATE index_version SET version = ?1", [INDEX_VERSION])
+            .context("Could not record index version")?;
+
+        tx.commit().context("Could not commit reindex")?;
+    }
+
+    Ok(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use rusqlite::Connection;
+
+    use crate::{air::Block, db::AmbleDB, rendering::render_to_org, DbBlockMatrix};
+
+    use super::*;
+
+    fn load_v1_fixture(connection: &Connection) {
+        let fixture =
+            fs::read_to_string("test/db/v1.sql").expect("Should be able to open fixture");
+        connection
+            .execute_batch(&fixture)
+            .expect("Should be able to load fixture");
+    }
+
+    #[test]
+    fn migrations_are_ordered() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, index + 1);
+        }
+    }
+
+    #[test]
+    fn migrate_fresh_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        assert_eq!(schema_version(&connection).unwrap(), 0);
+        assert_eq!(migrate(&mut connection).unwrap(), latest_version());
+        assert_eq!(schema_version(&connection).unwrap(), latest_version());
+
+        // Migrating an up to date database does nothing
+        assert_eq!(migrate(&mut connection).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn migrate_v1_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        load_v1_fixture(&connection);
+
+        assert_eq!(schema_version(&connection).unwrap(), 1);
+        migrate(&mut connection).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), latest_version());
+
+        let properties: i64 = connection
+            .query_row("SELECT COUNT(*) FROM properties", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(properties, 0);
+    }
+
+    #[test]
+    fn backfill_headlines_of_v1_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        load_v1_fixture(&connection);
+        migrate(&mut connection).unwrap();
+
+        let title: String = connection
+            .query_row("SELECT title FROM category_blocks WHERE id = 2", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(title, "Hello!");
+    }
+
+    #[test]
+    fn backfill_timestamps_of_v1_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        load_v1_fixture(&connection);
+        connection
+            .execute(
+                "UPDATE text_blocks SET content = 'DEADLINE: <2023-10-20 Fri>' WHERE id = 1",
+                [],
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+
+        let (label, starts_at): (String, String) = connection
+            .query_row(
+                "SELECT label, starts_at FROM timestamps WHERE text_block_id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((label.as_str(), starts_at.as_str()), ("DEADLINE", "2023-10-20"));
+    }
+
+    #[test]
+    fn backfill_links_of_v1_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        load_v1_fixture(&connection);
+        connection
+            .execute(
+                "UPDATE text_blocks SET content = 'See [[file:Greetings.org]]' WHERE id = 1",
+                [],
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+
+        let (kind, target_category_id): (String, i64) = connection
+            .query_row(
+                "SELECT kind, target_category_id FROM links WHERE text_block_id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((kind.as_str(), target_category_id), ("file", 1));
+    }
+
+    #[test]
+    fn open_v1_database_file() {
+        let db_path = env::temp_dir().join(format!("amble-v1-{}.sqlite", std::process::id()));
+        let _ = fs::remove_file(&db_path);
+
+        load_v1_fixture(&Connection::open(&db_path).unwrap());
+
+        let db = AmbleDB::new(db_path.to_str().unwrap()).unwrap();
+        assert_eq!(schema_version(&db.connection).unwrap(), latest_version());
+
+        let matrix = DbBlockMatrix::new(&db.connection, 1).unwrap();
+        let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+        let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+        assert_eq!(
+            render_to_org(Block::Category(category)),
+            "* Hello!\nThis is some content\n** Here is some more content\n"
+        );
+
+        drop(db);
+        fs::remove_file(&db_path).unwrap();
+    }
+
+    #[test]
+    fn order_text_of_v1_database_before_subcategories() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        let fixture = fs::read_to_string("test/db/v1_text_after_subcategory.sql")
+            .expect("Should be able to open fixture");
+        connection
+            .execute_batch(&fixture)
+            .expect("Should be able to load fixture");
+        migrate(&mut connection).unwrap();
+
+        let matrix = DbBlockMatrix::new(&connection, 1).unwrap();
+        let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+        let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+        assert_eq!(
+            render_to_org(Block::Category(category)),
+            "* Parent\nbody of parent\nsecond line of parent\n** Child\n"
+        );
+    }
+
+    #[test]
+    fn move_drawer_properties_into_drawers() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        load_v1_fixture(&connection);
+
+        for migration in MIGRATIONS.iter().filter(|m| (2..19).contains(&m.version)) {
+            connection.execute_batch(migration.sql).unwrap();
+        }
+
+        connection
+            .execute_batch(
+                "CREATE TABLE schema_version (version INTEGER NOT NULL);
+                 INSERT INTO schema_version (version) VALUES (18);
+                 INSERT INTO properties
+                    (key, value, in_drawer, indent, separator, parent_category_id, position)
+                 VALUES ('ID', 'd775182b', 1, '  ', ' ', 2, 0),
+                    ('Effort', '2h', 0, '', ' ', 2, 1);
+                 UPDATE rich_text_blocks SET position = 2 WHERE id = 1;
+                 UPDATE category_blocks SET position = 3 WHERE id = 3;",
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+
+        let matrix = DbBlockMatrix::new(&connection, 1).unwrap();
+        let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+        let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+        assert_eq!(
+            render_to_org(Block::Category(category)),
+            "* Hello!\n  :PROPERTIES:\n  :ID: d775182b\n  :END:\n:Effort: 2h\n\
+             This is some content\n** Here is some more content\n"
+        );
+    }
+
+    #[test]
+    fn reindex_outdated_databases() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        load_v1_fixture(&connection);
+        migrate(&mut connection).unwrap();
+
+        connection
+            .execute_batch(
+                "UPDATE category_blocks SET title = '' WHERE id = 2;
+                 UPDATE index_version SET version = 0;",
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+
+        let (title, indexed): (String, u32) = connection
+            .query_row(
+                "SELECT title, (SELECT version FROM index_version) FROM category_blocks
+                WHERE id = 2",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((title.as_str(), indexed), ("Hello!", INDEX_VERSION));
+    }
+
+    #[test]
+    fn refuse_newer_databases() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+
+        connection
+            .execute(
+                "UPDATE schema_version SET version = ?1",
+                [latest_version() + 1],
+            )
+            .unwrap();
+
+        assert!(migrate(&mut connection).is_err());
+    }
+}
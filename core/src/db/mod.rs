@@ -1,6 +1,32 @@
+mod agenda;
 mod amble_db;
-mod schema;
+mod diff;
+mod links;
+mod migrations;
 mod extraction;
+mod search;
 
+pub use agenda::AgendaItem;
 pub use amble_db::*;
+pub use diff::ChangeSummary;
 pub use extraction::*;
+pub use links::Backlink;
+pub use search::SearchHit;
+
+/// Parses `document` into the top level category `name` and saves it, over
+/// the category with `id` when one is given
+#[cfg(test)]
+pub(crate) fn save_document(
+    db: &mut AmbleDB,
+    id: Option<i64>,
+    name: &str,
+    document: &str,
+) -> ChangeSummary {
+    use crate::{air::CategoryBlock, parsing::Parser};
+
+    db.write_top_level_category(&CategoryBlock {
+        id,
+        ..CategoryBlock::new(name, 0, Parser::new(document).parse())
+    })
+    .expect("Should be able to save category")
+}
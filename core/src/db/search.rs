@@ -0,0 +1,302 @@
+use anyhow::Context;
+
+use super::AmbleDB;
+
+/// The most hits `AmbleDB::search` returns
+const MAX_SEARCH_HITS: usize = 100;
+
+/// A headline, line of text, list item, table cell or verbatim block
+/// matching a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// Id of the top level category the match was found in
+    pub category_id: i64,
+    pub category_name: String,
+    /// Headlines from beneath the top level category down to the headline
+    /// containing the match, or that is the match
+    pub path: Vec<String>,
+    /// The matching text, with matched terms wrapped in `[` and `]`
+    pub snippet: String,
+    /// Lower is a better match
+    pub rank: f64,
+}
+
+/// Turns free text into an FTS5 query matching blocks that contain every
+/// word, treating the words as prefixes so partially typed words match
+fn to_fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Where a category sits in the tree: the top level category holding it
+/// and the headlines from beneath that down to the category itself, all
+/// with surrounding whitespace trimmed
+pub(crate) struct Location {
+    pub category_id: i64,
+    pub category_name: String,
+    pub path: Vec<String>,
+}
+
+impl AmbleDB {
+    /// Returns the id and name of the category with `category_id` and of
+    /// each of its ancestors, starting from its top level category
+    pub(crate) fn ancestors(&self, category_id: i64) -> Result<Vec<(i64, String)>, anyhow::Error> {
+        let mut stmt = self
+            .connection
+            .prepare_cached(
+                "
+            WITH RECURSIVE ancestors(id, name, parent_category_id, depth) AS (
+                SELECT id, name, parent_category_id, 0 FROM category_blocks
+                WHERE id = ?1
+                UNION ALL
+                SELECT cb.id, cb.name, cb.parent_category_id, a.depth + 1
+                FROM category_blocks cb
+                INNER JOIN ancestors a ON cb.id = a.parent_category_id
+            )
+            SELECT id, name FROM ancestors ORDER BY depth DESC",
+            )
+            .context("Could not prepare ancestor statement")?;
+
+        let ancestors = stmt
+            .query_map([category_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .context(format!("Could not query ancestors of {}", category_id))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Could not read ancestor")?;
+
+        Ok(ancestors)
+    }
+
+    /// Locates the category with `category_id` beneath its top level
+    /// category. Blocks outside of any category have no location, since
+    /// they cannot be opened.
+    pub(crate) fn locate(
+        &self,
+        category_id: Option<i64>,
+    ) -> Result<Option<Location>, anyhow::Error> {
+        let Some(category_id) = category_id else {
+            return Ok(None);
+        };
+
+        let mut ancestors = self.ancestors(category_id)?.into_iter();
+
+        let Some((top_level_id, top_level_name)) = ancestors.next() else {
+            return Ok(None);
+        };
+
+        Ok(Some(Location {
+            category_id: top_level_id,
+            category_name: top_level_name.trim().to_string(),
+            path: ancestors.map(|(_, name)| name.trim().to_string()).collect(),
+        }))
+    }
+
+    /// Searches headlines, text, list items, table cells and the content of
+    /// verbatim blocks for `query`, returning the best matches first
+    pub fn search(&self, query: &str) -> Result<Vec<SearchHit>, anyhow::Error> {
+        let fts_query = to_fts_query(query);
+
+        if fts_query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut hit_stmt = self
+            .connection
+            .prepare_cached(
+                "
+            WITH RECURSIVE list_categories(id, category_id) AS (
+                SELECT id, parent_category_id FROM lists
+                WHERE parent_category_id IS NOT NULL
+                UNION ALL
+                SELECT l.id, lc.category_id
+                FROM lists l
+                INNER JOIN list_items li ON l.parent_list_item_id = li.id
+                INNER JOIN list_categories lc ON li.parent_list_id = lc.id
+            )
+            SELECT COALESCE(tb.parent_category_id, rtb.parent_category_id),
+                snippet(text_search, 0, '[', ']', '...', 12) AS snippet,
+                text_search.rank AS rank
+            FROM text_search
+            INNER JOIN text_blocks tb ON tb.id = text_search.rowid
+            LEFT JOIN rich_text_blocks rtb ON rtb.id = tb.parent_rich_text_block_id
+            WHERE text_search MATCH ?1
+            UNION ALL
+            SELECT category_search.rowid,
+                snippet(category_search, 0, '[', ']', '...', 12),
+                category_search.rank
+            FROM category_search
+            WHERE category_search MATCH ?1
+            UNION ALL
+            SELECT lc.category_id,
+                snippet(list_item_search, 0, '[', ']', '...', 12),
+                list_item_search.rank
+            FROM list_item_search
+            INNER JOIN list_items li ON li.id = list_item_search.rowid
+            INNER JOIN list_categories lc ON lc.id = li.parent_list_id
+            WHERE list_item_search MATCH ?1
+            UNION ALL
+            SELECT t.parent_category_id,
+                snippet(table_cell_search, 0, '[', ']', '...', 12),
+                table_cell_search.rank
+            FROM table_cell_search
+            INNER JOIN table_cells tc ON tc.id = table_cell_search.rowid
+            INNER JOIN table_rows tr ON tr.id = tc.parent_table_row_id
+            INNER JOIN tables t ON t.id = tr.parent_table_id
+            WHERE table_cell_search MATCH ?1
+            UNION ALL
+            SELECT vb.parent_category_id,
+                snippet(verbatim_search, 0, '[', ']', '...', 12),
+                verbatim_search.rank
+            FROM verbatim_search
+            INNER JOIN verbatim_blocks vb ON vb.id = verbatim_search.rowid
+            WHERE verbatim_search MATCH ?1
+            ORDER BY rank
+            LIMIT ?2",
+            )
+            .context("Could not prepare search statement")?;
+
+        let hits = hit_stmt
+            .query_map((&fts_query, MAX_SEARCH_HITS), |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            })
+            .context(format!("Could not search for {:?}", query))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Could not read search hit")?;
+
+        let mut results: Vec<SearchHit> = Vec::new();
+
+        for (category_id, snippet, rank) in hits {
+            let Some(location) = self.locate(category_id)? else {
+                continue;
+            };
+
+            results.push(SearchHit {
+                category_id: location.category_id,
+                category_name: location.category_name,
+                path: location.path,
+                snippet: snippet.trim().to_string(),
+                rank,
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{save_document, AmbleDB};
+
+    use super::to_fts_query;
+
+    #[test]
+    fn build_fts_queries() {
+        assert_eq!(to_fts_query("  map reduce "), "\"map\"* \"reduce\"*");
+        assert_eq!(to_fts_query("say \"hi\""), "\"say\"* \"\"\"hi\"\"\"*");
+        assert_eq!(to_fts_query(" "), "");
+    }
+
+    #[test]
+    fn search_text_and_headlines() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let papers = save_document(
+            &mut db,
+            None,
+            "Papers",
+            "* Distributed systems
+** MapReduce
+Simplified data processing on large clusters
+* Databases
+Notes on query planning
+",
+        )
+        .category_id;
+        let recipes = save_document(
+            &mut db,
+            None,
+            "Recipes",
+            "* Bread\nNeeds a large bowl\n- Flour\n  - [ ] Rye flour\n| Oven | 220C |\n",
+        )
+        .category_id;
+
+        let hits = db.search("larg").unwrap();
+        assert_eq!(hits.len(), 2);
+
+        let paper_hit = hits.iter().find(|hit| hit.category_id == papers).unwrap();
+        assert_eq!(paper_hit.category_name, "Papers");
+        assert_eq!(paper_hit.path, vec!["Distributed systems", "MapReduce"]);
+        assert_eq!(
+            paper_hit.snippet,
+            "Simplified data processing on [large] clusters"
+        );
+
+        let recipe_hit = hits.iter().find(|hit| hit.category_id == recipes).unwrap();
+        assert_eq!(recipe_hit.path, vec!["Bread"]);
+
+        let headline_hits = db.search("mapreduce").unwrap();
+        assert_eq!(headline_hits.len(), 1);
+        assert_eq!(headline_hits[0].snippet, "[MapReduce]");
+        assert_eq!(headline_hits[0].path, vec!["Distributed systems", "MapReduce"]);
+
+        let item_hits = db.search("rye").unwrap();
+        assert_eq!(item_hits.len(), 1);
+        assert_eq!(item_hits[0].category_id, recipes);
+        assert_eq!(item_hits[0].path, vec!["Bread"]);
+        assert_eq!(item_hits[0].snippet, "[Rye] flour");
+
+        let cell_hits = db.search("oven").unwrap();
+        assert_eq!(cell_hits.len(), 1);
+        assert_eq!(cell_hits[0].path, vec!["Bread"]);
+
+        assert!(db.search("query clusters").unwrap().is_empty());
+        assert!(db.search("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_follows_edits_and_deletes() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let id = save_document(&mut db, None, "Notes", "* Ideas\nBuy a telescope\n").category_id;
+        assert_eq!(db.search("telescope").unwrap().len(), 1);
+
+        save_document(&mut db, Some(id), "Notes", "* Ideas\nBuy a microscope\n");
+        assert!(db.search("telescope").unwrap().is_empty());
+        assert_eq!(db.search("microscope").unwrap().len(), 1);
+
+        save_document(&mut db, Some(id), "Notes", "* Other\n");
+        assert!(db.search("microscope").unwrap().is_empty());
+        assert!(db.search("ideas").unwrap().is_empty());
+        assert_eq!(db.search("other").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn search_verbatim_blocks() {
+        let mut db = AmbleDB::new(":memory:").unwrap();
+
+        let document = "* Deploy\n#+BEGIN_SRC sh\ncargo build --release\n#+END_SRC\n";
+        let id = save_document(&mut db, None, "Scripts", document).category_id;
+
+        let hits = db.search("release").unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].category_id, id);
+        assert_eq!(hits[0].path, vec!["Deploy"]);
+        assert_eq!(hits[0].snippet, "cargo build --[release]");
+
+        let edited = document.replace("--release", "--profile bench");
+        save_document(&mut db, Some(id), "Scripts", &edited);
+        assert!(db.search("release").unwrap().is_empty());
+        assert_eq!(db.search("bench").unwrap().len(), 1);
+
+        save_document(&mut db, Some(id), "Scripts", "* Deploy\n");
+        assert!(db.search("bench").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    ffi::{c_char, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use anyhow::anyhow;
+
+/// Returned by every fallible function exported over the C ABI. When a
+/// function does not return `AmbleStatus::Ok`, `amble_last_error` describes
+/// what went wrong and none of its out parameters have been written.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmbleStatus {
+    Ok = 0,
+    /// The call failed, e.g. because of invalid input or a database error
+    Error = 1,
+    /// Amble panicked while handling the call. The panic was caught before
+    /// it could unwind into the caller
+    Panic = 2,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior nul bytes would truncate the message, so they are dropped
+    let message = CString::new(message.replace('\0', ""))
+        .expect("Message should not contain nul bytes");
+
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Runs `f`, converting errors and panics into an `AmbleStatus` and
+/// recording a description of them for `amble_last_error`
+pub(crate) fn guard<F>(f: F) -> AmbleStatus
+where
+    F: FnOnce() -> Result<(), anyhow::Error>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => AmbleStatus::Ok,
+        Ok(Err(error)) => {
+            set_last_error(format!("{:#}", error));
+            AmbleStatus::Error
+        }
+        Err(payload) => {
+            set_last_error(format!("Amble panicked: {}", panic_message(payload.as_ref())));
+            AmbleStatus::Panic
+        }
+    }
+}
+
+/// Converts a nullable pointer into a reference, failing with a message
+/// naming the argument when it is null
+///
+/// # Safety
+///
+/// `pointer` must be null or valid for reads of `T` for the lifetime `'a`.
+pub(crate) unsafe fn non_null<'a, T>(
+    pointer: *const T,
+    name: &str,
+) -> Result<&'a T, anyhow::Error> {
+    unsafe { pointer.as_ref() }.ok_or_else(|| anyhow!("{} was null", name))
+}
+
+/// Like `non_null`, for pointers the callee writes through
+///
+/// # Safety
+///
+/// `pointer` must be null or valid for writes of `T` for the lifetime `'a`.
+pub(crate) unsafe fn non_null_mut<'a, T>(
+    pointer: *mut T,
+    name: &str,
+) -> Result<&'a mut T, anyhow::Error> {
+    unsafe { pointer.as_mut() }.ok_or_else(|| anyhow!("{} was null", name))
+}
+
+/// Returns a description of the most recent failure on the calling thread,
+/// including the full chain of error context, or null if no call has
+/// failed yet. The string is owned by Amble and remains valid until the
+/// next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn amble_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| match last_error.borrow().as_ref() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
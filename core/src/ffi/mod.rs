@@ -0,0 +1,1054 @@
+mod error;
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+use anyhow::{anyhow, Context};
+
+use crate::{render_to_org, AmbleDB, Block, CategoryBlock, Date, DbBlockMatrix, Parser};
+
+pub use error::*;
+
+/// A top level category passed across the C ABI. Categories written by
+/// `write_category` own their strings, which must be released with
+/// `amble_free_category`.
+#[repr(C)]
+pub struct TopLevelCategory {
+    id: i64,
+    name: *const c_char,
+    content: *const c_char,
+}
+
+pub struct RTopLevelCategory {
+    id: i64,
+    name: String,
+    content: String,
+}
+
+impl TopLevelCategory {
+    fn to_rust(&self) -> Result<RTopLevelCategory, anyhow::Error> {
+        if self.name.is_null() {
+            return Err(anyhow!("Name was null"));
+        }
+        if self.content.is_null() {
+            return Err(anyhow!("Content was null"));
+        }
+
+        let name = unsafe { CStr::from_ptr(self.name) };
+        let content = unsafe { CStr::from_ptr(self.content) };
+
+        Ok(RTopLevelCategory {
+            id: self.id,
+            name: name.to_str().context("Invalid UTF-8 for name")?.to_owned(),
+            content: content
+                .to_str()
+                .context("Invalid UTF-8 for content")?
+                .to_owned(),
+        })
+    }
+}
+
+/// Converts a string into one that can be handed to C
+fn to_c_string(value: String) -> Result<CString, anyhow::Error> {
+    CString::new(value).context("String contained a nul byte")
+}
+
+/// Converts the strings of each item with `convert`, then hands them out as
+/// results made by `into_result`, returning the boxed results and their
+/// length. Every string is converted before any is handed out, so a failed
+/// conversion frees the ones converted so far.
+fn to_c_results<T, S, R>(
+    items: Vec<T>,
+    convert: impl Fn(T) -> Result<S, anyhow::Error>,
+    into_result: impl Fn(S) -> R,
+) -> Result<(*const R, usize), anyhow::Error> {
+    let strings = items.into_iter().map(convert).collect::<Result<Vec<S>, _>>()?;
+
+    let results: Box<[R]> = strings.into_iter().map(into_result).collect();
+    let length = results.len();
+
+    Ok((Box::into_raw(results) as *const R, length))
+}
+
+/// An open Amble database, handed to C callers as an opaque pointer
+pub struct AmbleHandle {
+    db: AmbleDB,
+}
+
+/// Opens the database at `path`, creating it if it does not exist, and
+/// writes the new handle to `out`. `path` may be absolute, relative to the
+/// current directory, or `:memory:`.
+///
+/// # Safety
+///
+/// `path` must be a valid nul terminated string and `out` must be valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn amble_open(
+    path: *const c_char,
+    out: *mut *mut AmbleHandle,
+) -> AmbleStatus {
+    guard(|| {
+        let out = unsafe { non_null_mut(out, "Out pointer") }?;
+
+        let path = unsafe { non_null(path, "Path") }?;
+        let path = unsafe { CStr::from_ptr(path) }
+            .to_str()
+            .context("Invalid UTF-8 for path")?;
+
+        let db = AmbleDB::new(path).context(format!("Could not open database {}", path))?;
+
+        *out = Box::into_raw(Box::new(AmbleHandle { db }));
+
+        Ok(())
+    })
+}
+
+/// Closes a database opened with `amble_open`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `amble_open`, and must not be used
+/// after this call.
+#[no_mangle]
+pub unsafe extern "C" fn amble_close(handle: *mut AmbleHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Loads the category with `id` and everything beneath it, rendered as org
+fn render_category(db: &AmbleDB, id: i64) -> Result<String, anyhow::Error> {
+    let matrix =
+        DbBlockMatrix::new(&db.connection, id).context("Could not create db block matrix")?;
+
+    let flat_blocks = matrix
+        .produce_flat_db_block_vec()
+        .context("Could not produce flat vec of db blocks")?;
+
+    let category_block = matrix
+        .form_category_block_tree(&flat_blocks)
+        .context("Could not get category block")?;
+
+    Ok(render_to_org(Block::Category(category_block)))
+}
+
+/// Parses and saves a top level category, creating it when its id is not
+/// positive, then writes the category as stored back to `out`. The strings
+/// in `out` are owned by the caller and must be released with
+/// `amble_free_category`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `amble_open`, `category` must point
+/// to a valid `TopLevelCategory` whose strings are nul terminated, and
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn write_category(
+    handle: *mut AmbleHandle,
+    category: *const TopLevelCategory,
+    out: *mut TopLevelCategory,
+) -> AmbleStatus {
+    guard(|| {
+        let db = &mut unsafe { non_null_mut(handle, "Handle") }?.db;
+        let out = unsafe { non_null_mut(out, "Out pointer") }?;
+
+        let rust_category = unsafe { non_null(category, "Category") }?
+            .to_rust()
+            .context("Could not convert category pointer to rust category")?;
+
+        let parser = Parser::new(&rust_category.content);
+        let blocks = parser.parse();
+
+        let category = CategoryBlock {
+            id: if rust_category.id > 0 { Some(rust_category.id) } else { None },
+            ..CategoryBlock::new(&rust_category.name, 1, blocks)
+        };
+
+        let cat_id = db
+            .write_top_level_category(&category)
+            .context("Could not save category to database")?
+            .category_id;
+
+        let out_string = render_category(db, cat_id).context("Could not render category")?;
+
+        let name = to_c_string(rust_category.name)?;
+        let content = to_c_string(out_string)?;
+
+        *out = TopLevelCategory {
+            id: cat_id,
+            name: name.into_raw(),
+            content: content.into_raw(),
+        };
+
+        Ok(())
+    })
+}
+
+#[repr(C)]
+pub struct TopLevelCategoryResult {
+    id: i64,
+    name: *const c_char,
+}
+
+/// The categories returned by `get_top_level_categories`. Must be released
+/// with `amble_free_category_results`.
+#[repr(C)]
+pub struct TopLevelCategoryResults {
+    categories: *const TopLevelCategoryResult,
+    length: usize,
+}
+
+/// Writes the id and name of every top level category to `out`, which must
+/// be released with `amble_free_category_results`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `amble_open` and `out` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn get_top_level_categories(
+    handle: *mut AmbleHandle,
+    out: *mut TopLevelCategoryResults,
+) -> AmbleStatus {
+    guard(|| {
+        let db = &mut unsafe { non_null_mut(handle, "Handle") }?.db;
+        let out = unsafe { non_null_mut(out, "Out pointer") }?;
+
+        let db_categories = db
+            .get_top_level_categories()
+            .context("Could not get categories")?;
+
+        let mut names: Vec<CString> = Vec::new();
+        let mut ids: Vec<i64> = Vec::new();
+
+        for cat in db_categories {
+            ids.push(cat.id.context("Id was not present")?);
+            names.push(to_c_string(cat.name)?);
+        }
+
+        let tl_categories: Box<[TopLevelCategoryResult]> = ids
+            .into_iter()
+            .zip(names)
+            .map(|(id, name)| TopLevelCategoryResult {
+                id,
+                name: name.into_raw(),
+            })
+            .collect();
+
+        let length = tl_categories.len();
+
+        *out = TopLevelCategoryResults {
+            categories: Box::into_raw(tl_categories) as *const TopLevelCategoryResult,
+            length,
+        };
+
+        Ok(())
+    })
+}
+
+/// Writes the content of the category with `id`, rendered as org, to `out`.
+/// The string must be released with `amble_free_string`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `amble_open` and `out` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn get_category_content(
+    handle: *mut AmbleHandle,
+    id: i64,
+    out: *mut *mut c_char,
+) -> AmbleStatus {
+    guard(|| {
+        let db = &unsafe { non_null(handle, "Handle") }?.db;
+        let out = unsafe { non_null_mut(out, "Out pointer") }?;
+
+        let content = render_category(db, id)
+            .context(format!("Could not render category with id {}", id))?;
+
+        *out = to_c_string(content)?.into_raw();
+
+        Ok(())
+    })
+}
+
+#[repr(C)]
+pub struct SearchResult {
+    /// Id of the top level category containing the match
+    category_id: i64,
+    category_name: *const c_char,
+    /// Headlines beneath the top level category leading to the match,
+    /// separated by " > "
+    path: *const c_char,
+    /// The matching text, with matched terms wrapped in `[` and `]`
+    snippet: *const c_char,
+    /// Lower is a better match
+    rank: f64,
+}
+
+/// The results of `amble_search`, best match first. Must be released with
+/// `amble_free_search_results`.
+#[repr(C)]
+pub struct SearchResults {
+    results: *const SearchResult,
+    length: usize,
+}
+
+/// Searches the headlines and text of every note for the words in `query`
+/// and writes the matches to `out`, which must be released with
+/// `amble_free_search_results`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `amble_open`, `query` must be a valid
+/// nul terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn amble_search(
+    handle: *mut AmbleHandle,
+    query: *const c_char,
+    out: *mut SearchResults,
+) -> AmbleStatus {
+    guard(|| {
+        let db = &unsafe { non_null(handle, "Handle") }?.db;
+        let out = unsafe { non_null_mut(out, "Out pointer") }?;
+
+        let query = unsafe { CStr::from_ptr(non_null(query, "Query")?) }
+            .to_str()
+            .context("Invalid UTF-8 for query")?;
+
+        let hits = db
+            .search(query)
+            .context(format!("Could not search for {:?}", query))?;
+
+        let (results, length) = to_c_results(
+            hits,
+            |hit| {
+                Ok((
+                    hit.category_id,
+                    to_c_string(hit.category_name)?,
+                    to_c_string(hit.path.join(" > "))?,
+                    to_c_string(hit.snippet)?,
+                    hit.rank,
+                ))
+            },
+            |(category_id, category_name, path, snippet, rank)| SearchResult {
+                category_id,
+                category_name: category_name.into_raw(),
+                path: path.into_raw(),
+                snippet: snippet.into_raw(),
+                rank,
+            },
+        )?;
+
+        *out = SearchResults {
+            results,
+            length,
+        };
+
+        Ok(())
+    })
+}
+
+#[repr(C)]
+pub struct AgendaResult {
+    /// Id of the top level category containing the headline
+    category_id: i64,
+    category_name: *const c_char,
+    /// Headlines beneath the top level category leading to the headline,
+    /// separated by " > "
+    path: *const c_char,
+    headline: *const c_char,
+    /// What the date means for the headline, such as `DEADLINE`
+    label: *const c_char,
+    /// The date as an org timestamp
+    timestamp: *const c_char,
+    /// The day the headline is listed on, as `YYYY-MM-DD`
+    date: *const c_char,
+    /// The headline is still open, though its date has passed
+    overdue: bool,
+}
+
+/// The results of `amble_agenda`, ordered by date. Must be released with
+/// `amble_free_agenda_results`.
+#[repr(C)]
+pub struct AgendaResults {
+    results: *const AgendaResult,
+    length: usize,
+}
+
+/// Reads a `YYYY-MM-DD` date passed from C
+unsafe fn to_date(date: *const c_char, name: &str) -> Result<Date, anyhow::Error> {
+    let date = unsafe { CStr::from_ptr(non_null(date, name)?) }
+        .to_str()
+        .context(format!("Invalid UTF-8 for {}", name))?;
+
+    Date::parse(date).ok_or_else(|| anyhow!("{} {:?} is not a YYYY-MM-DD date", name, date))
+}
+
+/// Writes every headline scheduled, due or with a deadline between `start`
+/// and `end` inclusive, both `YYYY-MM-DD` dates, to `out`. When the range
+/// includes today, open items that are overdue are included too. `out` must
+/// be released with `amble_free_agenda_results`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `amble_open`, `start` and `end` must
+/// be valid nul terminated strings and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn amble_agenda(
+    handle: *mut AmbleHandle,
+    start: *const c_char,
+    end: *const c_char,
+    out: *mut AgendaResults,
+) -> AmbleStatus {
+    guard(|| {
+        let db = &unsafe { non_null(handle, "Handle") }?.db;
+        let out = unsafe { non_null_mut(out, "Out pointer") }?;
+
+        let start = unsafe { to_date(start, "Start") }?;
+        let end = unsafe { to_date(end, "End") }?;
+
+        let items = db
+            .agenda_items(start..=end)
+            .context(format!("Could not build agenda from {} to {}", start, end))?;
+
+        let (results, length) = to_c_results(
+            items,
+            |item| {
+                Ok((
+                    item.category_id,
+                    [
+                        to_c_string(item.category_name)?,
+                        to_c_string(item.path.join(" > "))?,
+                        to_c_string(item.headline)?,
+                        to_c_string(item.label)?,
+                        to_c_string(item.timestamp.to_string())?,
+                        to_c_string(item.timestamp.start.date.to_string())?,
+                    ],
+                    item.overdue,
+                ))
+            },
+            |(category_id, [category_name, path, headline, label, timestamp, date], overdue)| {
+                AgendaResult {
+                    category_id,
+                    category_name: category_name.into_raw(),
+                    path: path.into_raw(),
+                    headline: headline.into_raw(),
+                    label: label.into_raw(),
+                    timestamp: timestamp.into_raw(),
+                    date: date.into_raw(),
+                    overdue,
+                }
+            },
+        )?;
+
+        *out = AgendaResults {
+            results,
+            length,
+        };
+
+        Ok(())
+    })
+}
+
+#[repr(C)]
+pub struct BacklinkResult {
+    /// Id of the top level category containing the link
+    category_id: i64,
+    category_name: *const c_char,
+    /// Headlines beneath the top level category leading to the link,
+    /// separated by " > "
+    path: *const c_char,
+    /// The text containing the link
+    content: *const c_char,
+    /// Id of the category the link points at, which is either the category
+    /// backlinks were asked for or a headline beneath it
+    target_category_id: i64,
+}
+
+/// The results of `amble_backlinks`. Must be released with
+/// `amble_free_backlink_results`.
+#[repr(C)]
+pub struct BacklinkResults {
+    results: *const BacklinkResult,
+    length: usize,
+}
+
+/// Writes every link pointing at the category with `id`, or at a headline
+/// beneath it, to `out`, which must be released with
+/// `amble_free_backlink_results`.
+///
+/// # Safety
+///
+/// `handle` must have been returned by `amble_open` and `out` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn amble_backlinks(
+    handle: *mut AmbleHandle,
+    id: i64,
+    out: *mut BacklinkResults,
+) -> AmbleStatus {
+    guard(|| {
+        let db = &unsafe { non_null(handle, "Handle") }?.db;
+        let out = unsafe { non_null_mut(out, "Out pointer") }?;
+
+        let backlinks = db
+            .backlinks(id)
+            .context(format!("Could not find backlinks of category with id {}", id))?;
+
+        let (results, length) = to_c_results(
+            backlinks,
+            |backlink| {
+                Ok((
+                    backlink.category_id,
+                    to_c_string(backlink.category_name)?,
+                    to_c_string(backlink.path.join(" > "))?,
+                    to_c_string(backlink.content)?,
+                    backlink.target_category_id,
+                ))
+            },
+            |(category_id, category_name, path, content, target_category_id)| BacklinkResult {
+                category_id,
+                category_name: category_name.into_raw(),
+                path: path.into_raw(),
+                content: content.into_raw(),
+                target_category_id,
+            },
+        )?;
+
+        *out = BacklinkResults {
+            results,
+            length,
+        };
+
+        Ok(())
+    })
+}
+
+/// Releases a string returned by Amble. Does nothing if `string` is null.
+///
+/// # Safety
+///
+/// `string` must be null or have been returned by Amble, and must not be
+/// used after this call. `amble_last_error` is owned by Amble and must not be
+/// passed here.
+#[no_mangle]
+pub unsafe extern "C" fn amble_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Releases the strings of a category written by `write_category` and sets
+/// them to null, so freeing the same category twice is harmless. The
+/// category itself belongs to the caller.
+///
+/// # Safety
+///
+/// `category` must be null or point to a category written by
+/// `write_category`.
+#[no_mangle]
+pub unsafe extern "C" fn amble_free_category(category: *mut TopLevelCategory) {
+    let Some(category) = (unsafe { category.as_mut() }) else {
+        return;
+    };
+
+    unsafe {
+        amble_free_string(category.name as *mut c_char);
+        amble_free_string(category.content as *mut c_char);
+    }
+
+    category.name = ptr::null();
+    category.content = ptr::null();
+}
+
+/// Releases the categories written by `get_top_level_categories`, along with
+/// their names, and empties `results`.
+///
+/// # Safety
+///
+/// `results` must be null or point to results written by
+/// `get_top_level_categories`.
+#[no_mangle]
+pub unsafe extern "C" fn amble_free_category_results(results: *mut TopLevelCategoryResults) {
+    let Some(results) = (unsafe { results.as_mut() }) else {
+        return;
+    };
+
+    if !results.categories.is_null() {
+        let categories = unsafe {
+            Box::from_raw(ptr::slice_from_raw_parts_mut(
+                results.categories as *mut TopLevelCategoryResult,
+                results.length,
+            ))
+        };
+
+        for category in categories.iter() {
+            unsafe { amble_free_string(category.name as *mut c_char) };
+        }
+    }
+
+    results.categories = ptr::null();
+    results.length = 0;
+}
+
+/// Releases the results written by `amble_search`, along with their
+/// strings, and empties `results`.
+///
+/// # Safety
+///
+/// `results` must be null or point to results written by `amble_search`.
+#[no_mangle]
+pub unsafe extern "C" fn amble_free_search_results(results: *mut SearchResults) {
+    let Some(results) = (unsafe { results.as_mut() }) else {
+        return;
+    };
+
+    if !results.results.is_null() {
+        let hits = unsafe {
+            Box::from_raw(ptr::slice_from_raw_parts_mut(
+                results.results as *mut SearchResult,
+                results.length,
+            ))
+        };
+
+        for hit in hits.iter() {
+            unsafe {
+                amble_free_string(hit.category_name as *mut c_char);
+                amble_free_string(hit.path as *mut c_char);
+                amble_free_string(hit.snippet as *mut c_char);
+            }
+        }
+    }
+
+    results.results = ptr::null();
+    results.length = 0;
+}
+
+/// Releases the results written by `amble_agenda`, along with their
+/// strings, and empties `results`.
+///
+/// # Safety
+///
+/// `results` must be null or point to results written by `amble_agenda`.
+#[no_mangle]
+pub unsafe extern "C" fn amble_free_agenda_results(results: *mut AgendaResults) {
+    let Some(results) = (unsafe { results.as_mut() }) else {
+        return;
+    };
+
+    if !results.results.is_null() {
+        let items = unsafe {
+            Box::from_raw(ptr::slice_from_raw_parts_mut(
+                results.results as *mut AgendaResult,
+                results.length,
+            ))
+        };
+
+        for item in items.iter() {
+            unsafe {
+                amble_free_string(item.category_name as *mut c_char);
+                amble_free_string(item.path as *mut c_char);
+                amble_free_string(item.headline as *mut c_char);
+                amble_free_string(item.label as *mut c_char);
+                amble_free_string(item.timestamp as *mut c_char);
+                amble_free_string(item.date as *mut c_char);
+            }
+        }
+    }
+
+    results.results = ptr::null();
+    results.length = 0;
+}
+
+/// Releases the results written by `amble_backlinks`, along with their
+/// strings, and empties `results`.
+///
+/// # Safety
+///
+/// `results` must be null or point to results written by `amble_backlinks`.
+#[no_mangle]
+pub unsafe extern "C" fn amble_free_backlink_results(results: *mut BacklinkResults) {
+    let Some(results) = (unsafe { results.as_mut() }) else {
+        return;
+    };
+
+    if !results.results.is_null() {
+        let backlinks = unsafe {
+            Box::from_raw(ptr::slice_from_raw_parts_mut(
+                results.results as *mut BacklinkResult,
+                results.length,
+            ))
+        };
+
+        for backlink in backlinks.iter() {
+            unsafe {
+                amble_free_string(backlink.category_name as *mut c_char);
+                amble_free_string(backlink.path as *mut c_char);
+                amble_free_string(backlink.content as *mut c_char);
+            }
+        }
+    }
+
+    results.results = ptr::null();
+    results.length = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    fn open(path: &str) -> *mut AmbleHandle {
+        let path = CString::new(path).unwrap();
+        let mut handle: *mut AmbleHandle = ptr::null_mut();
+
+        let status = unsafe { amble_open(path.as_ptr(), &mut handle) };
+        assert_eq!(status, AmbleStatus::Ok);
+        assert!(!handle.is_null());
+
+        handle
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(amble_last_error()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn empty_category() -> TopLevelCategory {
+        TopLevelCategory {
+            id: 0,
+            name: ptr::null(),
+            content: ptr::null(),
+        }
+    }
+
+    #[test]
+    fn write_and_read_through_handle() {
+        let handle = open(":memory:");
+
+        let name = CString::new("Greetings").unwrap();
+        let content = CString::new("* Hello!\nThis is some content\n").unwrap();
+
+        let mut written = empty_category();
+        let status = unsafe {
+            write_category(
+                handle,
+                &TopLevelCategory {
+                    id: -1,
+                    name: name.as_ptr(),
+                    content: content.as_ptr(),
+                },
+                &mut written,
+            )
+        };
+        assert_eq!(status, AmbleStatus::Ok);
+
+        let mut stored: *mut c_char = ptr::null_mut();
+        let status = unsafe { get_category_content(handle, written.id, &mut stored) };
+        assert_eq!(status, AmbleStatus::Ok);
+
+        let stored_str = unsafe { CStr::from_ptr(stored) };
+        assert_eq!(stored_str.to_str().unwrap(), "* Hello!\nThis is some content\n");
+        unsafe { amble_free_string(stored) };
+
+        let mut results = TopLevelCategoryResults {
+            categories: ptr::null(),
+            length: 0,
+        };
+        let status = unsafe { get_top_level_categories(handle, &mut results) };
+        assert_eq!(status, AmbleStatus::Ok);
+        assert_eq!(results.length, 1);
+
+        unsafe {
+            amble_free_category(&mut written);
+            amble_free_category_results(&mut results);
+            amble_close(handle);
+        }
+    }
+
+    #[test]
+    fn open_absolute_path() {
+        let db_path = env::temp_dir().join(format!("amble-open-{}.sqlite", std::process::id()));
+        let _ = fs::remove_file(&db_path);
+
+        let handle = open(db_path.to_str().unwrap());
+        unsafe { amble_close(handle) };
+
+        assert!(db_path.exists());
+        fs::remove_file(&db_path).unwrap();
+    }
+
+    #[test]
+    fn report_errors_instead_of_panicking() {
+        let handle = open(":memory:");
+
+        let name = CString::new("Broken").unwrap();
+        let mut written = empty_category();
+
+        let status = unsafe {
+            write_category(
+                handle,
+                &TopLevelCategory {
+                    id: -1,
+                    name: name.as_ptr(),
+                    content: ptr::null(),
+                },
+                &mut written,
+            )
+        };
+
+        assert_eq!(status, AmbleStatus::Error);
+        assert_eq!(
+            last_error(),
+            "Could not convert category pointer to rust category: Content was null"
+        );
+        assert!(written.name.is_null());
+
+        let mut content: *mut c_char = ptr::null_mut();
+        let status = unsafe { get_category_content(ptr::null_mut(), 1, &mut content) };
+        assert_eq!(status, AmbleStatus::Error);
+        assert_eq!(last_error(), "Handle was null");
+        assert!(content.is_null());
+
+        unsafe { amble_close(handle) };
+    }
+
+    #[test]
+    fn catch_panics() {
+        let status = guard(|| panic!("Something went very wrong"));
+
+        assert_eq!(status, AmbleStatus::Panic);
+        assert_eq!(last_error(), "Amble panicked: Something went very wrong");
+    }
+
+    #[test]
+    fn free_returned_memory() {
+        let handle = open(":memory:");
+
+        for name in ["First", "Second", "Third"] {
+            let name = CString::new(name).unwrap();
+            let content = CString::new("* A headline\n").unwrap();
+            let mut written = empty_category();
+
+            let status = unsafe {
+                write_category(
+                    handle,
+                    &TopLevelCategory {
+                        id: -1,
+                        name: name.as_ptr(),
+                        content: content.as_ptr(),
+                    },
+                    &mut written,
+                )
+            };
+            assert_eq!(status, AmbleStatus::Ok);
+
+            unsafe { amble_free_category(&mut written) };
+            assert!(written.name.is_null());
+            assert!(written.content.is_null());
+
+            // Freeing twice is harmless since the strings were nulled
+            unsafe { amble_free_category(&mut written) };
+        }
+
+        let mut results = TopLevelCategoryResults {
+            categories: ptr::null(),
+            length: 0,
+        };
+        let status = unsafe { get_top_level_categories(handle, &mut results) };
+        assert_eq!(status, AmbleStatus::Ok);
+        assert_eq!(results.length, 3);
+
+        unsafe { amble_free_category_results(&mut results) };
+        assert!(results.categories.is_null());
+        assert_eq!(results.length, 0);
+
+        unsafe {
+            amble_free_string(ptr::null_mut());
+            amble_free_category(ptr::null_mut());
+            amble_free_category_results(ptr::null_mut());
+            amble_close(handle);
+        }
+    }
+
+    #[test]
+    fn search_through_handle() {
+        let handle = open(":memory:");
+
+        let name = CString::new("Papers").unwrap();
+        let content = CString::new("* MapReduce\nProcessing on large clusters\n").unwrap();
+        let mut written = empty_category();
+
+        let status = unsafe {
+            write_category(
+                handle,
+                &TopLevelCategory {
+                    id: -1,
+                    name: name.as_ptr(),
+                    content: content.as_ptr(),
+                },
+                &mut written,
+            )
+        };
+        assert_eq!(status, AmbleStatus::Ok);
+
+        let query = CString::new("clusters").unwrap();
+        let mut results = SearchResults {
+            results: ptr::null(),
+            length: 0,
+        };
+
+        let status = unsafe { amble_search(handle, query.as_ptr(), &mut results) };
+        assert_eq!(status, AmbleStatus::Ok);
+        assert_eq!(results.length, 1);
+
+        let hit = unsafe { &*results.results };
+        assert_eq!(hit.category_id, written.id);
+        assert_eq!(unsafe { CStr::from_ptr(hit.category_name) }.to_str(), Ok("Papers"));
+        assert_eq!(unsafe { CStr::from_ptr(hit.path) }.to_str(), Ok("MapReduce"));
+        assert_eq!(
+            unsafe { CStr::from_ptr(hit.snippet) }.to_str(),
+            Ok("Processing on large [clusters]")
+        );
+
+        unsafe {
+            amble_free_search_results(&mut results);
+            amble_free_category(&mut written);
+            amble_close(handle);
+        }
+        assert!(results.results.is_null());
+    }
+
+    #[test]
+    fn backlinks_through_handle() {
+        let handle = open(":memory:");
+
+        let mut written: Vec<TopLevelCategory> = Vec::new();
+
+        for (name, content) in [
+            ("Journal", "* Monday\nWorked on [[file:Work.org::*Website][the website]]\n"),
+            ("Work", "* Website\n"),
+        ] {
+            let name = CString::new(name).unwrap();
+            let content = CString::new(content).unwrap();
+            let mut category = empty_category();
+
+            let status = unsafe {
+                write_category(
+                    handle,
+                    &TopLevelCategory {
+                        id: -1,
+                        name: name.as_ptr(),
+                        content: content.as_ptr(),
+                    },
+                    &mut category,
+                )
+            };
+            assert_eq!(status, AmbleStatus::Ok);
+
+            written.push(category);
+        }
+
+        let mut results = BacklinkResults {
+            results: ptr::null(),
+            length: 0,
+        };
+
+        let status = unsafe { amble_backlinks(handle, written[1].id, &mut results) };
+        assert_eq!(status, AmbleStatus::Ok);
+        assert_eq!(results.length, 1);
+
+        let backlink = unsafe { &*results.results };
+        assert_eq!(backlink.category_id, written[0].id);
+        assert_eq!(unsafe { CStr::from_ptr(backlink.category_name) }.to_str(), Ok("Journal"));
+        assert_eq!(unsafe { CStr::from_ptr(backlink.path) }.to_str(), Ok("Monday"));
+        assert_eq!(
+            unsafe { CStr::from_ptr(backlink.content) }.to_str(),
+            Ok("Worked on [[file:Work.org::*Website][the website]]")
+        );
+        assert_ne!(backlink.target_category_id, written[1].id);
+
+        unsafe { amble_free_backlink_results(&mut results) };
+        assert!(results.results.is_null());
+
+        unsafe {
+            for category in &mut written {
+                amble_free_category(category);
+            }
+            amble_close(handle);
+        }
+    }
+
+    #[test]
+    fn agenda_through_handle() {
+        let handle = open(":memory:");
+
+        let today = Date::today();
+        let name = CString::new("Work").unwrap();
+        let content = CString::new(format!(
+            "* Website\n** TODO Ship the homepage\nDEADLINE: <{} {}>\n",
+            today,
+            today.weekday()
+        ))
+        .unwrap();
+        let mut written = empty_category();
+
+        let status = unsafe {
+            write_category(
+                handle,
+                &TopLevelCategory {
+                    id: -1,
+                    name: name.as_ptr(),
+                    content: content.as_ptr(),
+                },
+                &mut written,
+            )
+        };
+        assert_eq!(status, AmbleStatus::Ok);
+
+        let start = CString::new(today.to_string()).unwrap();
+        let end = CString::new(today.add_days(7).to_string()).unwrap();
+        let mut results = AgendaResults {
+            results: ptr::null(),
+            length: 0,
+        };
+
+        let status = unsafe { amble_agenda(handle, start.as_ptr(), end.as_ptr(), &mut results) };
+        assert_eq!(status, AmbleStatus::Ok);
+        assert_eq!(results.length, 1);
+
+        let item = unsafe { &*results.results };
+        assert_eq!(item.category_id, written.id);
+        assert_eq!(unsafe { CStr::from_ptr(item.path) }.to_str(), Ok("Website"));
+        assert_eq!(
+            unsafe { CStr::from_ptr(item.headline) }.to_str(),
+            Ok("TODO Ship the homepage")
+        );
+        assert_eq!(unsafe { CStr::from_ptr(item.label) }.to_str(), Ok("DEADLINE"));
+        assert_eq!(
+            unsafe { CStr::from_ptr(item.date) }.to_str(),
+            Ok(today.to_string().as_str())
+        );
+        assert!(!item.overdue);
+
+        unsafe { amble_free_agenda_results(&mut results) };
+        assert!(results.results.is_null());
+
+        let invalid = CString::new("next week").unwrap();
+        let status = unsafe { amble_agenda(handle, invalid.as_ptr(), end.as_ptr(), &mut results) };
+        assert_eq!(status, AmbleStatus::Error);
+        assert_eq!(last_error(), "Start \"next week\" is not a YYYY-MM-DD date");
+
+        unsafe {
+            amble_free_category(&mut written);
+            amble_close(handle);
+        }
+    }
+}
@@ -1,18 +1,6 @@
-mod air;
-mod db;
-mod parsing;
-mod rendering;
-mod matching;
-
 use std::fs;
 
-pub use air::CategoryBlock;
-pub use db::AmbleDB;
-pub use parsing::Parser;
-
-use air::Block;
-use db::DbBlockMatrix;
-use rendering::render_to_org;
+use amble::{render_to_org, AmbleDB, Block, CategoryBlock, DbBlockMatrix, Parser};
 
 fn main() {
     let document =
@@ -25,9 +13,7 @@ fn main() {
 
     let category = CategoryBlock {
         id: Some(1),
-        name: "MapReduce",
-        children: blocks,
-        level: 1,
+        ..CategoryBlock::new("MapReduce", 1, blocks)
     };
 
     db.write_top_level_category(&category)
@@ -36,7 +22,7 @@ fn main() {
     let matrix = DbBlockMatrix::new(&db.connection, 1).expect("Could not create db block matrix");
 
     let flat_blocks = matrix
-        .produce_flat_db_block_vec(&db.connection)
+        .produce_flat_db_block_vec()
         .expect("Could not produce flat vec of db blocks");
 
     let category_block = matrix
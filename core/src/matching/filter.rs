@@ -0,0 +1,144 @@
+use std::cmp::Ordering;
+
+use crate::air::{Date, DateTime, Timestamp};
+
+use super::provenance::Bindings;
+
+/// Reads a captured value as a date, either from a timestamp or a bare
+/// `YYYY-MM-DD` date
+fn coerce_date_time(value: &str) -> Option<DateTime> {
+    match Timestamp::parse(value) {
+        Some(timestamp) => Some(timestamp.start),
+        None => Some(DateTime {
+            date: Date::parse(value.trim())?,
+            time: None,
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+impl Comparison {
+    fn parse(text: &str) -> Option<Comparison> {
+        match text {
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            "=" => Some(Comparison::Equal),
+            _ => None,
+        }
+    }
+
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Less => ordering.is_lt(),
+            Comparison::LessOrEqual => ordering.is_le(),
+            Comparison::Greater => ordering.is_gt(),
+            Comparison::GreaterOrEqual => ordering.is_ge(),
+            Comparison::Equal => ordering.is_eq(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    /// The date the fold is evaluated on
+    Today,
+    Date(Date),
+}
+
+/// Keeps matches whose capture holds a date satisfying a comparison,
+/// written as a `:WHERE: due <= today` property of the fold. Dates are
+/// compared by the day they start on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateFilter {
+    capture: String,
+    comparison: Comparison,
+    operand: Operand,
+}
+
+impl DateFilter {
+    pub fn parse(text: &str) -> Option<DateFilter> {
+        let mut parts = text.trim().splitn(3, char::is_whitespace);
+        let capture = parts.next()?;
+        let comparison = Comparison::parse(parts.next()?)?;
+        let operand = match parts.next()?.trim() {
+            "today" => Operand::Today,
+            operand => Operand::Date(coerce_date_time(operand)?.date),
+        };
+
+        Some(DateFilter {
+            capture: capture.to_string(),
+            comparison,
+            operand,
+        })
+    }
+
+    /// Determines if the bindings of a match pass the filter. Matches
+    /// without a date in the capture never do.
+    pub fn keeps(&self, bindings: &Bindings, today: Date) -> bool {
+        let Some(value) = bindings
+            .get(&self.capture)
+            .and_then(|capture| coerce_date_time(&capture.value))
+        else {
+            return false;
+        };
+
+        let operand = match self.operand {
+            Operand::Today => today,
+            Operand::Date(date) => date,
+        };
+
+        self.comparison.holds(value.date.cmp(&operand))
+    }
+}
+
+/// Orders matches by the date in a capture, written as a `:SORT: due` or
+/// `:SORT: due desc` property of the fold. Matches without a date in the
+/// capture come last, in the order they were found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    capture: String,
+    descending: bool,
+}
+
+impl SortKey {
+    pub fn parse(text: &str) -> Option<SortKey> {
+        let mut parts = text.split_whitespace();
+        let capture = parts.next()?;
+        let descending = match parts.next() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(_) => return None,
+        };
+
+        Some(SortKey {
+            capture: capture.to_string(),
+            descending,
+        })
+    }
+
+    pub fn sort(&self, matches: &mut [Bindings]) {
+        let key = |bindings: &Bindings| {
+            bindings
+                .get(&self.capture)
+                .and_then(|capture| coerce_date_time(&capture.value))
+        };
+
+        matches.sort_by(|left, right| match (key(left), key(right)) {
+            (Some(left), Some(right)) if self.descending => right.cmp(&left),
+            (Some(left), Some(right)) => left.cmp(&right),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+    }
+}
@@ -1,35 +1,706 @@
-use crate::{air::Block, parsing::Tokenizer, CategoryBlock};
-use crate::Parser;
+use std::borrow::Cow;
 
-use super::value_matcher::ValueMatcher;
+use bumpalo::Bump;
+
+use crate::{
+    air::{
+        split_tags, Block, Checkbox, Date, DrawerBlock, ListBlock, ListItemBlock, ListKind,
+        PropertyBlock, RichTextBlock, TableBlock, TableCell, TableRow, TextBlock, TextLine,
+        TODO_KEYWORDS,
+    },
+    parsing::Tokenizer,
+    CategoryBlock,
+};
+
+use super::{
+    filter::{DateFilter, SortKey},
+    provenance::{Bindings, Capture, CaptureSource, FoldResult, Origin},
+    value_matcher::ValueMatcher,
+};
+
+/// Merges two sets of bindings, returning None if they bind the same
+/// capture to different values
+fn unify(left: &Bindings, right: &Bindings) -> Option<Bindings> {
+    let mut merged = left.clone();
+
+    for (capture, bound) in right {
+        match merged.get(capture) {
+            Some(existing) if existing.value != bound.value => return None,
+            _ => {
+                merged.insert(capture.clone(), bound.clone());
+            }
+        }
+    }
+
+    Some(merged)
+}
+
+/// Pairs every result with every candidate, keeping the combinations whose
+/// bindings agree
+fn combine(results: Vec<Bindings>, candidates: &[Bindings]) -> Vec<Bindings> {
+    results
+        .iter()
+        .flat_map(|result| {
+            candidates
+                .iter()
+                .filter_map(move |candidate| unify(result, candidate))
+        })
+        .collect()
+}
+
+/// Runs `matcher` over `input`, recording `source` as the origin of each capture
+fn capture_with_source(
+    matcher: &ValueMatcher,
+    input: &str,
+    source: Option<CaptureSource>,
+) -> Option<Bindings> {
+    let spans = matcher.capture_spans(input)?;
+
+    Some(
+        spans
+            .into_iter()
+            .map(|(name, span)| {
+                let capture = Capture {
+                    value: input[span.0..span.1].to_string(),
+                    source,
+                    span,
+                };
+                (name, capture)
+            })
+            .collect(),
+    )
+}
+
+/// Returns the non blank lines of text directly inside a category
+fn text_lines<'a>(category: &CategoryBlock<'a>) -> Vec<TextLine<'a>> {
+    let mut lines: Vec<TextLine> = Vec::new();
+
+    for child in &category.children {
+        match child {
+            Block::RichText(rich_text) => lines.extend(rich_text.lines()),
+            Block::Text(text) => lines.push(TextLine {
+                id: text.id,
+                content: Cow::Borrowed(text.content),
+            }),
+            Block::Category(_)
+            | Block::Property(_)
+            | Block::Drawer(_)
+            | Block::List(_)
+            | Block::ListItem(_)
+            | Block::Table(_)
+            | Block::Verbatim(_)
+            | Block::Keyword(_) => (),
+        }
+    }
+
+    lines.retain(|text| !text.content.trim().is_empty());
+    lines
+}
+
+/// Returns the byte offset of `inner` within `outer`, if it is a slice of it
+fn offset_within(outer: &str, inner: &str) -> Option<usize> {
+    let offset = (inner.as_ptr() as usize).checked_sub(outer.as_ptr() as usize)?;
+    (offset + inner.len() <= outer.len()).then_some(offset)
+}
+
+/// Splits the headline of a matcher into the TODO keywords it accepts,
+/// written as `TODO` or `TODO|DONE`, the text matched against the title,
+/// and the tags it requires
+fn split_matcher_headline(name: &str) -> (Vec<String>, &str, Vec<String>) {
+    let rest = name.trim();
+    let (first_word, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+    let (keywords, rest) = if first_word
+        .split('|')
+        .all(|keyword| TODO_KEYWORDS.contains(&keyword))
+    {
+        (first_word.split('|').map(String::from).collect(), after.trim_start())
+    } else {
+        (Vec::new(), rest)
+    };
+
+    let (title, tags) = split_tags(rest);
+
+    (keywords, title, tags.into_iter().map(String::from).collect())
+}
+
+fn parse_value_matcher(source: &str) -> ValueMatcher {
+    let mut tokenizer = Tokenizer::new(source);
+    let tokens = tokenizer.get_tokens();
+    ValueMatcher::parse(source, &tokens)
+}
+
+/// Matches a `:Key: value` property of a category. Keys are compared
+/// case insensitively, as in org mode.
+#[derive(Debug)]
+struct PropertyMatcher {
+    key: String,
+    value: ValueMatcher,
+    /// Whether the template property was written in a property drawer
+    in_drawer: bool,
+}
+
+impl PropertyMatcher {
+    fn parse(property: &PropertyBlock, in_drawer: bool) -> PropertyMatcher {
+        PropertyMatcher {
+            key: property.key.to_string(),
+            value: parse_value_matcher(property.value),
+            in_drawer,
+        }
+    }
+
+    fn match_category(&self, category: &CategoryBlock) -> Vec<Bindings> {
+        category
+            .properties()
+            .filter(|property| property.key.eq_ignore_ascii_case(&self.key))
+            .filter_map(|property| {
+                let source = property.id.map(CaptureSource::Property);
+                capture_with_source(&self.value, property.value, source)
+            })
+            .collect()
+    }
+}
+
+/// Returns every item of the lists directly inside a category, including
+/// those of nested lists, in document order
+fn list_items<'b, 'a>(category: &'b CategoryBlock<'a>) -> Vec<&'b ListItemBlock<'a>> {
+    fn visit<'b, 'a>(blocks: &'b [Block<'a>], items: &mut Vec<&'b ListItemBlock<'a>>) {
+        for block in blocks {
+            match block {
+                Block::List(list) => visit(&list.children, items),
+                Block::ListItem(item) => {
+                    items.push(item);
+                    visit(&item.children, items);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut items: Vec<&ListItemBlock> = Vec::new();
+    visit(&category.children, &mut items);
+    items
+}
+
+/// Matches a list item of a category, at any depth. When the matcher has a
+/// checkbox, only items with a checkbox in the same state match.
+#[derive(Debug)]
+struct ListItemMatcher {
+    checkbox: Option<Checkbox>,
+    content: ValueMatcher,
+}
+
+impl ListItemMatcher {
+    fn parse(item: &ListItemBlock) -> ListItemMatcher {
+        ListItemMatcher {
+            checkbox: item.checkbox_state(),
+            content: parse_value_matcher(item.content),
+        }
+    }
+
+    fn match_category(&self, category: &CategoryBlock) -> Vec<Bindings> {
+        list_items(category)
+            .into_iter()
+            .filter(|item| self.checkbox.is_none() || item.checkbox_state() == self.checkbox)
+            .filter_map(|item| {
+                let source = item.id.map(CaptureSource::ListItem);
+                capture_with_source(&self.content, item.content, source)
+            })
+            .collect()
+    }
+}
+
+/// Matches rows of the tables directly inside a category, with a value
+/// matcher for each cell of a row in the matcher's body. When the matcher
+/// has a header, its cells are matched against the columns of the same
+/// name, compared case insensitively. Otherwise they are matched against
+/// the columns at the same index. Blank cells match anything.
+#[derive(Debug)]
+struct TableMatcher {
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<Option<ValueMatcher>>>,
+}
+
+impl TableMatcher {
+    fn parse(table: &TableBlock) -> TableMatcher {
+        let header = table
+            .header()
+            .map(|row| row.cells.iter().map(|cell| cell.content.to_string()).collect());
+
+        let rows = table
+            .body()
+            .map(|row| {
+                row.cells
+                    .iter()
+                    .map(|cell| {
+                        (!cell.content.is_empty()).then(|| parse_value_matcher(cell.content))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        TableMatcher { header, rows }
+    }
+
+    /// Finds the column of `table` each column of the matcher refers to
+    fn columns(&self, table: &TableBlock) -> Option<Vec<usize>> {
+        let Some(names) = &self.header else {
+            return Some((0..table.columns()).collect());
+        };
+
+        let header = table.header()?;
+
+        names
+            .iter()
+            .map(|name| {
+                header
+                    .cells
+                    .iter()
+                    .position(|cell| cell.content.eq_ignore_ascii_case(name))
+            })
+            .collect()
+    }
+
+    fn match_row(
+        matchers: &[Option<ValueMatcher>],
+        columns: &[usize],
+        row: &TableRow,
+    ) -> Option<Bindings> {
+        let mut bindings = Bindings::new();
+
+        for (matcher, column) in matchers.iter().zip(columns) {
+            let Some(matcher) = matcher else {
+                continue;
+            };
+
+            let cell = row.cells.get(*column)?;
+            let source = cell.id.map(CaptureSource::TableCell);
+            bindings = unify(&bindings, &capture_with_source(matcher, cell.content, source)?)?;
+        }
+
+        Some(bindings)
+    }
+
+    /// Returns every combination of rows, one for each row of the matcher,
+    /// whose bindings agree
+    fn match_category(&self, category: &CategoryBlock) -> Vec<Bindings> {
+        let tables: Vec<(&TableBlock, Vec<usize>)> = category
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Block::Table(table) => Some((table, self.columns(table)?)),
+                _ => None,
+            })
+            .collect();
+
+        let mut results = vec![Bindings::new()];
+
+        for matchers in &self.rows {
+            let candidates: Vec<Bindings> = tables
+                .iter()
+                .flat_map(|(table, columns)| {
+                    table
+                        .body()
+                        .filter_map(|row| Self::match_row(matchers, columns, row))
+                })
+                .collect();
+
+            results = combine(results, &candidates);
+        }
+
+        results
+    }
+
+    /// Builds a table from this matcher used as a template, with its header
+    /// and a row for each row of the matcher. Cells that reference captures
+    /// missing from `bindings` are left blank.
+    fn instantiate<'a>(
+        &self,
+        bump: &'a Bump,
+        bindings: &Bindings,
+        origins: &mut Vec<Origin>,
+    ) -> TableBlock<'a> {
+        let mut rows: Vec<TableRow<'a>> = Vec::new();
+
+        if let Some(names) = &self.header {
+            let cells = names
+                .iter()
+                .map(|name| TableCell {
+                    id: None,
+                    content: bump.alloc_str(name),
+                })
+                .collect();
+
+            rows.push(TableRow {
+                id: None,
+                indent: "",
+                rule: false,
+                cells,
+            });
+            rows.push(TableRow {
+                id: None,
+                indent: "",
+                rule: true,
+                cells: Vec::new(),
+            });
+        }
+
+        for matchers in &self.rows {
+            let mut cells: Vec<TableCell<'a>> = Vec::new();
+
+            for matcher in matchers {
+                let value = matcher
+                    .as_ref()
+                    .and_then(|matcher| Some((matcher, matcher.instantiate(bindings)?)));
+
+                let content: &'a str = match value {
+                    Some((matcher, value)) if !value.trim().is_empty() => {
+                        origins.push(Origin {
+                            template: matcher.clone(),
+                            bindings: bindings.clone(),
+                        });
+                        bump.alloc_str(&value)
+                    }
+                    _ => "",
+                };
+
+                cells.push(TableCell { id: None, content });
+            }
+
+            rows.push(TableRow {
+                id: None,
+                indent: "",
+                rule: false,
+                cells,
+            });
+        }
+
+        TableBlock { id: None, rows }
+    }
+}
 
 #[derive(Debug)]
 struct CategoryMatcher {
+    /// Matched against the title of a category when the matcher constrains
+    /// its keyword or tags, and against its whole name otherwise
     title: ValueMatcher,
+    /// Builds the name of a category when the matcher is used as a template
+    name: ValueMatcher,
+    /// The TODO keywords a category may have, any of which will do. When
+    /// empty, the keyword is not constrained.
+    keywords: Vec<String>,
+    /// Tags a category must have, alongside any others
+    tags: Vec<String>,
+    properties: Vec<PropertyMatcher>,
+    lines: Vec<ValueMatcher>,
+    items: Vec<ListItemMatcher>,
+    tables: Vec<TableMatcher>,
     body: Vec<CategoryMatcher>,
 }
 
 impl CategoryMatcher {
     fn parse(category: &CategoryBlock) -> CategoryMatcher {
-        let source = category.name;
-        let mut tokenizer = Tokenizer::new(source);
-        let tokens = tokenizer.get_tokens();
-        let title_value_matcher = ValueMatcher::parse(source, &tokens);
+        let (keywords, title, tags) = split_matcher_headline(category.name);
 
-        let mut child_category_matchers: Vec<CategoryMatcher> = Vec::new();
+        let (title_value_matcher, name_value_matcher) = if keywords.is_empty() && tags.is_empty() {
+            (
+                parse_value_matcher(category.name),
+                parse_value_matcher(category.name),
+            )
+        } else {
+            // Templates produce the first keyword, so results can be read
+            // back in as headlines
+            let mut name = String::new();
+            if let Some(keyword) = keywords.first() {
+                name += &format!("\"{}\" ", keyword);
+            }
+            name += title;
+            if !tags.is_empty() {
+                name += &format!(" \":{}:\"", tags.join(":"));
+            }
+
+            (parse_value_matcher(title), parse_value_matcher(&name))
+        };
+
+        let mut property_matchers: Vec<PropertyMatcher> = Vec::new();
 
         for child in category.children.as_slice() {
             match child {
-                Block::Category(child_cat) => child_category_matchers.push(Self::parse(child_cat)),
+                Block::Property(property) => {
+                    property_matchers.push(PropertyMatcher::parse(property, false));
+                }
+                Block::Drawer(drawer) => property_matchers.extend(
+                    drawer
+                        .properties
+                        .iter()
+                        .map(|property| PropertyMatcher::parse(property, true)),
+                ),
                 _ => (),
             }
         }
 
+        let line_matchers: Vec<ValueMatcher> = text_lines(category)
+            .into_iter()
+            .map(|text| parse_value_matcher(&text.content))
+            .collect();
+
+        let item_matchers: Vec<ListItemMatcher> = list_items(category)
+            .into_iter()
+            .map(ListItemMatcher::parse)
+            .collect();
+
+        let table_matchers: Vec<TableMatcher> = category
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Block::Table(table) => Some(TableMatcher::parse(table)),
+                _ => None,
+            })
+            .collect();
+
+        let mut child_category_matchers: Vec<CategoryMatcher> = Vec::new();
+
+        for child in category.children.as_slice() {
+            if let Block::Category(child_cat) = child {
+                child_category_matchers.push(Self::parse(child_cat));
+            }
+        }
+
         CategoryMatcher {
             title: title_value_matcher,
+            name: name_value_matcher,
+            keywords,
+            tags,
+            properties: property_matchers,
+            lines: line_matchers,
+            items: item_matchers,
+            tables: table_matchers,
             body: child_category_matchers,
         }
     }
+
+    /// Matches this matcher against a category, returning every combination
+    /// of bindings that satisfies the title, properties, lines, list items,
+    /// tables and all body matchers. Each property, line and list item
+    /// matcher must match at least one property, line of text or list item
+    /// in the category, each row of a table matcher a row of one of its
+    /// tables, and each body matcher at least one direct child category.
+    fn match_category(&self, category: &CategoryBlock) -> Vec<Bindings> {
+        let title_bindings = match self.match_title(category) {
+            Some(bindings) => bindings,
+            None => return Vec::new(),
+        };
+
+        let mut results = vec![title_bindings];
+
+        for property_matcher in &self.properties {
+            results = combine(results, &property_matcher.match_category(category));
+
+            if results.is_empty() {
+                return results;
+            }
+        }
+
+        if !self.lines.is_empty() {
+            let lines = text_lines(category);
+
+            for line_matcher in &self.lines {
+                let line_results: Vec<Bindings> = lines
+                    .iter()
+                    .filter_map(|text| {
+                        let source = text.id.map(CaptureSource::Text);
+                        capture_with_source(line_matcher, &text.content, source)
+                    })
+                    .collect();
+
+                results = combine(results, &line_results);
+
+                if results.is_empty() {
+                    return results;
+                }
+            }
+        }
+
+        for item_matcher in &self.items {
+            results = combine(results, &item_matcher.match_category(category));
+
+            if results.is_empty() {
+                return results;
+            }
+        }
+
+        for table_matcher in &self.tables {
+            results = combine(results, &table_matcher.match_category(category));
+
+            if results.is_empty() {
+                return results;
+            }
+        }
+
+        for body_matcher in &self.body {
+            let mut child_results: Vec<Bindings> = Vec::new();
+
+            for child in &category.children {
+                if let Block::Category(child_cat) = child {
+                    child_results.extend(body_matcher.match_category(child_cat));
+                }
+            }
+
+            results = combine(results, &child_results);
+
+            if results.is_empty() {
+                break;
+            }
+        }
+
+        results
+    }
+
+    /// Matches the keyword, tags and title of a category, returning the
+    /// bindings captured from its title
+    fn match_title(&self, category: &CategoryBlock) -> Option<Bindings> {
+        let source = category.id.map(CaptureSource::Category);
+
+        if self.keywords.is_empty() && self.tags.is_empty() {
+            return capture_with_source(&self.title, category.name, source);
+        }
+
+        let headline = &category.headline;
+
+        if !self.keywords.is_empty()
+            && !headline
+                .todo_keyword
+                .is_some_and(|keyword| self.keywords.iter().any(|k| k == keyword))
+        {
+            return None;
+        }
+
+        if !self.tags.iter().all(|tag| headline.tags.contains(&tag.as_str())) {
+            return None;
+        }
+
+        // Captures record spans within the name, which is what edits are
+        // written back to
+        let offset = offset_within(category.name, headline.title);
+        let mut bindings =
+            capture_with_source(&self.title, headline.title, source.filter(|_| offset.is_some()))?;
+
+        for capture in bindings.values_mut() {
+            let offset = offset.unwrap_or_default();
+            capture.span = (capture.span.0 + offset, capture.span.1 + offset);
+        }
+
+        Some(bindings)
+    }
+
+    /// Builds a category from this matcher used as a template. Returns None
+    /// if the title references a capture missing from `bindings`. Every
+    /// value written into the category is recorded in `origins`.
+    fn instantiate<'a>(
+        &self,
+        bump: &'a Bump,
+        bindings: &Bindings,
+        level: usize,
+        origins: &mut Vec<Origin>,
+    ) -> Option<CategoryBlock<'a>> {
+        let title = self.name.instantiate(bindings)?;
+        origins.push(Origin {
+            template: self.name.clone(),
+            bindings: bindings.clone(),
+        });
+
+        let mut children: Vec<Block<'a>> = Vec::new();
+
+        // Properties written in a drawer are gathered into a single drawer
+        // ahead of the others, which is where org expects it
+        let mut drawer_properties: Vec<PropertyBlock<'a>> = Vec::new();
+        let (drawer_matchers, bare_matchers): (Vec<_>, Vec<_>) =
+            self.properties.iter().partition(|matcher| matcher.in_drawer);
+
+        for property_matcher in drawer_matchers.into_iter().chain(bare_matchers) {
+            if let Some(value) = property_matcher.value.instantiate(bindings) {
+                origins.push(Origin {
+                    template: property_matcher.value.clone(),
+                    bindings: bindings.clone(),
+                });
+
+                let property = PropertyBlock::new(
+                    bump.alloc_str(&property_matcher.key),
+                    bump.alloc_str(&value),
+                );
+
+                if property_matcher.in_drawer {
+                    drawer_properties.push(property);
+                } else {
+                    children.push(Block::Property(property));
+                }
+            }
+        }
+
+        if !drawer_properties.is_empty() {
+            children.insert(0, Block::Drawer(DrawerBlock::new(drawer_properties)));
+        }
+
+        for line_matcher in &self.lines {
+            if let Some(line) = line_matcher.instantiate(bindings) {
+                origins.push(Origin {
+                    template: line_matcher.clone(),
+                    bindings: bindings.clone(),
+                });
+
+                children.push(Block::RichText(RichTextBlock {
+                    children: vec![Block::Text(TextBlock {
+                        id: None,
+                        content: bump.alloc_str(&format!("{}\n", line)),
+                        markup: None,
+                    })],
+                }));
+            }
+        }
+
+        let mut items: Vec<Block<'a>> = Vec::new();
+
+        for item_matcher in &self.items {
+            if let Some(content) = item_matcher.content.instantiate(bindings) {
+                origins.push(Origin {
+                    template: item_matcher.content.clone(),
+                    bindings: bindings.clone(),
+                });
+
+                items.push(Block::ListItem(ListItemBlock {
+                    id: None,
+                    bullet: "- ",
+                    checkbox: item_matcher.checkbox.map(|checkbox| checkbox.as_str()),
+                    content: bump.alloc_str(&format!("{}\n", content)),
+                    children: Vec::new(),
+                }));
+            }
+        }
+
+        if !items.is_empty() {
+            children.push(Block::List(ListBlock {
+                id: None,
+                kind: ListKind::Unordered,
+                children: items,
+            }));
+        }
+
+        for table_matcher in &self.tables {
+            children.push(Block::Table(table_matcher.instantiate(bump, bindings, origins)));
+        }
+
+        for matcher in &self.body {
+            if let Some(child) = matcher.instantiate(bump, bindings, level + 1, origins) {
+                children.push(Block::Category(child));
+            }
+        }
+
+        Some(CategoryBlock::new(
+            bump.alloc_str(&format!("{}\n", title)),
+            level,
+            children,
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -43,9 +714,8 @@ impl FoldFrom {
 
         if category.name.contains("FROM") {
             for child in category.children.as_slice() {
-                match child {
-                    Block::Category(child_cat) => matchers.push(CategoryMatcher::parse(child_cat)),
-                    _ => (),
+                if let Block::Category(child_cat) = child {
+                    matchers.push(CategoryMatcher::parse(child_cat));
                 }
             }
             return Some(Self { matchers });
@@ -66,9 +736,8 @@ impl FoldInto {
 
         if category.name.contains("INTO") {
             for child in category.children.as_slice() {
-                match child {
-                    Block::Category(child_cat) => matchers.push(CategoryMatcher::parse(child_cat)),
-                    _ => (),
+                if let Block::Category(child_cat) = child {
+                    matchers.push(CategoryMatcher::parse(child_cat));
                 }
             }
             return Some(Self { matchers });
@@ -82,31 +751,94 @@ impl FoldInto {
 pub struct Fold {
     from: FoldFrom,
     into: FoldInto,
+    filters: Vec<DateFilter>,
+    sort: Option<SortKey>,
 }
 
 impl Fold {
+    /// Parses a FOLD category, whose first two child categories are its FROM
+    /// and INTO. `:WHERE:` and `:SORT:` properties of the fold filter and
+    /// order its matches by the dates they capture.
     pub fn parse(category: &CategoryBlock) -> Option<Self> {
-        if category.name.contains("FOLD") {
-            if category.children.len() < 2 {
-                dbg!(category.children.len());
-                return None;
+        if !category.name.contains("FOLD") {
+            return None;
+        }
+
+        let mut categories = category.children.iter().filter_map(|child| match child {
+            Block::Category(child_cat) => Some(child_cat),
+            _ => None,
+        });
+
+        let from = FoldFrom::parse(categories.next()?)?;
+        let into = FoldInto::parse(categories.next()?)?;
+
+        let mut filters: Vec<DateFilter> = Vec::new();
+        let mut sort: Option<SortKey> = None;
+
+        for property in category.properties() {
+            if property.key.eq_ignore_ascii_case("WHERE") {
+                filters.push(DateFilter::parse(property.value)?);
+            } else if property.key.eq_ignore_ascii_case("SORT") {
+                sort = Some(SortKey::parse(property.value)?);
             }
+        }
+
+        Some(Self {
+            from,
+            into,
+            filters,
+            sort,
+        })
+    }
+
+    /// Evaluates the fold against `blocks`, returning a new list of blocks
+    /// built from the INTO templates for every match of the FROM matchers.
+    /// Categories that are themselves folds are not searched. Any text
+    /// created during evaluation is allocated in `bump`.
+    pub fn evaluate<'a>(&self, bump: &'a Bump, blocks: &[Block]) -> FoldResult<'a> {
+        let mut matches: Vec<Bindings> = Vec::new();
+        self.collect_matches(blocks, &mut matches);
 
-            if let Block::Category(child_cat) = &category.children[0] {
-                if let Block::Category(child_cat_2) = &category.children[1] {
-                    let from_component = FoldFrom::parse(child_cat)?;
-                    let into_component = FoldInto::parse(child_cat_2)?;
+        if !self.filters.is_empty() {
+            let today = Date::today();
+            matches.retain(|bindings| {
+                self.filters
+                    .iter()
+                    .all(|filter| filter.keeps(bindings, today))
+            });
+        }
 
-                    return Some(Self {
-                        from: from_component,
-                        into: into_component,
-                    });
+        if let Some(sort) = &self.sort {
+            sort.sort(&mut matches);
+        }
+
+        let mut out: Vec<Block<'a>> = Vec::new();
+        let mut origins: Vec<Origin> = Vec::new();
+
+        for bindings in &matches {
+            for matcher in &self.into.matchers {
+                if let Some(category) = matcher.instantiate(bump, bindings, 1, &mut origins) {
+                    out.push(Block::Category(category));
                 }
-            } else {
-                return None;
             }
         }
 
-        None
+        FoldResult::new(out, origins)
+    }
+
+    fn collect_matches(&self, blocks: &[Block], matches: &mut Vec<Bindings>) {
+        for block in blocks {
+            if let Block::Category(category) = block {
+                if Fold::parse(category).is_some() {
+                    continue;
+                }
+
+                for matcher in &self.from.matchers {
+                    matches.extend(matcher.match_category(category));
+                }
+
+                self.collect_matches(&category.children, matches);
+            }
+        }
     }
 }
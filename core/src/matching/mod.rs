@@ -1,9 +1,16 @@
 mod value_matcher;
+mod filter;
 mod fold;
+mod provenance;
+
+pub use fold::Fold;
+pub use provenance::*;
 
 #[cfg(test)]
 mod tests {
-    use crate::{Block, Parser};
+    use bumpalo::Bump;
+
+    use crate::{render_to_org, AmbleDB, Block, CategoryBlock, DbBlockMatrix, Parser};
 
     use crate::{
         matching::value_matcher::{get_position_of_substring, ValueMatcher}, parsing::Tokenizer
@@ -18,12 +25,20 @@ mod tests {
 
         let test = "TODO I want to to take the dog outside BUT i need to do it before friday";
 
-        let mut tokenizer = Tokenizer::new(&name);
+        let mut tokenizer = Tokenizer::new(name);
         let tokens = tokenizer.get_tokens();
 
         let matcher = ValueMatcher::parse(name, &tokens);
 
-        dbg!(matcher.capture(test));
+        let spans = matcher
+            .capture_spans(test)
+            .expect("Value should match");
+
+        let (start, end) = spans["title"];
+        assert_eq!(&test[start..end], "I want to to take the dog outside");
+
+        let (start, end) = spans["condition"];
+        assert_eq!(&test[start..end], "i need to do it before friday");
     }
 
         #[test]
@@ -40,16 +55,532 @@ mod tests {
         let parser = Parser::new(test);
         let blocks = parser.parse();
 
-        for block in blocks {
-            match block {
-                Block::Category(category) => {
-                    if let Some(fold) = Fold::parse(&category) {
-                        dbg!(fold);
+        let folds: Vec<Fold> = blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::Category(category) => Fold::parse(category),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(folds.len(), 1);
+    }
+
+    fn evaluate_folds(document: &str) -> String {
+        let parser = Parser::new(document);
+        let blocks = parser.parse();
+        let bump = Bump::new();
+
+        let mut out = String::new();
+
+        for block in &blocks {
+            if let Block::Category(category) = block {
+                if let Some(fold) = Fold::parse(category) {
+                    for result in fold.evaluate(&bump, &blocks).blocks {
+                        out += &render_to_org(result);
                     }
                 }
-                _ => (),
             }
         }
+
+        out
+    }
+
+    #[test]
+    fn evaluate_fold() {
+        let test = r#"* TODO Design homepage layout
+* TODO Organize team-building event
+* Redesign Sprint Meeting
+** UI Feedback
+*** TODO Sit down with Katie
+* FOLD
+** FROM
+*** "TODO" title
+** INTO
+*** "DONE" title"#;
+
+        assert_eq!(
+            evaluate_folds(test),
+            "* DONE Design homepage layout\n\
+             * DONE Organize team-building event\n\
+             * DONE Sit down with Katie\n"
+        );
+    }
+
+    #[test]
+    fn evaluate_fold_with_nested_matchers() {
+        let test = r#"* Project Website
+** Owner Stacy
+** Task Design the homepage
+** Task Write the copy
+* Project Laundry
+** Task Fold the shirts
+* FOLD
+** FROM
+*** "Project" project
+**** "Owner" owner
+**** "Task" task
+** INTO
+*** owner
+**** project ":" task"#;
+
+        assert_eq!(
+            evaluate_folds(test),
+            "* Stacy\n\
+             ** Website : Design the homepage\n\
+             * Stacy\n\
+             ** Website : Write the copy\n"
+        );
+    }
+
+    #[test]
+    fn evaluate_fold_with_properties() {
+        let test = r#"* TODO Design homepage layout
+  :Project: Website Redesign
+  :Due: <2023-10-14>
+* TODO Organize team-building event
+  :Project: HR Activities
+  :Due: <2023-10-30>
+* TODO Need to do the laundry
+  :Due: <2023-09-20>
+* FOLD
+** FROM
+*** "TODO" todo-title
+    :Project: "Website Redesign"
+    :Due: due-date
+** INTO
+*** "TODO" todo-title
+    :Due: due-date"#;
+
+        assert_eq!(
+            evaluate_folds(test),
+            "* TODO Design homepage layout\n\
+             :Due: <2023-10-14>\n"
+        );
+    }
+
+    #[test]
+    fn propagate_fold_edits() {
+        let notes = "* TODO Design homepage layout
+* Sprint Meeting
+** TODO Sit down with Katie
+Owner: Katie
+";
+        let fold_document = r#"* FOLD
+** FROM
+*** "TODO" title
+"Owner:" owner
+** INTO
+*** "TODO" title
+"Owner:" owner
+"#;
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+
+        let category_id = db
+            .write_top_level_category(&CategoryBlock::new(
+                "Notes",
+                0,
+                Parser::new(notes).parse(),
+            ))
+            .expect("Should be able to write notes")
+            .category_id;
+
+        let load = |db: &AmbleDB| {
+            let matrix = DbBlockMatrix::new(&db.connection, category_id)
+                .expect("Should be able to create block matrix");
+            let flat_blocks = matrix
+                .produce_flat_db_block_vec()
+                .expect("Should be able to produce db blocks");
+            let category = matrix
+                .form_category_block_tree(&flat_blocks)
+                .expect("Should be able to form category tree");
+            render_to_org(Block::Category(category))
+        };
+
+        let fold_blocks = Parser::new(fold_document).parse();
+        let fold = match &fold_blocks[0] {
+            Block::Category(category) => Fold::parse(category).expect("Fold should parse"),
+            _ => panic!("Fold document should start with a category"),
+        };
+
+        let edits = {
+            let matrix = DbBlockMatrix::new(&db.connection, category_id)
+                .expect("Should be able to create block matrix");
+            let flat_blocks = matrix
+                .produce_flat_db_block_vec()
+                .expect("Should be able to produce db blocks");
+            let category = matrix
+                .form_category_block_tree(&flat_blocks)
+                .expect("Should be able to form category tree");
+
+            let bump = Bump::new();
+            let result = fold.evaluate(&bump, &[Block::Category(category)]);
+
+            let edited = "* TODO Sit down with Katie and Stacy\nOwner: Stacy\n";
+            result
+                .diff(&Parser::new(edited).parse())
+                .expect("Edited result should diff")
+        };
+
+        assert_eq!(edits.len(), 2);
+
+        let rewritten = db
+            .write_capture_edits(&edits)
+            .expect("Should be able to write edits");
+
+        assert_eq!(rewritten, 2);
+        assert_eq!(
+            load(&db),
+            "* TODO Design homepage layout
+* Sprint Meeting
+** TODO Sit down with Katie and Stacy
+Owner: Stacy
+"
+        );
+    }
+
+    #[test]
+    fn match_lines_with_inline_markup() {
+        let test = r#"* Launch
+Owner: *Stacy*
+* FOLD
+** FROM
+*** title
+"Owner:" owner
+** INTO
+*** owner
+"#;
+
+        assert_eq!(evaluate_folds(test), "* *Stacy*\n");
+    }
+
+    #[test]
+    fn match_keywords_and_tags() {
+        let test = r#"* TODO [#A] Write the report :work:urgent:
+* DONE File the expenses :work:
+* TODO Buy milk :home:
+* Read about TODO lists :work:
+* FOLD
+** FROM
+*** TODO|DONE title :work:
+** INTO
+*** DONE title :archived:"#;
+
+        assert_eq!(
+            evaluate_folds(test),
+            "* DONE Write the report :archived:\n\
+             * DONE File the expenses :archived:\n"
+        );
+    }
+
+    #[test]
+    fn propagate_edits_to_tagged_headlines() {
+        let notes = "* TODO [#A] Write the report :work:\n* TODO Buy milk :home:\n";
+        let fold_document = "* FOLD
+** FROM
+*** TODO title :work:
+** INTO
+*** TODO title :work:
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+        let category_id = db
+            .write_top_level_category(&CategoryBlock::new("Notes", 0, Parser::new(notes).parse()))
+            .expect("Should be able to write notes")
+            .category_id;
+
+        let fold_blocks = Parser::new(fold_document).parse();
+        let fold = match &fold_blocks[0] {
+            Block::Category(category) => Fold::parse(category).expect("Fold should parse"),
+            _ => panic!("Fold document should start with a category"),
+        };
+
+        let edits = {
+            let matrix = DbBlockMatrix::new(&db.connection, category_id).unwrap();
+            let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+            let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+            let bump = Bump::new();
+            let result = fold.evaluate(&bump, &[Block::Category(category)]);
+
+            result
+                .diff(&Parser::new("* TODO Write the final report :work:\n").parse())
+                .expect("Edited result should diff")
+        };
+
+        db.write_capture_edits(&edits).expect("Should be able to write edits");
+
+        let matrix = DbBlockMatrix::new(&db.connection, category_id).unwrap();
+        let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+        let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+        assert_eq!(
+            render_to_org(Block::Category(category)),
+            "* TODO [#A] Write the final report :work:\n* TODO Buy milk :home:\n"
+        );
+
+        let tagged: Vec<String> = db
+            .connection
+            .prepare(
+                "SELECT cb.title FROM category_tags ct
+                 INNER JOIN category_blocks cb ON cb.id = ct.category_id
+                 WHERE ct.tag = 'work' AND cb.todo_keyword = 'TODO'",
+            )
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tagged, vec!["Write the final report"]);
+    }
+
+    #[test]
+    fn collect_unchecked_checkboxes() {
+        let test = r#"* Packing
+- [ ] Tent
+  - [X] Poles
+  - [ ] Pegs
+* Groceries
+1. [X] Milk
+2. [ ] Bread
+3. Eggs
+* FOLD
+** FROM
+*** list
+- [ ] item
+** INTO
+*** "TODO" item
+- [ ] list"#;
+
+        assert_eq!(
+            evaluate_folds(test),
+            "* TODO Tent\n\
+             - [ ] Packing\n\
+             * TODO Pegs\n\
+             - [ ] Packing\n\
+             * TODO Bread\n\
+             - [ ] Groceries\n"
+        );
+    }
+
+    #[test]
+    fn propagate_edits_to_list_items() {
+        let notes = "* Packing\n- [ ] Tent\n- [X] Stove\n";
+        let fold_document = "* FOLD
+** FROM
+*** list
+- [ ] item
+** INTO
+*** list
+- [ ] item
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+        let category_id = db
+            .write_top_level_category(&CategoryBlock::new("Notes", 0, Parser::new(notes).parse()))
+            .expect("Should be able to write notes")
+            .category_id;
+
+        let fold_blocks = Parser::new(fold_document).parse();
+        let fold = match &fold_blocks[0] {
+            Block::Category(category) => Fold::parse(category).expect("Fold should parse"),
+            _ => panic!("Fold document should start with a category"),
+        };
+
+        let edits = {
+            let matrix = DbBlockMatrix::new(&db.connection, category_id).unwrap();
+            let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+            let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+            let bump = Bump::new();
+            let result = fold.evaluate(&bump, &[Block::Category(category)]);
+
+            result
+                .diff(&Parser::new("* Packing\n- [ ] Tent and poles\n").parse())
+                .expect("Edited result should diff")
+        };
+
+        assert_eq!(edits.len(), 1);
+        db.write_capture_edits(&edits).expect("Should be able to write edits");
+
+        let matrix = DbBlockMatrix::new(&db.connection, category_id).unwrap();
+        let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+        let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+        assert_eq!(
+            render_to_org(Block::Category(category)),
+            "* Packing\n- [ ] Tent and poles\n- [X] Stove\n"
+        );
+    }
+
+    #[test]
+    fn match_table_rows_by_header() {
+        let test = r#"* Website
+| Owner | Task                | Status |
+|-------+---------------------+--------|
+| Stacy | Design the homepage | open   |
+| Sam   | Write the copy      | done   |
+* Laundry
+| Task            | Status |
+|-----------------+--------|
+| Fold the shirts | open   |
+* FOLD
+** FROM
+*** project
+| status | task |
+|--------+------|
+| "open" | task |
+** INTO
+*** "TODO" task
+| Project |
+|---------|
+| project |"#;
+
+        assert_eq!(
+            evaluate_folds(test),
+            "* TODO Design the homepage\n\
+             | Project |\n\
+             |---------|\n\
+             | Website |\n\
+             * TODO Fold the shirts\n\
+             | Project |\n\
+             |---------|\n\
+             | Laundry |\n"
+        );
+    }
+
+    #[test]
+    fn propagate_edits_to_table_cells() {
+        let notes = "* Website\n| Task | Owner |\n|------+-------|\n| Copy | Sam   |\n";
+        let fold_document = "* FOLD
+** FROM
+*** project
+| Owner | Task |
+|-------+------|
+| owner | task |
+** INTO
+*** owner
+- task
+";
+
+        let mut db = AmbleDB::new(":memory:").expect("Could not create db");
+        let category_id = db
+            .write_top_level_category(&CategoryBlock::new("Notes", 0, Parser::new(notes).parse()))
+            .expect("Should be able to write notes")
+            .category_id;
+
+        let fold_blocks = Parser::new(fold_document).parse();
+        let fold = match &fold_blocks[0] {
+            Block::Category(category) => Fold::parse(category).expect("Fold should parse"),
+            _ => panic!("Fold document should start with a category"),
+        };
+
+        let edits = {
+            let matrix = DbBlockMatrix::new(&db.connection, category_id).unwrap();
+            let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+            let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+            let bump = Bump::new();
+            let result = fold.evaluate(&bump, &[Block::Category(category)]);
+
+            result
+                .diff(&Parser::new("* Alex\n- Copy edits\n").parse())
+                .expect("Edited result should diff")
+        };
+
+        assert_eq!(edits.len(), 2);
+        db.write_capture_edits(&edits).expect("Should be able to write edits");
+
+        let matrix = DbBlockMatrix::new(&db.connection, category_id).unwrap();
+        let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+        let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+        assert_eq!(
+            render_to_org(Block::Category(category)),
+            "* Website\n\
+             | Task       | Owner |\n\
+             |------------+-------|\n\
+             | Copy edits | Alex  |\n"
+        );
+    }
+
+    #[test]
+    fn match_date_captures() {
+        let name = "\"Due\" due:date";
+        let tokens = Tokenizer::new(name).get_tokens();
+        let matcher = ValueMatcher::parse(name, &tokens);
+
+        let test = "Due <2023-10-14 Sat>";
+        let spans = matcher.capture_spans(test).expect("Value should match");
+        let (start, end) = spans["due"];
+        assert_eq!(&test[start..end], "<2023-10-14 Sat>");
+
+        assert!(matcher.capture_spans("Due next week").is_none());
+        assert!(matcher.capture_spans("Due <2023-10-14> or later").is_none());
+    }
+
+    #[test]
+    fn filter_and_sort_by_date() {
+        let test = r#"* TODO Design homepage layout
+  :Due: <2023-10-14>
+* TODO Organize team-building event
+  :Due: <2023-10-30 Mon 14:00>
+* TODO Need to do the laundry
+  :Due: <2023-09-20>
+* TODO Call the bank
+  :Due: whenever
+* FOLD
+:WHERE: due >= 2023-10-01
+:WHERE: due < <2024-01-01>
+:SORT: due desc
+** FROM
+*** "TODO" title
+    :Due: due:date
+** INTO
+*** "TODO" title
+    :Due: due"#;
+
+        assert_eq!(
+            evaluate_folds(test),
+            "* TODO Organize team-building event\n\
+             :Due: <2023-10-30 Mon 14:00>\n\
+             * TODO Design homepage layout\n\
+             :Due: <2023-10-14>\n"
+        );
+
+        let sorted = test
+            .replace(":WHERE: due >= 2023-10-01\n:WHERE: due < <2024-01-01>\n", "")
+            .replace(":SORT: due desc", ":SORT: due")
+            .replace(":Due: due:date", ":Due: due");
+
+        assert_eq!(
+            evaluate_folds(&sorted),
+            "* TODO Need to do the laundry\n\
+             :Due: <2023-09-20>\n\
+             * TODO Design homepage layout\n\
+             :Due: <2023-10-14>\n\
+             * TODO Organize team-building event\n\
+             :Due: <2023-10-30 Mon 14:00>\n\
+             * TODO Call the bank\n\
+             :Due: whenever\n"
+        );
+    }
+
+    #[test]
+    fn capture_non_ascii_values() {
+        let name = "\"会議\" topic \"—\" détail";
+        let tokens = Tokenizer::new(name).get_tokens();
+        let matcher = ValueMatcher::parse(name, &tokens);
+
+        let test = "会議 Réunion de l'équipe — café ☕";
+        let spans = matcher.capture_spans(test).expect("Value should match");
+
+        let (start, end) = spans["topic"];
+        assert_eq!(&test[start..end], "Réunion de l'équipe");
+
+        let (start, end) = spans["détail"];
+        assert_eq!(&test[start..end], "café ☕");
     }
 
     #[test]
@@ -0,0 +1,191 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use anyhow::{anyhow, Context};
+
+use crate::air::Block;
+
+use super::value_matcher::ValueMatcher;
+
+/// Identifies the database row a captured value was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CaptureSource {
+    /// The value lives in the name of a row in `category_blocks`
+    Category(i64),
+    /// The value lives in the content of a row in `text_blocks`
+    Text(i64),
+    /// The value lives in the value of a row in `properties`
+    Property(i64),
+    /// The value lives in the content of a row in `list_items`
+    ListItem(i64),
+    /// The value lives in the content of a row in `table_cells`
+    TableCell(i64),
+}
+
+/// A value bound to a capture during matching, alongside where it came from
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub value: String,
+    /// None when the source block has never been written to the database
+    pub source: Option<CaptureSource>,
+    /// Byte span of the value within the source's name or content
+    pub span: (usize, usize),
+}
+
+impl AsRef<str> for Capture {
+    fn as_ref(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Maps capture names to the values they were bound to during matching
+pub type Bindings = HashMap<String, Capture>;
+
+/// A change to a captured value that should be written back to its source
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureEdit {
+    pub source: CaptureSource,
+    pub span: (usize, usize),
+    pub old_value: String,
+    pub new_value: String,
+}
+
+/// The template and bindings that produced a single value (a category name,
+/// property value, line of text, list item or table cell) in a fold result
+#[derive(Debug)]
+pub(super) struct Origin {
+    pub(super) template: ValueMatcher,
+    pub(super) bindings: Bindings,
+}
+
+/// The output of evaluating a fold, along with the provenance of every
+/// value in it, so edits to the output can be traced back to their sources
+#[derive(Debug)]
+pub struct FoldResult<'a> {
+    pub blocks: Vec<Block<'a>>,
+    /// One origin per value, in the order `collect_values` visits them
+    origins: Vec<Origin>,
+}
+
+/// Collects category names, property values, non blank lines of text, the
+/// content of list items and non blank cells below table headers in
+/// document order. Verbatim blocks and keywords hold no values.
+fn collect_values<'b>(blocks: &[Block<'b>], values: &mut Vec<Cow<'b, str>>) {
+    for block in blocks {
+        match block {
+            Block::Category(category) => {
+                values.push(Cow::Borrowed(category.name));
+                collect_values(&category.children, values);
+            }
+            Block::RichText(rich_text) => values.extend(
+                rich_text
+                    .lines()
+                    .into_iter()
+                    .map(|line| line.content)
+                    .filter(|line| !line.trim().is_empty()),
+            ),
+            Block::Text(text) => {
+                if !text.content.trim().is_empty() {
+                    values.push(Cow::Borrowed(text.content));
+                }
+            }
+            Block::Property(property) => values.push(Cow::Borrowed(property.value)),
+            Block::Drawer(drawer) => values.extend(
+                drawer.properties.iter().map(|property| Cow::Borrowed(property.value)),
+            ),
+            Block::List(list) => collect_values(&list.children, values),
+            Block::ListItem(item) => {
+                values.push(Cow::Borrowed(item.content));
+                collect_values(&item.children, values);
+            }
+            Block::Table(table) => {
+                for row in table.body() {
+                    for cell in &row.cells {
+                        if !cell.content.trim().is_empty() {
+                            values.push(Cow::Borrowed(cell.content));
+                        }
+                    }
+                }
+            }
+            Block::Verbatim(_) | Block::Keyword(_) => (),
+        }
+    }
+}
+
+impl<'a> FoldResult<'a> {
+    pub(super) fn new(blocks: Vec<Block<'a>>, origins: Vec<Origin>) -> Self {
+        FoldResult { blocks, origins }
+    }
+
+    /// Compares an edited copy of this result against the evaluated output
+    /// and returns the edits that need to be made to the captured sources.
+    /// Only in place edits of captured values are supported; adding or
+    /// removing headlines and lines, or changing template literals, is an
+    /// error.
+    pub fn diff(&self, edited: &[Block]) -> Result<Vec<CaptureEdit>, anyhow::Error> {
+        let mut values: Vec<Cow<str>> = Vec::new();
+        collect_values(edited, &mut values);
+
+        if values.len() != self.origins.len() {
+            return Err(anyhow!(
+                "Edited fold result has {} values but the evaluated result has {}",
+                values.len(),
+                self.origins.len()
+            ));
+        }
+
+        let mut edits: HashMap<(CaptureSource, (usize, usize)), CaptureEdit> = HashMap::new();
+
+        for (origin, value) in self.origins.iter().zip(values) {
+            let spans = origin.template.capture_spans(&value).context(format!(
+                "Edited value {:?} no longer matches its template",
+                value.trim()
+            ))?;
+
+            for (name, (start, end)) in spans {
+                let new_value = &value[start..end];
+
+                let capture = origin
+                    .bindings
+                    .get(&name)
+                    .context(format!("Capture {} was not bound during evaluation", name))?;
+
+                if capture.value == new_value {
+                    continue;
+                }
+
+                let source = capture.source.context(format!(
+                    "Capture {} was not read from a block stored in the database",
+                    name
+                ))?;
+
+                match edits.get(&(source, capture.span)) {
+                    Some(edit) if edit.new_value != new_value => {
+                        return Err(anyhow!(
+                            "Conflicting edits for capture {}: {:?} and {:?}",
+                            name,
+                            edit.new_value,
+                            new_value
+                        ))
+                    }
+                    Some(_) => (),
+                    None => {
+                        edits.insert(
+                            (source, capture.span),
+                            CaptureEdit {
+                                source,
+                                span: capture.span,
+                                old_value: capture.value.clone(),
+                                new_value: new_value.to_string(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut edits: Vec<CaptureEdit> = edits.into_values().collect();
+        edits.sort_by_key(|edit| (edit.source, edit.span));
+
+        Ok(edits)
+    }
+}
@@ -1,27 +1,41 @@
-use crate::parsing::{Token, TokenType};
-use std::{char, collections::HashMap};
+use crate::{
+    air::Timestamp,
+    parsing::{Token, TokenType},
+};
+use std::collections::HashMap;
 
+/// Returns the byte span of the first occurrence of `substring` in `source`
 pub fn get_position_of_substring(source: &str, substring: &str) -> Option<(usize, usize)> {
-    let source_chars: Vec<char> = source.chars().collect();
-    let substring_chars: Vec<char> = substring.chars().collect();
+    source
+        .find(substring)
+        .map(|start| (start, start + substring.len()))
+}
 
-    let start = source_chars
-        .windows(substring_chars.len())
-        .position(|window| window == substring_chars.as_slice());
+/// Narrows `start..end` so it excludes leading and trailing whitespace
+fn trimmed_span(input: &str, start: usize, end: usize) -> (usize, usize) {
+    let slice = &input[start..end];
+    let trimmed_start = start + (slice.len() - slice.trim_start().len());
+    let trimmed_end = end - (slice.len() - slice.trim_end().len());
 
-    match start {
-        Some(start) => Some((start, start + substring_chars.len())),
-        None => None,
-    }
+    (trimmed_start, trimmed_end.max(trimmed_start))
+}
+
+/// What a capture must hold for its matcher to match, written after its
+/// name as in `due:date`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureType {
+    Text,
+    /// An org timestamp, such as `<2023-10-14 Sat>`
+    Date,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum ValueComponent {
     Literal(String),
-    Capture(String),
+    Capture(String, CaptureType),
 }
 
-fn parse_literal(source: &str, tokens: &Vec<Token>, index: usize) -> (String, usize) {
+fn parse_literal(source: &str, tokens: &[Token], index: usize) -> (String, usize) {
     let mut index = index;
 
     let lexeme_start = tokens[index].lexeme_start;
@@ -39,10 +53,10 @@ fn parse_literal(source: &str, tokens: &Vec<Token>, index: usize) -> (String, us
         }
     }
 
-    return (source[lexeme_start..lexeme_end].to_string(), index);
+    (source[lexeme_start..lexeme_end].to_string(), index)
 }
 
-fn parse_capture(source: &str, tokens: &Vec<Token>, index: usize) -> (String, usize) {
+fn parse_capture(source: &str, tokens: &[Token], index: usize) -> (String, usize) {
     let mut index = index;
 
     let lexeme_start = tokens[index].lexeme_start;
@@ -61,16 +75,59 @@ fn parse_capture(source: &str, tokens: &Vec<Token>, index: usize) -> (String, us
         index += 1;
     }
 
-    return (source[lexeme_start..lexeme_end].to_string(), index);
+    (source[lexeme_start..lexeme_end].to_string(), index)
+}
+
+/// Reads the `:type` that may follow a capture at `index`, returning the
+/// type and the index of the last token that was part of it
+fn parse_capture_type(source: &str, tokens: &[Token], index: usize) -> (CaptureType, usize) {
+    let is_colon = tokens
+        .get(index)
+        .is_some_and(|token| matches!(token.tok_type, TokenType::Colon));
+
+    match tokens.get(index + 1) {
+        Some(token)
+            if is_colon
+                && matches!(token.tok_type, TokenType::Text)
+                && &source[token.lexeme_start..token.lexeme_end] == "date" =>
+        {
+            (CaptureType::Date, index + 1)
+        }
+        _ => (CaptureType::Text, index),
+    }
+}
+
+fn is_date(component: &ValueComponent) -> bool {
+    matches!(component, ValueComponent::Capture(_, CaptureType::Date))
+}
+
+/// Records the span of a capture, returning None if the captured text is
+/// not of the type the capture requires
+fn insert_capture(
+    captures: &mut HashMap<String, (usize, usize)>,
+    component: &ValueComponent,
+    input: &str,
+    span: (usize, usize),
+) -> Option<()> {
+    let ValueComponent::Capture(name, capture_type) = component else {
+        return Some(());
+    };
+
+    if *capture_type == CaptureType::Date && Timestamp::parse(&input[span.0..span.1]).is_none() {
+        return None;
+    }
+
+    captures.insert(name.to_string(), span);
+    Some(())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ValueMatcher {
     components: Vec<ValueComponent>,
 }
 
 impl ValueMatcher {
-    pub fn parse(source: &str, tokens: &Vec<Token>) -> ValueMatcher {
+    pub fn parse(source: &str, tokens: &[Token]) -> ValueMatcher {
         let mut index = 0;
 
         let mut components: Vec<ValueComponent> = Vec::new();
@@ -86,8 +143,9 @@ impl ValueMatcher {
                 }
                 TokenType::Text => {
                     let (val, idx) = parse_capture(source, tokens, index);
+                    let (capture_type, idx) = parse_capture_type(source, tokens, idx);
                     index = idx;
-                    components.push(ValueComponent::Capture(val))
+                    components.push(ValueComponent::Capture(val, capture_type))
                 }
                 _ => (),
             }
@@ -98,11 +156,14 @@ impl ValueMatcher {
         ValueMatcher { components }
     }
 
-    pub fn capture(&self, input: &str) -> Option<HashMap<String, String>> {
-        let mut captures: HashMap<String, String> = HashMap::new();
+    /// Matches `input` against this matcher, returning the span of each
+    /// captured value within `input`. Spans exclude surrounding whitespace.
+    /// Date captures only match text that is a single timestamp.
+    pub fn capture_spans(&self, input: &str) -> Option<HashMap<String, (usize, usize)>> {
+        let mut captures: HashMap<String, (usize, usize)> = HashMap::new();
 
         let mut last_slice: (usize, usize) = (0, 0);
-        let mut capture_pending: Option<&str> = None;
+        let mut capture_pending: Option<&ValueComponent> = None;
         for component in &self.components {
             match component {
                 ValueComponent::Literal(literal) => {
@@ -115,10 +176,14 @@ impl ValueMatcher {
 
                     if let Some(capture) = capture_pending {
                         if slice.0 > last_slice.1 {
-                            captures.insert(
-                                capture.to_string(),
-                                input[last_slice.1..slice.0].trim().to_string(),
-                            );
+                            insert_capture(
+                                &mut captures,
+                                capture,
+                                input,
+                                trimmed_span(input, last_slice.1, slice.0),
+                            )?;
+                        } else if is_date(capture) {
+                            return None;
                         }
 
                         capture_pending = None;
@@ -126,20 +191,41 @@ impl ValueMatcher {
 
                     last_slice = slice;
                 }
-                ValueComponent::Capture(capture) => {
-                    capture_pending = Some(capture);
+                ValueComponent::Capture(..) => {
+                    capture_pending = Some(component);
                 }
             }
         }
 
         // Ensure we resolve any dangling captures
         if let Some(capture) = capture_pending {
-            captures.insert(
-                capture.to_string(),
-                input[last_slice.1..input.len()].trim().to_string(),
-            );
+            insert_capture(
+                &mut captures,
+                capture,
+                input,
+                trimmed_span(input, last_slice.1, input.len()),
+            )?;
         }
 
         Some(captures)
     }
+
+    /// Builds a value from this matcher, substituting each capture with its
+    /// binding. Returns None if any capture is unbound.
+    pub fn instantiate<V: AsRef<str>>(&self, bindings: &HashMap<String, V>) -> Option<String> {
+        let mut parts: Vec<&str> = Vec::new();
+
+        for component in &self.components {
+            let part = match component {
+                ValueComponent::Literal(literal) => literal.as_str(),
+                ValueComponent::Capture(capture, _) => bindings.get(capture)?.as_ref(),
+            };
+
+            if !part.is_empty() {
+                parts.push(part);
+            }
+        }
+
+        Some(parts.join(" "))
+    }
 }
@@ -0,0 +1,181 @@
+use std::fmt;
+
+use anyhow::Context;
+use bumpalo::Bump;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::air::{Block, CategoryBlock, PropertyBlock, RichTextBlock, TextBlock};
+
+use super::lexer::{TokenType, Tokenizer};
+
+/// A JSON value with the fields of objects kept in document order, which
+/// `serde_json::Value` does not preserve. Scalars are kept as their text.
+enum JsonValue {
+    Object(Vec<(String, JsonValue)>),
+    Array(Vec<JsonValue>),
+    Scalar(String),
+}
+
+struct JsonValueVisitor;
+
+impl<'de> Visitor<'de> for JsonValueVisitor {
+    type Value = JsonValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<JsonValue, E> {
+        Ok(JsonValue::Scalar(value.to_string()))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<JsonValue, E> {
+        Ok(JsonValue::Scalar(value.to_string()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<JsonValue, E> {
+        Ok(JsonValue::Scalar(value.to_string()))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<JsonValue, E> {
+        // Formatted as JSON would, so 1.0 stays 1.0 rather than becoming 1
+        let number = serde_json::Number::from_f64(value)
+            .map_or_else(|| value.to_string(), |number| number.to_string());
+        Ok(JsonValue::Scalar(number))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<JsonValue, E> {
+        Ok(JsonValue::Scalar(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<JsonValue, E> {
+        Ok(JsonValue::Scalar(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<JsonValue, E> {
+        Ok(JsonValue::Scalar("null".to_string()))
+    }
+
+    fn visit_none<E>(self) -> Result<JsonValue, E> {
+        Ok(JsonValue::Scalar("null".to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JsonValue, A::Error> {
+        let mut elements = Vec::new();
+
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+
+        Ok(JsonValue::Array(elements))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<JsonValue, A::Error> {
+        let mut fields = Vec::new();
+
+        while let Some(field) = map.next_entry()? {
+            fields.push(field);
+        }
+
+        Ok(JsonValue::Object(fields))
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<JsonValue, D::Error> {
+        deserializer.deserialize_any(JsonValueVisitor)
+    }
+}
+
+/// Forms a category named `name` from a JSON value
+fn category<'a>(bump: &'a Bump, name: &str, value: &JsonValue, level: usize) -> Block<'a> {
+    // Category names end in a line break, as they do when parsed from org,
+    // and are kept to one line so they stay a single headline
+    Block::Category(CategoryBlock::new(
+        bump.alloc_str(&format!("{}\n", name.replace('\n', " "))),
+        level,
+        children(bump, value, level + 1),
+    ))
+}
+
+/// Forms the lines of text holding a scalar
+fn text_lines<'a>(bump: &'a Bump, scalar: &str) -> Vec<Block<'a>> {
+    scalar
+        .split('\n')
+        .map(|line| {
+            Block::RichText(RichTextBlock {
+                children: vec![Block::Text(TextBlock {
+                    id: None,
+                    content: bump.alloc_str(&format!("{}\n", line)),
+                    markup: None,
+                })],
+            })
+        })
+        .collect()
+}
+
+/// Determines if a field can be written as a `:key: value` line and read
+/// back as the same property. The key must be a single word without
+/// colons, and the value a single line without surrounding whitespace.
+fn is_property(key: &str, value: &str) -> bool {
+    let line = format!(":{}:", key);
+    let tokens: Vec<TokenType> = Tokenizer::new(&line).map(|token| token.tok_type).collect();
+
+    matches!(tokens.as_slice(), [TokenType::Colon, TokenType::Text, TokenType::Colon])
+        && !key.eq_ignore_ascii_case("PROPERTIES")
+        && !value.contains('\n')
+        && value.trim() == value
+}
+
+/// Converts the contents of a JSON value into blocks, with any categories
+/// at `level`. Categories come after every other block, as anything
+/// written after a headline in org belongs to it.
+fn children<'a>(bump: &'a Bump, value: &JsonValue, level: usize) -> Vec<Block<'a>> {
+    let mut blocks: Vec<Block<'a>> = Vec::new();
+    let mut categories: Vec<Block<'a>> = Vec::new();
+
+    match value {
+        JsonValue::Object(fields) => {
+            for (key, value) in fields {
+                match value {
+                    JsonValue::Scalar(scalar) if is_property(key, scalar) => {
+                        blocks.push(Block::Property(PropertyBlock::new(
+                            bump.alloc_str(key),
+                            bump.alloc_str(scalar),
+                        )))
+                    }
+                    _ => categories.push(category(bump, key, value, level)),
+                }
+            }
+        }
+        JsonValue::Array(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                match element {
+                    JsonValue::Scalar(scalar) => blocks.extend(text_lines(bump, scalar)),
+                    _ => categories.push(category(bump, &index.to_string(), element, level)),
+                }
+            }
+        }
+        JsonValue::Scalar(scalar) => blocks.extend(text_lines(bump, scalar)),
+    }
+
+    blocks.extend(categories);
+    blocks
+}
+
+/// Parses a JSON document into AIR, allocating any text in `bump`.
+///
+/// Fields of objects holding objects or arrays become categories named by
+/// the field, while fields holding scalars become properties. Scalars that
+/// could not be read back as a property, because their key is not a single
+/// word or their value spans lines, become a category holding the value as
+/// text instead. Elements of arrays become lines of text for scalars and
+/// categories named by their index for objects or arrays. Categories
+/// follow the properties and lines of their parent, which otherwise keep
+/// their order. A top level scalar becomes lines of text.
+pub fn parse_json<'a>(bump: &'a Bump, document: &str) -> Result<Vec<Block<'a>>, anyhow::Error> {
+    let value: JsonValue =
+        serde_json::from_str(document).context("Could not parse JSON document")?;
+
+    Ok(children(bump, &value, 1))
+}
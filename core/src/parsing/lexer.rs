@@ -1,5 +1,3 @@
-use std::usize;
-
 #[derive(Debug)]
 pub enum TokenType {
     Asterisk,     // *
@@ -14,12 +12,14 @@ pub enum TokenType {
     LParen,       // (
     HashPlus,     // #+
     Text,         // Normal text that you'd normally write
-    NumberPeriod, // '1.', '2.', etc..
+    NumberPeriod, // '1.', '12.', etc..
     Space,        // ' ' character
     Tab,          // '\t' character
-    EOF,
+    Eof,
 }
 
+/// A token in the source document. `lexeme_start` and `lexeme_end` are byte
+/// offsets into the document, and always fall on UTF-8 character boundaries
 #[derive(Debug)]
 pub struct Token {
     pub tok_type: TokenType,
@@ -27,81 +27,135 @@ pub struct Token {
     pub lexeme_end: usize
 }
 
-pub struct Tokenizer {
-    chars: Vec<char>,
-    num_chars: usize,
-    char_index: usize,
+/// Splits a document into tokens. Tokens are produced lazily by iterating
+/// over the tokenizer, in a single pass over the document without recursion,
+/// so arbitrarily long runs of text use constant stack space.
+pub struct Tokenizer<'a> {
+    document: &'a str,
+    byte_index: usize,
 }
 
-impl Tokenizer {
-    pub fn new(document: &str) -> Tokenizer {
+impl<'a> Tokenizer<'a> {
+    pub fn new(document: &'a str) -> Tokenizer<'a> {
         Tokenizer {
-            chars: document.chars().collect(),
-            num_chars: document.chars().count(),
-            char_index: 0,
+            document,
+            byte_index: 0,
         }
     }
 
-    fn peek_next_char(&mut self, index: usize) -> Option<char> {
-        if index >= self.num_chars {
-            None
-        } else {
-            Some(self.chars[index])
-        }
+    /// Returns the character starting at byte offset `index`
+    fn peek_next_char(&self, index: usize) -> Option<char> {
+        self.document.get(index..)?.chars().next()
     }
 
-    /// Determines the type and terminating index of the upcoming token
-    fn peek_next_token(&mut self, index: usize) -> (TokenType, usize) {
-        match self.peek_next_char(index) {
-            Some('*') => (TokenType::Asterisk, index + 1),
-            Some('"') => (TokenType::DoubleQuote, index + 1),
-            Some('(') => (TokenType::LParen, index + 1),
-            Some(')') => (TokenType::RParen, index + 1),
-            Some('=') => (TokenType::EqualsSign, index + 1),
-            Some('\n') => (TokenType::LineBreak, index + 1),
-            Some('\r') if self.peek_next_char(index + 1) == Some('\n') => {
+    /// Returns the end of the run of digits starting at `index`
+    fn peek_digits_end(&self, index: usize) -> usize {
+        let digits = self.document[index..]
+            .chars()
+            .take_while(|char| char.is_numeric())
+            .map(char::len_utf8)
+            .sum::<usize>();
+
+        index + digits
+    }
+
+    /// Returns the end of the number starting at `index`, if it is followed
+    /// by a period
+    fn peek_number_period(&self, index: usize) -> Option<usize> {
+        let end = self.peek_digits_end(index);
+
+        (end > index && self.peek_next_char(end) == Some('.')).then_some(end)
+    }
+
+    /// Determines the type and terminating index of the token starting at
+    /// `index`, if it is anything other than text
+    fn peek_special_token(&self, index: usize) -> Option<(TokenType, usize)> {
+        let token = match self.peek_next_char(index)? {
+            '*' => (TokenType::Asterisk, index + 1),
+            '"' => (TokenType::DoubleQuote, index + 1),
+            '(' => (TokenType::LParen, index + 1),
+            ')' => (TokenType::RParen, index + 1),
+            '=' => (TokenType::EqualsSign, index + 1),
+            '\n' => (TokenType::LineBreak, index + 1),
+            '\r' if self.peek_next_char(index + 1) == Some('\n') => {
                 (TokenType::LineBreak, index + 2)
             }
-            Some('|') => (TokenType::Bar, index + 1),
-            Some(' ') => (TokenType::Space, index + 1),
-            Some('\t') => (TokenType::Tab, index + 1),
-            Some('[') => (TokenType::LBracket, index + 1),
-            Some(']') => (TokenType::RBracket, index + 1),
-            Some(':') => (TokenType::Colon, index + 1),
-            Some('#') if self.peek_next_char(index + 1) == Some('+') => {
-                (TokenType::HashPlus, index + 1)
+            '|' => (TokenType::Bar, index + 1),
+            ' ' => (TokenType::Space, index + 1),
+            '\t' => (TokenType::Tab, index + 1),
+            '[' => (TokenType::LBracket, index + 1),
+            ']' => (TokenType::RBracket, index + 1),
+            ':' => (TokenType::Colon, index + 1),
+            '#' if self.peek_next_char(index + 1) == Some('+') => {
+                (TokenType::HashPlus, index + 2)
             }
-            Some(char) if char.is_numeric() && self.peek_next_char(index + 1) == Some('.') => {
-                (TokenType::NumberPeriod, index + 2)
+            char if char.is_numeric() => {
+                (TokenType::NumberPeriod, self.peek_number_period(index)? + 1)
             }
-            None => (TokenType::EOF, self.num_chars),
-            _ => {
-                let (tok_type, i) = self.peek_next_token(index + 1);
-                match tok_type {
-                    TokenType::Text => (tok_type, i),
-                    _ => (TokenType::Text, index + 1),
+            _ => return None,
+        };
+
+        Some(token)
+    }
+
+    /// Determines the type and terminating index of the upcoming token
+    fn peek_next_token(&self, index: usize) -> (TokenType, usize) {
+        if let Some(token) = self.peek_special_token(index) {
+            return token;
+        }
+
+        // Anything else is text, which runs until the next special token
+        let mut end = index;
+
+        while let Some(char) = self.peek_next_char(end) {
+            // Digits only start a token when the whole run ends in a period,
+            // so skip a run that doesn't in one step rather than rescanning it
+            // from every digit
+            if char.is_numeric() {
+                let digits_end = self.peek_digits_end(end);
+
+                if end > index && self.peek_next_char(digits_end) == Some('.') {
+                    break;
                 }
+                end = digits_end;
+                continue;
+            }
+
+            if end > index && self.peek_special_token(end).is_some() {
+                break;
             }
+            end += char.len_utf8();
+        }
+
+        if end == index {
+            (TokenType::Eof, self.document.len())
+        } else {
+            (TokenType::Text, end)
         }
     }
 
     pub fn get_tokens(&mut self) -> Vec<Token> {
-        let mut tokens: Vec<Token> = Vec::new();
-
-        loop {
-            let (tok_type, index) = self.peek_next_token(self.char_index);
-            let token = Token {
-                tok_type,
-                lexeme_start: self.char_index,
-                lexeme_end: index,
-            };
-            self.char_index = index;
-            match token.tok_type {
-                TokenType::EOF => break,
-                _ => tokens.push(token)
+        self.collect()
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let (tok_type, index) = self.peek_next_token(self.byte_index);
+
+        match tok_type {
+            TokenType::Eof => None,
+            _ => {
+                let token = Token {
+                    tok_type,
+                    lexeme_start: self.byte_index,
+                    lexeme_end: index,
+                };
+                self.byte_index = index;
+                Some(token)
             }
         }
-
-        tokens
     }
 }
@@ -0,0 +1,445 @@
+use bumpalo::Bump;
+
+use crate::air::{
+    Block, CategoryBlock, Checkbox, ListBlock, ListItemBlock, ListKind, RichTextBlock, TextBlock,
+    VerbatimBlock,
+};
+
+/// Parses Markdown documents into AIR, so Markdown notes can be stored and
+/// matched by folds alongside org documents. Any text that is not part of
+/// the document, such as the joined name of a Setext heading, is allocated
+/// in the bump.
+///
+/// ATX (`## Heading`) and Setext (underlined) headings become categories at
+/// their heading level. As with org headlines, a category's name is the
+/// rest of its heading line, including the line break, with the lines of a
+/// Setext heading joined by spaces. List items become lists, with more
+/// deeply indented items nested in the item before them. Fenced code blocks
+/// become verbatim blocks, written as org source blocks tagged with the
+/// language of the fence, or example blocks when it has none. Paragraphs
+/// and unclosed code blocks become a rich text block holding one text block
+/// per line, and blank lines are kept as rich text blocks of their own.
+pub struct MarkdownParser<'a> {
+    bump: &'a Bump,
+    document: &'a str,
+    /// Byte offsets of the start and end of each line, including its line
+    /// break
+    lines: Vec<(usize, usize)>,
+}
+
+/// Returns the line with up to three spaces of indentation removed, or None
+/// if it is indented further, which Markdown treats as code or continuation
+fn strip_indent(line: &str) -> Option<&str> {
+    let stripped = line.trim_start_matches(' ');
+
+    if line.len() - stripped.len() > 3 || stripped.starts_with('\t') {
+        return None;
+    }
+
+    Some(stripped)
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Returns the level of an ATX heading line and the byte offset within the
+/// line at which its name starts
+fn atx_heading(line: &str) -> Option<(usize, usize)> {
+    let stripped = strip_indent(line)?;
+    let level = stripped.len() - stripped.trim_start_matches('#').len();
+
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+
+    let rest = &stripped[level..];
+    let name = rest.trim_start_matches([' ', '\t']);
+
+    // The hashes must be followed by whitespace or the end of the line
+    if name.len() == rest.len() && !is_blank(rest) {
+        return None;
+    }
+
+    Some((level, line.len() - name.len()))
+}
+
+/// Returns the level of the heading a Setext underline of `=` or `-`
+/// characters makes of the paragraph above it
+fn setext_underline(line: &str) -> Option<usize> {
+    let underline = strip_indent(line)?.trim_end();
+
+    if !underline.is_empty() && underline.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !underline.is_empty() && underline.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Determines if the line is a thematic break, three or more `-`, `*` or
+/// `_` characters optionally separated by spaces
+fn is_thematic_break(line: &str) -> bool {
+    let Some(stripped) = strip_indent(line) else {
+        return false;
+    };
+
+    let marks: Vec<char> = stripped.chars().filter(|c| !c.is_whitespace()).collect();
+
+    marks.len() >= 3
+        && matches!(marks[0], '-' | '*' | '_')
+        && marks.iter().all(|c| *c == marks[0])
+}
+
+/// Returns the character and length of the fence opening a fenced code
+/// block
+fn code_fence(line: &str) -> Option<(char, usize)> {
+    let stripped = strip_indent(line)?;
+    let fence_char = stripped.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let length = stripped.len() - stripped.trim_start_matches(fence_char).len();
+
+    // Backtick fences cannot have backticks in their info string
+    if length < 3 || (fence_char == '`' && stripped[length..].contains('`')) {
+        return None;
+    }
+
+    Some((fence_char, length))
+}
+
+/// Determines if the line closes a code block opened by a fence of
+/// `length` `fence_char` characters
+fn closes_code_fence(line: &str, fence_char: char, length: usize) -> bool {
+    let Some(stripped) = strip_indent(line) else {
+        return false;
+    };
+    let stripped = stripped.trim_end();
+
+    stripped.len() >= length && stripped.chars().all(|c| c == fence_char)
+}
+
+/// Determines if the line starts a list item, either a bullet (`-`, `*`,
+/// `+`) or an ordered item (`1.`, `1)`), followed by whitespace, and is not
+/// a thematic break. Returns the width of its indentation, the kind of list
+/// it belongs to and the byte offset within the line at which its content
+/// starts.
+fn list_item(line: &str) -> Option<(usize, ListKind, usize)> {
+    if is_thematic_break(line) {
+        return None;
+    }
+
+    let item = line.trim_start_matches([' ', '\t']);
+    let indent = line.len() - item.len();
+    let digits = item.len() - item.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let (kind, marker_length) = match item.as_bytes().get(digits) {
+        Some(b'-' | b'*' | b'+') if digits == 0 => (ListKind::Unordered, 1),
+        Some(b'.' | b')') if (1..=9).contains(&digits) => (ListKind::Ordered, digits + 1),
+        _ => return None,
+    };
+
+    let rest = &item[marker_length..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t', '\r', '\n']) {
+        return None;
+    }
+
+    let spacing = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+
+    Some((indent, kind, indent + marker_length + spacing))
+}
+
+fn is_list_item(line: &str) -> bool {
+    list_item(line).is_some()
+}
+
+impl<'a> MarkdownParser<'a> {
+    pub fn new(bump: &'a Bump, document: &'a str) -> MarkdownParser<'a> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+
+        for line in document.split_inclusive('\n') {
+            lines.push((start, start + line.len()));
+            start += line.len();
+        }
+
+        MarkdownParser {
+            bump,
+            document,
+            lines,
+        }
+    }
+
+    fn line(&self, index: usize) -> &'a str {
+        let (start, end) = self.lines[index];
+        &self.document[start..end]
+    }
+
+    /// Determines if the line starts a block that interrupts a paragraph or
+    /// list item
+    fn interrupts(&self, index: usize) -> bool {
+        let line = self.line(index);
+
+        is_blank(line)
+            || atx_heading(line).is_some()
+            || code_fence(line).is_some()
+            || is_thematic_break(line)
+            || is_list_item(line)
+    }
+
+    /// Forms a rich text block from the lines in `start..end`
+    fn rich_text(&self, start: usize, end: usize) -> Block<'a> {
+        Block::RichText(RichTextBlock {
+            children: (start..end)
+                .map(|index| {
+                    Block::Text(TextBlock {
+                        id: None,
+                        content: self.line(index),
+                        markup: None,
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns the index of the line closing the code block whose opening
+    /// fence is on line `index`, or None if it is never closed
+    fn find_closing_fence(&self, index: usize, fence_char: char, length: usize) -> Option<usize> {
+        (index + 1..self.lines.len())
+            .find(|&end| closes_code_fence(self.line(end), fence_char, length))
+    }
+
+    /// Forms a verbatim block from the code block between the fences on
+    /// lines `open` and `close`
+    fn verbatim(&self, open: usize, close: usize, fence_length: usize) -> VerbatimBlock<'a> {
+        let info = strip_indent(self.line(open)).unwrap_or_default()[fence_length..].trim();
+        let (begin, end) = if info.is_empty() {
+            ("#+BEGIN_EXAMPLE", "#+END_EXAMPLE")
+        } else {
+            (&*self.bump.alloc_str(&format!("#+BEGIN_SRC {}", info)), "#+END_SRC")
+        };
+
+        VerbatimBlock {
+            id: None,
+            begin,
+            content: &self.document[self.lines[open].1..self.lines[close].0],
+            end,
+            end_line_break: self.line(close).ends_with('\n'),
+        }
+    }
+
+    /// Finds the extent of the paragraph starting on line `index`. Returns
+    /// the index of the line after it, and the heading level if it is
+    /// underlined as a Setext heading, in which case the underline is not
+    /// counted as part of the paragraph.
+    fn find_paragraph_end(&self, index: usize) -> (usize, Option<usize>) {
+        let mut end = index + 1;
+
+        while end < self.lines.len() {
+            let line = self.line(end);
+
+            if let Some(level) = setext_underline(line) {
+                return (end, Some(level));
+            }
+
+            if self.interrupts(end) {
+                break;
+            }
+
+            end += 1;
+        }
+
+        (end, None)
+    }
+
+    /// Finds the extent of the list item starting on line `index`, which
+    /// runs until the next line that starts a block of its own
+    fn find_list_item_end(&self, index: usize) -> usize {
+        let mut end = index + 1;
+
+        while end < self.lines.len() && !self.interrupts(end) {
+            end += 1;
+        }
+
+        end
+    }
+
+    /// Parses the list item on line `index`, whose content starts at byte
+    /// offset `content_offset` within the line and runs over any lines
+    /// continuing it
+    fn parse_list_item(&self, index: usize, content_offset: usize) -> (ListItemBlock<'a>, usize) {
+        let line = self.line(index);
+        let end = self.find_list_item_end(index);
+
+        let rest = &line[content_offset..];
+        let checkbox = rest
+            .get(..3)
+            .filter(|checkbox| Checkbox::parse(checkbox).is_some() && rest[3..].starts_with(' '));
+        let content_start = self.lines[index].0 + content_offset + checkbox.map_or(0, |_| 4);
+
+        let item = ListItemBlock {
+            id: None,
+            bullet: &line[..content_offset],
+            checkbox,
+            content: &self.document[content_start..self.lines[end - 1].1],
+            children: Vec::new(),
+        };
+
+        (item, end)
+    }
+
+    /// Parses consecutive list items of the same kind and indentation into
+    /// a list. More deeply indented items form a list nested in the item
+    /// before them, and the list ends at any other line.
+    fn parse_list(&self, start: usize) -> Option<(ListBlock<'a>, usize)> {
+        let (indent, kind, _) = list_item(self.line(start))?;
+
+        let mut list = ListBlock {
+            id: None,
+            kind,
+            children: Vec::new(),
+        };
+        let mut index = start;
+
+        while index < self.lines.len() {
+            let Some((item_indent, item_kind, content_offset)) = list_item(self.line(index)) else {
+                break;
+            };
+
+            if item_indent > indent {
+                let Some(Block::ListItem(item)) = list.children.last_mut() else {
+                    break;
+                };
+
+                let (nested, new_index) = self.parse_list(index)?;
+                item.children.push(Block::List(nested));
+                index = new_index;
+                continue;
+            }
+
+            if item_indent < indent || item_kind != kind {
+                break;
+            }
+
+            let (item, new_index) = self.parse_list_item(index, content_offset);
+            list.children.push(Block::ListItem(item));
+            index = new_index;
+        }
+
+        Some((list, index))
+    }
+
+    /// Returns the name of a Setext heading underlining the lines in
+    /// `start..end`, which are joined by spaces when there are several
+    fn setext_name(&self, start: usize, end: usize) -> &'a str {
+        let (start_offset, end_offset) = (self.lines[start].0, self.lines[end - 1].1);
+        let text = &self.document[start_offset..end_offset];
+        let name = text.trim_start();
+
+        if end - start == 1 {
+            return name;
+        }
+
+        let line_break = &name[name.trim_end_matches(['\r', '\n']).len()..];
+        let lines: Vec<&str> = name.lines().map(str::trim).collect();
+
+        self.bump.alloc_str(&format!("{}{}", lines.join(" "), line_break))
+    }
+
+    /// Parses a category named `name` along with every block beneath it,
+    /// starting from the line after its heading
+    fn parse_category_block(
+        &self,
+        name: &'a str,
+        level: usize,
+        content_start: usize,
+    ) -> (CategoryBlock<'a>, usize) {
+        let mut block = CategoryBlock::new(name, level, Vec::new());
+
+        let mut index = content_start;
+
+        while let Some((new_block, new_index)) = self.parse_next_block(index, Some(level)) {
+            block.children.push(new_block);
+            index = new_index;
+        }
+
+        (block, index)
+    }
+
+    /// Checks whether a heading of `level` closes the category currently
+    /// being parsed
+    fn closes_category(level: usize, category_level: Option<usize>) -> bool {
+        category_level.is_some_and(|current| current >= level)
+    }
+
+    fn parse_next_block(
+        &self,
+        index: usize,
+        category_level: Option<usize>,
+    ) -> Option<(Block<'a>, usize)> {
+        if index >= self.lines.len() {
+            return None;
+        }
+
+        let line = self.line(index);
+        let (line_start, line_end) = self.lines[index];
+
+        if is_blank(line) {
+            return Some((self.rich_text(index, index + 1), index + 1));
+        }
+
+        if let Some((level, name_offset)) = atx_heading(line) {
+            if Self::closes_category(level, category_level) {
+                return None;
+            }
+
+            let (category, new_index) = self.parse_category_block(
+                &self.document[line_start + name_offset..line_end],
+                level,
+                index + 1,
+            );
+            return Some((Block::Category(category), new_index));
+        }
+
+        if let Some((fence_char, length)) = code_fence(line) {
+            return Some(match self.find_closing_fence(index, fence_char, length) {
+                Some(close) => (Block::Verbatim(self.verbatim(index, close, length)), close + 1),
+                None => (self.rich_text(index, self.lines.len()), self.lines.len()),
+            });
+        }
+
+        if is_thematic_break(line) {
+            return Some((self.rich_text(index, index + 1), index + 1));
+        }
+
+        if let Some((list, new_index)) = self.parse_list(index) {
+            return Some((Block::List(list), new_index));
+        }
+
+        let (end, setext_level) = self.find_paragraph_end(index);
+
+        match setext_level {
+            Some(level) => {
+                if Self::closes_category(level, category_level) {
+                    return None;
+                }
+
+                let (category, new_index) =
+                    self.parse_category_block(self.setext_name(index, end), level, end + 1);
+                Some((Block::Category(category), new_index))
+            }
+            None => Some((self.rich_text(index, end), end)),
+        }
+    }
+
+    pub fn parse(&self) -> Vec<Block<'a>> {
+        let mut index = 0;
+        let mut blocks = Vec::new();
+
+        while let Some((new_block, new_index)) = self.parse_next_block(index, None) {
+            blocks.push(new_block);
+            index = new_index;
+        }
+
+        blocks
+    }
+}
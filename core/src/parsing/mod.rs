@@ -1,5 +1,825 @@
 mod parser;
 mod lexer;
+mod json;
+mod markdown;
 
 pub use parser::*;
 pub use lexer::*;
+pub use json::parse_json;
+pub use markdown::MarkdownParser;
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bumpalo::Bump;
+
+    use crate::{
+        render_to_json, render_to_org, AmbleDB, Block, CategoryBlock, Checkbox, DbBlockMatrix, Fold,
+        Headline, ListKind, Markup, Parser,
+    };
+
+    use super::{parse_json, MarkdownParser, TokenType, Tokenizer};
+
+    fn render_document(document: &str) -> String {
+        let parser = Parser::new(document);
+
+        render_to_org(Block::Category(CategoryBlock::new("", 0, parser.parse())))
+    }
+
+    #[test]
+    fn parse_property_lines() {
+        let document = "* TODO Design homepage layout
+  :Project: Website Redesign
+  :Due: <2023-10-14>
+Some notes: not a property
+";
+
+        let parser = Parser::new(document);
+        let blocks = parser.parse();
+
+        let category = match &blocks[0] {
+            Block::Category(category) => category,
+            _ => panic!("Expected a category"),
+        };
+
+        let properties: Vec<(&str, &str)> = category
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Block::Property(p) => Some((p.key, p.value)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            properties,
+            vec![
+                ("Project", "Website Redesign"),
+                ("Due", "<2023-10-14>")
+            ]
+        );
+        assert!(matches!(category.children[2], Block::RichText(_)));
+    }
+
+    #[test]
+    fn parse_property_drawer() {
+        let document = "* Test Category
+:PROPERTIES:
+:ID:       d775182b
+:CREATED:
+:END:
+#+title: MapReduce
+";
+
+        let parser = Parser::new(document);
+        let blocks = parser.parse();
+
+        let category = match &blocks[0] {
+            Block::Category(category) => category,
+            _ => panic!("Expected a category"),
+        };
+
+        let Block::Drawer(drawer) = &category.children[0] else {
+            panic!("Expected a drawer");
+        };
+        assert_eq!((drawer.begin, drawer.end), (":PROPERTIES:", Some(":END:")));
+        match drawer.properties.as_slice() {
+            [id, created] => {
+                assert_eq!((id.key, id.value), ("ID", "d775182b"));
+                assert_eq!((created.key, created.value), ("CREATED", ""));
+            }
+            _ => panic!("Expected two properties"),
+        }
+        assert!(matches!(category.children[1], Block::Keyword(_)));
+        assert_eq!(render_document(document), document);
+    }
+
+    #[test]
+    fn render_empty_and_unclosed_drawers_round_trip() {
+        for document in [
+            "* Empty drawer\n:PROPERTIES:\n:END:\nbody\n",
+            "* Unclosed drawer\n  :PROPERTIES:\n  :ID: d775182b\nbody\n",
+            "* Outdented end\n  :PROPERTIES:\n  :ID: d775182b\n:END:\n",
+        ] {
+            assert_eq!(render_document(document), document);
+        }
+
+        let blocks = Parser::new("* Unclosed drawer\n:PROPERTIES:\n:ID: d775182b\n").parse();
+        let Block::Category(category) = &blocks[0] else {
+            panic!("Expected a category");
+        };
+        let Block::Drawer(drawer) = &category.children[0] else {
+            panic!("Expected a drawer");
+        };
+        assert_eq!((drawer.end, drawer.properties.len()), (None, 1));
+    }
+
+    #[test]
+    fn render_properties_round_trip() {
+        let document = "* Test Category
+:PROPERTIES:
+:ID: d775182b
+:END:
+:Due: <2023-10-14>
+Text after the properties
+";
+
+        assert_eq!(render_document(document), document);
+    }
+
+    #[test]
+    fn render_indented_and_aligned_properties_round_trip() {
+        let document = "* Test Category
+  :PROPERTIES:
+  :ID:       d775182b
+  :CREATED:  
+  :END:
+  :Project:  Website Redesign   
+\t:Due:\t<2023-10-14>
+";
+
+        assert_eq!(render_document(document), document);
+
+        let blocks = Parser::new(document).parse();
+        let Block::Category(category) = &blocks[0] else {
+            panic!("Expected a category");
+        };
+        let Block::Property(project) = &category.children[1] else {
+            panic!("Expected a property");
+        };
+        assert_eq!((project.value, project.trailing), ("Website Redesign", "   "));
+    }
+
+    #[test]
+    fn parse_lists() {
+        let document = "* Shopping
+- [ ] Groceries
+  + [x] Milk
+  + [-] Bread
+    1. Rye
+    12) Sourdough
+- Laundry
+Not an item
+  * indented asterisk
+-not a bullet
+";
+
+        let blocks = Parser::new(document).parse();
+        let Block::Category(category) = &blocks[0] else {
+            panic!("Expected a category");
+        };
+
+        let Block::List(list) = &category.children[0] else {
+            panic!("Expected a list");
+        };
+        assert_eq!(list.kind, ListKind::Unordered);
+        assert_eq!(list.children.len(), 2);
+
+        let Block::ListItem(groceries) = &list.children[0] else {
+            panic!("Expected a list item");
+        };
+        assert_eq!(
+            (groceries.bullet, groceries.checkbox_state(), groceries.content),
+            ("- ", Some(Checkbox::Unchecked), "Groceries\n")
+        );
+
+        let Block::List(nested) = &groceries.children[0] else {
+            panic!("Expected a nested list");
+        };
+        let checkboxes: Vec<Option<Checkbox>> = nested
+            .children
+            .iter()
+            .map(|child| match child {
+                Block::ListItem(item) => item.checkbox_state(),
+                _ => panic!("Expected a list item"),
+            })
+            .collect();
+        assert_eq!(checkboxes, vec![Some(Checkbox::Checked), Some(Checkbox::Partial)]);
+
+        let Block::ListItem(milk) = &nested.children[0] else {
+            panic!("Expected a list item");
+        };
+        assert_eq!(milk.checkbox, Some("[x]"));
+
+        let Block::ListItem(bread) = &nested.children[1] else {
+            panic!("Expected a list item");
+        };
+        let Block::List(numbered) = &bread.children[0] else {
+            panic!("Expected a numbered list");
+        };
+        assert_eq!(numbered.kind, ListKind::Ordered);
+        assert_eq!(numbered.children.len(), 2);
+
+        assert!(matches!(category.children[1], Block::RichText(_)));
+        assert!(matches!(category.children[2], Block::List(_)));
+        assert!(matches!(category.children[3], Block::RichText(_)));
+
+        assert_eq!(render_document(document), document);
+    }
+
+    #[test]
+    fn parse_and_align_tables() {
+        let document = "* Tasks
+  | Task | Owner |
+|-+-|
+|Design the homepage|Stacy|
+| Copy | Sam | extra
+| Café |
+After the table
+";
+
+        let blocks = Parser::new(document).parse();
+        let Block::Category(category) = &blocks[0] else {
+            panic!("Expected a category");
+        };
+        let Block::Table(table) = &category.children[0] else {
+            panic!("Expected a table");
+        };
+
+        let rows: Vec<Vec<&str>> = table
+            .rows
+            .iter()
+            .map(|row| row.cells.iter().map(|cell| cell.content).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Task", "Owner"],
+                vec![],
+                vec!["Design the homepage", "Stacy"],
+                vec!["Copy", "Sam", "extra"],
+                vec!["Café"],
+            ]
+        );
+        assert!(table.rows[1].rule);
+        assert_eq!(table.header().map(|row| row.cells.len()), Some(2));
+        assert_eq!(table.body().count(), 3);
+        assert!(matches!(category.children[1], Block::RichText(_)));
+
+        assert_eq!(
+            render_document(document),
+            "* Tasks
+  | Task                | Owner |       |
+|---------------------+-------+-------|
+| Design the homepage | Stacy |       |
+| Copy                | Sam   | extra |
+| Café                |       |       |
+After the table
+"
+        );
+    }
+
+    #[test]
+    fn parse_verbatim_blocks_and_keywords() {
+        let document = "#+TITLE: Parser notes
+  #+filetags:   :code:
+* Tokenizer
+#+begin_src c -n :tangle lexer.c
+/*
+* foo is not a headline
+*/
+#+end_src
+  #+BEGIN_QUOTE
+| not | a table |
+  #+END_QUOTE
+#+BEGIN_EXAMPLE
+never closed
+* Parser
+";
+
+        let root = CategoryBlock::new("", 0, Parser::new(document).parse());
+
+        assert_eq!(root.keyword("title"), Some("Parser notes"));
+        assert_eq!(root.keyword("FILETAGS"), Some(":code:"));
+
+        let Block::Category(tokenizer) = &root.children[2] else {
+            panic!("Expected a category");
+        };
+        let Block::Verbatim(source) = &tokenizer.children[0] else {
+            panic!("Expected a verbatim block");
+        };
+
+        assert_eq!(source.kind(), "src");
+        assert_eq!(source.language(), Some("c"));
+        assert_eq!(source.parameters(), "-n :tangle lexer.c");
+        assert_eq!(source.content, "/*\n* foo is not a headline\n*/\n");
+
+        let Block::Verbatim(quote) = &tokenizer.children[1] else {
+            panic!("Expected a verbatim block");
+        };
+
+        assert_eq!(quote.language(), None);
+        assert_eq!(quote.content, "| not | a table |\n");
+
+        // An unclosed block is ordinary text
+        assert!(matches!(tokenizer.children[2], Block::RichText(_)));
+        assert_eq!(root.children.len(), 4);
+
+        assert_eq!(render_document(document), document);
+    }
+
+    #[test]
+    fn render_keyword_whitespace_and_final_verbatim_block_round_trip() {
+        let document = "#+TITLE: Parser notes  \n#+BEGIN_SRC sh\necho done\n#+END_SRC";
+
+        let root = CategoryBlock::new("", 0, Parser::new(document).parse());
+
+        assert_eq!(root.keyword("TITLE"), Some("Parser notes"));
+        let Block::Verbatim(source) = &root.children[1] else {
+            panic!("Expected a verbatim block");
+        };
+        assert!(!source.end_line_break);
+
+        assert_eq!(render_document(document), document);
+    }
+
+    #[test]
+    fn parse_inline_markup() {
+        let document = "* Launch
+Ask *Stacy* about ~cargo publish~ and [[*Tasks][the tasks]]
+2 * 3 is not bold
+";
+
+        let root = CategoryBlock::new("", 0, Parser::new(document).parse());
+        let Block::Category(launch) = &root.children[0] else {
+            panic!("Expected a category");
+        };
+        let Block::RichText(line) = &launch.children[0] else {
+            panic!("Expected rich text");
+        };
+
+        let spans: Vec<(Option<Markup>, &str)> = line
+            .children
+            .iter()
+            .map(|span| match span {
+                Block::Text(text) => (text.markup, text.text()),
+                _ => panic!("Expected a text span"),
+            })
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                (None, "Ask "),
+                (Some(Markup::Bold), "Stacy"),
+                (None, " about "),
+                (Some(Markup::Code), "cargo publish"),
+                (None, " and "),
+                (Some(Markup::Link), "the tasks"),
+                (None, "\n"),
+            ]
+        );
+        assert_eq!(
+            line.lines()[0].content,
+            "Ask *Stacy* about ~cargo publish~ and [[*Tasks][the tasks]]\n"
+        );
+
+        let Block::RichText(plain) = &launch.children[1] else {
+            panic!("Expected rich text");
+        };
+        assert_eq!(plain.children.len(), 1);
+
+        assert_eq!(render_document(document), document);
+    }
+
+    #[test]
+    fn split_headlines() {
+        let document = "* TODO [#A] Write the report :work:urgent:
+** DONE Call Stacy
+** TODOS are not keywords
+** [#B] Prioritised
+** Meeting at 10:30 :not:tags
+** :tagged:
+";
+
+        let blocks = Parser::new(document).parse();
+        let category = match &blocks[0] {
+            Block::Category(category) => category,
+            _ => panic!("Expected a category"),
+        };
+
+        assert_eq!(category.name, "TODO [#A] Write the report :work:urgent:\n");
+        assert_eq!(
+            category.headline,
+            Headline {
+                todo_keyword: Some("TODO"),
+                priority: Some("A"),
+                title: "Write the report",
+                tags: vec!["work", "urgent"],
+            }
+        );
+
+        let headlines: Vec<&Headline> = category
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Block::Category(child) => Some(&child.headline),
+                _ => None,
+            })
+            .collect();
+
+        let headline = |todo_keyword, priority, title, tags| Headline {
+            todo_keyword,
+            priority,
+            title,
+            tags,
+        };
+
+        assert_eq!(
+            headlines,
+            vec![
+                &headline(Some("DONE"), None, "Call Stacy", vec![]),
+                &headline(None, None, "TODOS are not keywords", vec![]),
+                &headline(None, Some("B"), "Prioritised", vec![]),
+                &headline(None, None, "Meeting at 10:30 :not:tags", vec![]),
+                &headline(None, None, "", vec!["tagged"]),
+            ]
+        );
+
+        assert_eq!(render_document(document), document);
+    }
+
+    #[test]
+    fn tokens_use_byte_offsets() {
+        let document = "* Café 😊 ３. done";
+        let tokens = Tokenizer::new(document).get_tokens();
+
+        for token in &tokens {
+            assert!(document.is_char_boundary(token.lexeme_start));
+            assert!(document.is_char_boundary(token.lexeme_end));
+        }
+
+        let lexemes: Vec<&str> = tokens
+            .iter()
+            .map(|token| &document[token.lexeme_start..token.lexeme_end])
+            .collect();
+
+        assert_eq!(
+            lexemes,
+            vec!["*", " ", "Café", " ", "😊", " ", "３.", " ", "done"]
+        );
+        assert!(matches!(tokens[6].tok_type, TokenType::NumberPeriod));
+    }
+
+    #[test]
+    fn round_trip_multilingual_corpus() {
+        for file in ["test/org/multilingual.org", "test/org/cjk_and_emoji.org"] {
+            let document = fs::read_to_string(file).expect("Should be able to read test file");
+
+            assert_eq!(render_document(&document), document, "{} did not round trip", file);
+        }
+    }
+
+    #[test]
+    fn tokenize_long_run_of_text() {
+        // A run of text with no separators used to recurse once per character
+        let document = format!("* {}\n", "長".repeat(1_000_000));
+        let tokens = Tokenizer::new(&document).get_tokens();
+
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[2].tok_type, TokenType::Text));
+        assert_eq!(tokens[2].lexeme_end - tokens[2].lexeme_start, 3_000_000);
+    }
+
+    #[test]
+    fn tokenize_long_run_of_digits() {
+        // Each digit used to rescan the rest of the run for a period
+        let document = format!("* {}\n{}.", "7".repeat(1_000_000), "8".repeat(1_000_000));
+        let tokens = Tokenizer::new(&document).get_tokens();
+
+        assert_eq!(tokens.len(), 5);
+        assert!(matches!(tokens[2].tok_type, TokenType::Text));
+        assert_eq!(tokens[2].lexeme_end - tokens[2].lexeme_start, 1_000_000);
+        assert!(matches!(tokens[4].tok_type, TokenType::NumberPeriod));
+        assert_eq!(tokens[4].lexeme_end - tokens[4].lexeme_start, 1_000_001);
+    }
+
+    #[test]
+    fn tokens_are_produced_lazily() {
+        let document = "* Headline\nBody";
+        let mut tokenizer = Tokenizer::new(document);
+
+        assert!(matches!(tokenizer.next().map(|t| t.tok_type), Some(TokenType::Asterisk)));
+        assert!(matches!(tokenizer.next().map(|t| t.tok_type), Some(TokenType::Space)));
+        assert_eq!(tokenizer.count(), 3);
+    }
+
+    /// Summarises blocks as (level, name) for categories and the joined
+    /// lines of each rich text block, which is easier to compare than AIR
+    fn outline(blocks: &[Block]) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for block in blocks {
+            match block {
+                Block::Category(category) => {
+                    lines.push(format!("{} {:?}", category.level, category.name));
+                    lines.extend(outline(&category.children).into_iter().map(|l| "  ".to_string() + &l));
+                }
+                Block::RichText(rich_text) => {
+                    let text: String = rich_text
+                        .children
+                        .iter()
+                        .filter_map(|child| match child {
+                            Block::Text(text) => Some(text.content),
+                            _ => None,
+                        })
+                        .collect();
+                    lines.push(format!("{:?}", text));
+                }
+                Block::Text(text) => lines.push(format!("{:?}", text.content)),
+                Block::Property(property) => lines.push(format!(":{}: {}", property.key, property.value)),
+                Block::Drawer(drawer) => lines.extend(
+                    drawer
+                        .properties
+                        .iter()
+                        .map(|property| format!(":{}: {}", property.key, property.value)),
+                ),
+                Block::List(list) => lines.extend(outline(&list.children)),
+                Block::ListItem(item) => {
+                    lines.push(format!("{:?}", format!("{}{}", item.bullet, item.content)));
+                    lines.extend(outline(&item.children));
+                }
+                Block::Table(table) => {
+                    lines.push(format!("{:?}", render_to_org(Block::Table(table.clone()))))
+                }
+                Block::Verbatim(verbatim) => {
+                    lines.push(format!("{:?}", render_to_org(Block::Verbatim(verbatim.clone()))))
+                }
+                Block::Keyword(keyword) => lines.push(format!("#+{}: {}", keyword.key, keyword.value)),
+            }
+        }
+
+        lines
+    }
+
+    #[test]
+    fn parse_markdown_headings() {
+        let document = "Preamble
+# Project
+Some *notes*
+spanning two lines
+
+## Tasks
+### Deep #
+Setext heading
+==============
+Under the setext heading
+
+Second level
+---
+####### Not a heading
+#hashtag
+
+  Setext heading
+  over two lines
+---
+";
+
+        let bump = Bump::new();
+        assert_eq!(
+            outline(&MarkdownParser::new(&bump, document).parse()),
+            vec![
+                "\"Preamble\\n\"",
+                "1 \"Project\\n\"",
+                "  \"Some *notes*\\nspanning two lines\\n\"",
+                "  \"\\n\"",
+                "  2 \"Tasks\\n\"",
+                "    3 \"Deep #\\n\"",
+                "1 \"Setext heading\\n\"",
+                "  \"Under the setext heading\\n\"",
+                "  \"\\n\"",
+                "  2 \"Second level\\n\"",
+                "    \"####### Not a heading\\n#hashtag\\n\"",
+                "    \"\\n\"",
+                "  2 \"Setext heading over two lines\\n\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_markdown_lists_and_code() {
+        let document = "# Notes
+- first item
+  continues here
+- second item
+1. ordered
+* * *
+```rust
+# not a heading
+fn main() {}
+```
+```
+plain
+```
+~~~
+unclosed
+";
+
+        let bump = Bump::new();
+        assert_eq!(
+            outline(&MarkdownParser::new(&bump, document).parse()),
+            vec![
+                "1 \"Notes\\n\"",
+                "  \"- first item\\n  continues here\\n\"",
+                "  \"- second item\\n\"",
+                "  \"1. ordered\\n\"",
+                "  \"* * *\\n\"",
+                "  \"#+BEGIN_SRC rust\\n# not a heading\\nfn main() {}\\n#+END_SRC\\n\"",
+                "  \"#+BEGIN_EXAMPLE\\nplain\\n#+END_EXAMPLE\\n\"",
+                "  \"~~~\\nunclosed\\n\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_markdown_nested_lists() {
+        let document = "- [ ] Groceries
+  + [x] Milk
+  + [-] Bread
+    1. Rye
+- Laundry
+2) numbered
+";
+
+        let bump = Bump::new();
+        let blocks = MarkdownParser::new(&bump, document).parse();
+
+        let Block::List(list) = &blocks[0] else {
+            panic!("Expected a list");
+        };
+        assert_eq!((list.kind, list.children.len()), (ListKind::Unordered, 2));
+
+        let Block::ListItem(groceries) = &list.children[0] else {
+            panic!("Expected a list item");
+        };
+        assert_eq!(
+            (groceries.bullet, groceries.checkbox_state(), groceries.content),
+            ("- ", Some(Checkbox::Unchecked), "Groceries\n")
+        );
+
+        let Block::List(nested) = &groceries.children[0] else {
+            panic!("Expected a nested list");
+        };
+        let checkboxes: Vec<Option<Checkbox>> = nested
+            .children
+            .iter()
+            .map(|child| match child {
+                Block::ListItem(item) => item.checkbox_state(),
+                _ => panic!("Expected a list item"),
+            })
+            .collect();
+        assert_eq!(checkboxes, vec![Some(Checkbox::Checked), Some(Checkbox::Partial)]);
+
+        let Block::ListItem(bread) = &nested.children[1] else {
+            panic!("Expected a list item");
+        };
+        let Block::List(rye) = &bread.children[0] else {
+            panic!("Expected a nested list");
+        };
+        assert_eq!(rye.kind, ListKind::Ordered);
+
+        let Block::List(numbered) = &blocks[1] else {
+            panic!("Expected an ordered list");
+        };
+        assert_eq!(numbered.kind, ListKind::Ordered);
+
+        assert_eq!(
+            render_to_org(Block::Category(CategoryBlock::new("", 0, blocks))),
+            document
+        );
+    }
+
+    #[test]
+    fn store_markdown_notes() {
+        let document = "# TODO Write the report\nDue friday\n\n## Sources\n- The paper\n";
+
+        let bump = Bump::new();
+        let mut db = AmbleDB::new(":memory:").unwrap();
+        let id = db
+            .write_top_level_category(&CategoryBlock::new(
+                "Report",
+                0,
+                MarkdownParser::new(&bump, document).parse(),
+            ))
+            .expect("Should be able to save markdown notes")
+            .category_id;
+
+        let matrix = DbBlockMatrix::new(&db.connection, id).unwrap();
+        let flat_blocks = matrix.produce_flat_db_block_vec().unwrap();
+        let category = matrix.form_category_block_tree(&flat_blocks).unwrap();
+
+        assert_eq!(
+            render_to_org(Block::Category(category)),
+            "* TODO Write the report\nDue friday\n\n** Sources\n- The paper\n"
+        );
+    }
+
+    #[test]
+    fn parse_json_documents() {
+        let document = r#"{
+            "Website": {"status": "TODO", "owner": "Stacy", "estimate": 1.0},
+            "Laundry": {"status": "DONE", "owner": null},
+            "tags": ["home", 3, true, {"nested": []}]
+        }"#;
+
+        let bump = Bump::new();
+        let blocks = parse_json(&bump, document).expect("Should be able to parse JSON");
+
+        assert_eq!(
+            outline(&blocks),
+            vec![
+                "1 \"Website\\n\"",
+                "  :status: TODO",
+                "  :owner: Stacy",
+                "  :estimate: 1.0",
+                "1 \"Laundry\\n\"",
+                "  :status: DONE",
+                "  :owner: null",
+                "1 \"tags\\n\"",
+                "  \"home\\n\"",
+                "  \"3\\n\"",
+                "  \"true\\n\"",
+                "  2 \"3\\n\"",
+                "    3 \"nested\\n\"",
+            ]
+        );
+
+        assert!(parse_json(&bump, "{\"unterminated\": ").is_err());
+    }
+
+    #[test]
+    fn json_fields_survive_org() {
+        let document = r#"{
+            "Contact": {
+                "first name": "Stacy",
+                "a:b": "colon",
+                "properties": "drawer",
+                "address": "1 Main St\nSpringfield",
+                "email": "stacy@example.com"
+            },
+            "notes": ["one line", "two\nlines"]
+        }"#;
+
+        let bump = Bump::new();
+        let blocks = parse_json(&bump, document).expect("Should be able to parse JSON");
+        let expected = vec![
+            "1 \"Contact\\n\"",
+            "  :email: stacy@example.com",
+            "  2 \"first name\\n\"",
+            "    \"Stacy\\n\"",
+            "  2 \"a:b\\n\"",
+            "    \"colon\\n\"",
+            "  2 \"properties\\n\"",
+            "    \"drawer\\n\"",
+            "  2 \"address\\n\"",
+            "    \"1 Main St\\n\"",
+            "    \"Springfield\\n\"",
+            "1 \"notes\\n\"",
+            "  \"one line\\n\"",
+            "  \"two\\n\"",
+            "  \"lines\\n\"",
+        ];
+        assert_eq!(outline(&blocks), expected);
+
+        let org = render_to_org(Block::Category(CategoryBlock::new("", 0, blocks)));
+        assert_eq!(outline(&Parser::new(&org).parse()), expected);
+    }
+
+    #[test]
+    fn fold_over_json() {
+        let document = r#"{
+            "Website": {"status": "TODO", "owner": "Stacy"},
+            "Laundry": {"status": "DONE", "owner": "Sam"}
+        }"#;
+        let fold_document = r#"* FOLD
+** FROM
+*** project
+    :status: "TODO"
+    :owner: owner
+** INTO
+*** owner
+    :project: project
+"#;
+
+        let bump = Bump::new();
+        let blocks = parse_json(&bump, document).unwrap();
+
+        let fold_blocks = Parser::new(fold_document).parse();
+        let fold = match &fold_blocks[0] {
+            Block::Category(category) => Fold::parse(category).expect("Fold should parse"),
+            _ => panic!("Fold document should start with a category"),
+        };
+
+        let rendered: Vec<String> = fold
+            .evaluate(&bump, &blocks)
+            .blocks
+            .into_iter()
+            .map(render_to_json)
+            .collect();
+
+        assert_eq!(rendered.len(), 1);
+        assert!(rendered[0].contains(r#""name":"Stacy""#));
+        assert!(rendered[0].contains(r#""key":"project","type":"property","value":"Website""#));
+    }
+}
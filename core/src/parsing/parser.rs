@@ -1,5 +1,8 @@
 use super::lexer::{Token, TokenType, Tokenizer};
-use crate::air::{Block, CategoryBlock, RichTextBlock, TextBlock};
+use crate::air::{
+    Block, CategoryBlock, Checkbox, DrawerBlock, KeywordBlock, ListBlock, ListItemBlock, ListKind,
+    PropertyBlock, RichTextBlock, TableBlock, TableCell, TableRow, TextBlock, VerbatimBlock,
+};
 
 pub struct Parser<'a> {
     tokens: Vec<Token>,
@@ -7,7 +10,7 @@ pub struct Parser<'a> {
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(document: &str) -> Parser {
+    pub fn new(document: &'a str) -> Parser<'a> {
         let tokens = Tokenizer::new(document).get_tokens();
         Parser { tokens, document }
     }
@@ -43,7 +46,7 @@ impl<'a> Parser<'a> {
         &self,
         token_index: usize,
         level: Option<usize>,
-    ) -> Option<(CategoryBlock, usize)> {
+    ) -> Option<(CategoryBlock<'a>, usize)> {
         let mut index = token_index;
 
         let lexeme_start = self.tokens[index].lexeme_start;
@@ -61,12 +64,11 @@ impl<'a> Parser<'a> {
             }
         }
 
-        let mut block = CategoryBlock {
-            id: None,
-            level: level.unwrap_or_default(),
-            name: &self.document[lexeme_start..lexeme_end],
-            children: Vec::new(),
-        };
+        let mut block = CategoryBlock::new(
+            &self.document[lexeme_start..lexeme_end],
+            level.unwrap_or_default(),
+            Vec::new(),
+        );
 
         while let Some((new_block, new_index)) = self.parse_next_block(index, level) {
             block.children.push(new_block);
@@ -76,11 +78,9 @@ impl<'a> Parser<'a> {
         Some((block, index))
     }
 
-    fn parse_rich_text_block(&self, token_index: usize) -> Option<(RichTextBlock, usize)> {
-        let mut block = RichTextBlock {
-            children: Vec::new(),
-        };
-
+    /// Parses a line of text, split into spans of plain text, emphasis and
+    /// links
+    fn parse_rich_text_block(&self, token_index: usize) -> Option<(RichTextBlock<'a>, usize)> {
         let mut index = token_index;
 
         let lexeme_start = self.tokens[index].lexeme_start;
@@ -98,20 +98,407 @@ impl<'a> Parser<'a> {
             }
         }
 
-        let new_block = Block::Text(TextBlock {
-            content: &self.document[lexeme_start..lexeme_end],
-        });
-
-        block.children.push(new_block);
+        let block = RichTextBlock {
+            children: TextBlock::spans(None, &self.document[lexeme_start..lexeme_end])
+                .into_iter()
+                .map(Block::Text)
+                .collect(),
+        };
 
         Some((block, index))
     }
 
+    /// Determines if the line starting at `token_index` is a `:Key: value`
+    /// property line, returning the property outside of a drawer and the
+    /// index of the first token after the line
+    fn parse_property_line(&self, token_index: usize) -> Option<(PropertyBlock<'a>, usize)> {
+        let mut index = token_index;
+        let line_start = self.tokens.get(index)?.lexeme_start;
+
+        // Properties may be indented under their headline
+        while index < self.tokens.len() {
+            match self.tokens[index].tok_type {
+                TokenType::Space | TokenType::Tab => index += 1,
+                _ => break,
+            }
+        }
+
+        let colon = self.tokens.get(index)?;
+        match colon.tok_type {
+            TokenType::Colon => index += 1,
+            _ => return None,
+        }
+        let indent = &self.document[line_start..colon.lexeme_start];
+
+        let key_token = self.tokens.get(index)?;
+        match key_token.tok_type {
+            TokenType::Text => index += 1,
+            _ => return None,
+        }
+
+        match self.tokens.get(index)?.tok_type {
+            TokenType::Colon => index += 1,
+            _ => return None,
+        }
+
+        let value_start = match self.tokens.get(index) {
+            None => self.document.len(),
+            Some(token) => match token.tok_type {
+                TokenType::Space | TokenType::Tab | TokenType::LineBreak => token.lexeme_start,
+                _ => return None,
+            },
+        };
+        let mut value_end = value_start;
+
+        while index < self.tokens.len() {
+            let token = &self.tokens[index];
+            index += 1;
+            match token.tok_type {
+                TokenType::LineBreak => break,
+                _ => value_end = token.lexeme_end,
+            }
+        }
+
+        let raw_value = &self.document[value_start..value_end];
+        let separator = &raw_value[..raw_value.len() - raw_value.trim_start().len()];
+        let value = raw_value[separator.len()..].trim_end();
+
+        let property = PropertyBlock {
+            indent,
+            separator,
+            trailing: &raw_value[separator.len() + value.len()..],
+            ..PropertyBlock::new(
+                &self.document[key_token.lexeme_start..key_token.lexeme_end],
+                value,
+            )
+        };
+
+        Some((property, index))
+    }
+
+    /// Determines if the line starting at `token_index` is a list item,
+    /// returning the width of its indentation, the kind of list it belongs
+    /// to and the index of the first token after its bullet. Bullets are
+    /// `-`, `+`, an indented `*`, or a number followed by `.` or `)`, and
+    /// must be followed by whitespace or the end of the line.
+    fn list_item_bullet(&self, token_index: usize) -> Option<(usize, ListKind, usize)> {
+        let mut index = token_index;
+
+        while let Some(TokenType::Space | TokenType::Tab) =
+            self.tokens.get(index).map(|token| &token.tok_type)
+        {
+            index += 1;
+        }
+
+        let indent = index - token_index;
+        let token = self.tokens.get(index)?;
+        let lexeme = &self.document[token.lexeme_start..token.lexeme_end];
+
+        let kind = match token.tok_type {
+            TokenType::Text if lexeme == "-" || lexeme == "+" => ListKind::Unordered,
+            // An unindented `*` starts a headline
+            TokenType::Asterisk if indent > 0 => ListKind::Unordered,
+            TokenType::NumberPeriod => ListKind::Ordered,
+            TokenType::Text
+                if lexeme.chars().all(|char| char.is_ascii_digit())
+                    && matches!(
+                        self.tokens.get(index + 1).map(|token| &token.tok_type),
+                        Some(TokenType::RParen)
+                    ) =>
+            {
+                index += 1;
+                ListKind::Ordered
+            }
+            _ => return None,
+        };
+
+        index += 1;
+
+        match self.tokens.get(index).map(|token| &token.tok_type) {
+            None | Some(TokenType::LineBreak) => (),
+            Some(TokenType::Space | TokenType::Tab) => {
+                while let Some(TokenType::Space | TokenType::Tab) =
+                    self.tokens.get(index).map(|token| &token.tok_type)
+                {
+                    index += 1;
+                }
+            }
+            _ => return None,
+        }
+
+        Some((indent, kind, index))
+    }
+
+    /// Determines if a list item's content starts with a checkbox such as
+    /// `[ ]` or `[X]` followed by a space, returning it as written and the
+    /// index of the first token after that space
+    fn parse_checkbox(&self, token_index: usize) -> Option<(&'a str, usize)> {
+        let open = self.tokens.get(token_index)?;
+        let close = self.tokens.get(token_index + 2)?;
+
+        if !matches!(open.tok_type, TokenType::LBracket)
+            || !matches!(close.tok_type, TokenType::RBracket)
+            || !matches!(self.tokens.get(token_index + 3)?.tok_type, TokenType::Space)
+        {
+            return None;
+        }
+
+        let checkbox = &self.document[open.lexeme_start..close.lexeme_end];
+        Checkbox::parse(checkbox)?;
+
+        Some((checkbox, token_index + 4))
+    }
+
+    /// Parses a list item whose content starts at `content_index`, up to
+    /// the end of its line
+    fn parse_list_item(
+        &self,
+        token_index: usize,
+        content_index: usize,
+    ) -> (ListItemBlock<'a>, usize) {
+        let (checkbox, mut index) = match self.parse_checkbox(content_index) {
+            Some((checkbox, index)) => (Some(checkbox), index),
+            None => (None, content_index),
+        };
+
+        let token_start = |index: usize| {
+            self.tokens
+                .get(index)
+                .map_or(self.document.len(), |token| token.lexeme_start)
+        };
+
+        let bullet_start = token_start(token_index);
+        let bullet_end = token_start(content_index);
+        let content_start = token_start(index);
+        let mut content_end = content_start;
+
+        while index < self.tokens.len() {
+            let token = &self.tokens[index];
+            index += 1;
+            content_end = token.lexeme_end;
+
+            if let TokenType::LineBreak = token.tok_type {
+                break;
+            }
+        }
+
+        let item = ListItemBlock {
+            id: None,
+            bullet: &self.document[bullet_start..bullet_end],
+            checkbox,
+            content: &self.document[content_start..content_end],
+            children: Vec::new(),
+        };
+
+        (item, index)
+    }
+
+    /// Parses consecutive list items of the same kind and indentation into
+    /// a list. More deeply indented items form a list nested in the item
+    /// before them, and the list ends at any other line.
+    fn parse_list(&self, token_index: usize) -> Option<(ListBlock<'a>, usize)> {
+        let (indent, kind, _) = self.list_item_bullet(token_index)?;
+
+        let mut list = ListBlock {
+            id: None,
+            kind,
+            children: Vec::new(),
+        };
+
+        let mut index = token_index;
+
+        while let Some((item_indent, item_kind, content_index)) = self.list_item_bullet(index) {
+            if item_indent > indent {
+                let Some(Block::ListItem(item)) = list.children.last_mut() else {
+                    break;
+                };
+
+                let (nested, new_index) = self.parse_list(index)?;
+                item.children.push(Block::List(nested));
+                index = new_index;
+                continue;
+            }
+
+            if item_indent < indent || item_kind != kind {
+                break;
+            }
+
+            let (item, new_index) = self.parse_list_item(index, content_index);
+            list.children.push(Block::ListItem(item));
+            index = new_index;
+        }
+
+        Some((list, index))
+    }
+
+    /// Determines if the line starting at `token_index` is a table row,
+    /// which starts with `|` after any indentation. Returns the row and the
+    /// index of the first token after the line.
+    fn parse_table_row(&self, token_index: usize) -> Option<(TableRow<'a>, usize)> {
+        let mut index = token_index;
+
+        while let Some(TokenType::Space | TokenType::Tab) =
+            self.tokens.get(index).map(|token| &token.tok_type)
+        {
+            index += 1;
+        }
+
+        let bar = self.tokens.get(index)?;
+        let TokenType::Bar = bar.tok_type else {
+            return None;
+        };
+
+        let indent = &self.document[self.tokens[token_index].lexeme_start..bar.lexeme_start];
+        let mut line_end = bar.lexeme_end;
+
+        while index < self.tokens.len() {
+            let token = &self.tokens[index];
+            index += 1;
+
+            match token.tok_type {
+                TokenType::LineBreak => break,
+                _ => line_end = token.lexeme_end,
+            }
+        }
+
+        // Everything after the first bar, without the optional last bar
+        let line = self.document[bar.lexeme_end..line_end].trim_end();
+        let line = line.strip_suffix('|').unwrap_or(line);
+
+        let row = if line.starts_with('-') {
+            TableRow {
+                id: None,
+                indent,
+                rule: true,
+                cells: Vec::new(),
+            }
+        } else {
+            TableRow {
+                id: None,
+                indent,
+                rule: false,
+                cells: line
+                    .split('|')
+                    .map(|content| TableCell {
+                        id: None,
+                        content: content.trim(),
+                    })
+                    .collect(),
+            }
+        };
+
+        Some((row, index))
+    }
+
+    /// Parses consecutive table rows into a table
+    fn parse_table(&self, token_index: usize) -> Option<(TableBlock<'a>, usize)> {
+        let mut table = TableBlock {
+            id: None,
+            rows: Vec::new(),
+        };
+        let mut index = token_index;
+
+        while let Some((row, new_index)) = self.parse_table_row(index) {
+            table.rows.push(row);
+            index = new_index;
+        }
+
+        if table.rows.is_empty() {
+            return None;
+        }
+
+        Some((table, index))
+    }
+
+    /// Returns the line starting at byte offset `start`, without its `\n`,
+    /// and the offset of the line after it
+    fn line_at(&self, start: usize) -> (&'a str, usize) {
+        match self.document[start..].find('\n') {
+            Some(length) => (&self.document[start..start + length], start + length + 1),
+            None => (&self.document[start..], self.document.len()),
+        }
+    }
+
+    /// Returns the index of the first token starting at or after byte
+    /// offset `offset`
+    fn token_at(&self, offset: usize) -> usize {
+        self.tokens.partition_point(|token| token.lexeme_start < offset)
+    }
+
+    /// Parses a `#+BEGIN_KIND` ... `#+END_KIND` block, keeping every line up
+    /// to the matching end line as written. Returns None if the block is
+    /// never closed.
+    fn parse_verbatim_block(&self, token_index: usize) -> Option<(VerbatimBlock<'a>, usize)> {
+        let (begin, content_start) = self.line_at(self.tokens.get(token_index)?.lexeme_start);
+        let kind = VerbatimBlock::begin_kind(begin)?;
+
+        let mut line_start = content_start;
+
+        while line_start < self.document.len() {
+            let (line, next_line_start) = self.line_at(line_start);
+
+            if VerbatimBlock::is_end(line, kind) {
+                let block = VerbatimBlock {
+                    id: None,
+                    begin,
+                    content: &self.document[content_start..line_start],
+                    end: line,
+                    end_line_break: self.document[..next_line_start].ends_with('\n'),
+                };
+
+                return Some((block, self.token_at(next_line_start)));
+            }
+
+            line_start = next_line_start;
+        }
+
+        None
+    }
+
+    /// Determines if the line starting at `token_index` is a `#+KEY: value`
+    /// keyword line
+    fn parse_keyword(&self, token_index: usize) -> Option<(KeywordBlock<'a>, usize)> {
+        let (line, next_line_start) = self.line_at(self.tokens.get(token_index)?.lexeme_start);
+        let keyword = KeywordBlock::parse(line)?;
+
+        Some((keyword, self.token_at(next_line_start)))
+    }
+
+    /// Parses a :PROPERTIES: ... :END: drawer. A drawer that is never
+    /// closed ends at the first line that is not a property.
+    fn parse_property_drawer(&self, token_index: usize) -> Option<(DrawerBlock<'a>, usize)> {
+        let (opening, mut index) = self.parse_property_line(token_index)?;
+
+        if !opening.key.eq_ignore_ascii_case("PROPERTIES") {
+            return None;
+        }
+
+        let (begin, _) = self.line_at(self.tokens[token_index].lexeme_start);
+        let mut drawer = DrawerBlock {
+            begin,
+            end: None,
+            ..DrawerBlock::new(Vec::new())
+        };
+
+        while let Some((property, new_index)) = self.parse_property_line(index) {
+            if property.key.eq_ignore_ascii_case("END") {
+                drawer.end = Some(self.line_at(self.tokens[index].lexeme_start).0);
+                index = new_index;
+                break;
+            }
+
+            index = new_index;
+            drawer.properties.push(property);
+        }
+
+        Some((drawer, index))
+    }
+
     fn parse_next_block(
         &self,
         token_index: usize,
         category_level: Option<usize>,
-    ) -> Option<(Block, usize)> {
+    ) -> Option<(Block<'a>, usize)> {
         if token_index >= self.tokens.len() {
             return None;
         }
@@ -126,16 +513,26 @@ impl<'a> Parser<'a> {
             if let Some((category, new_index)) = self.parse_category_block(index, Some(level)) {
                 return Some((Block::Category(category), new_index));
             }
-        } else {
-            if let Some((rich_text, new_index)) = self.parse_rich_text_block(token_index) {
-                return Some((Block::RichText(rich_text), new_index));
-            }
+        } else if let Some((verbatim, new_index)) = self.parse_verbatim_block(token_index) {
+            return Some((Block::Verbatim(verbatim), new_index));
+        } else if let Some((keyword, new_index)) = self.parse_keyword(token_index) {
+            return Some((Block::Keyword(keyword), new_index));
+        } else if let Some((drawer, new_index)) = self.parse_property_drawer(token_index) {
+            return Some((Block::Drawer(drawer), new_index));
+        } else if let Some((property, new_index)) = self.parse_property_line(token_index) {
+            return Some((Block::Property(property), new_index));
+        } else if let Some((table, new_index)) = self.parse_table(token_index) {
+            return Some((Block::Table(table), new_index));
+        } else if let Some((list, new_index)) = self.parse_list(token_index) {
+            return Some((Block::List(list), new_index));
+        } else if let Some((rich_text, new_index)) = self.parse_rich_text_block(token_index) {
+            return Some((Block::RichText(rich_text), new_index));
         }
 
         None
     }
 
-    pub fn parse(&self) -> Vec<Block> {
+    pub fn parse(&self) -> Vec<Block<'a>> {
         let mut index = 0;
         let mut blocks = Vec::new();
 
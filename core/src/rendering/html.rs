@@ -0,0 +1,638 @@
+use std::borrow::Cow;
+
+use crate::{
+    Block, Checkbox, Headline, ListBlock, ListKind, Markup, TableBlock, TableRow, TextBlock,
+    VerbatimBlock,
+};
+
+use super::{link_href, slug};
+
+/// The deepest heading HTML supports. Deeper categories are rendered with
+/// this heading, though their sections still nest.
+const MAX_HEADING_LEVEL: usize = 6;
+
+/// Escapes text for use in HTML content and attribute values
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => out += "&amp;",
+            '<' => out += "&lt;",
+            '>' => out += "&gt;",
+            '"' => out += "&quot;",
+            '\'' => out += "&#39;",
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Schemes a link may point at. Anything else, such as `javascript:`, could
+/// run script in the exported page.
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto", "file"];
+
+/// Determines if a link to `target` is safe to follow: either relative, or
+/// using one of `ALLOWED_SCHEMES`. Browsers ignore tabs and line breaks in
+/// URLs and leading whitespace and control characters, so those are
+/// skipped before looking for the scheme.
+fn is_allowed_target(target: &str) -> bool {
+    let target: String = target
+        .trim_start_matches(|c: char| c.is_ascii_control() || c == ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+
+    let scheme_end = target.find(':');
+    let path_start = target.find(['/', '?', '#']);
+
+    match (scheme_end, path_start) {
+        (None, _) => true,
+        (Some(colon), Some(path)) if path < colon => true,
+        (Some(colon), _) => {
+            let scheme = target[..colon].to_ascii_lowercase();
+            ALLOWED_SCHEMES.contains(&scheme.as_str())
+        }
+    }
+}
+
+/// Renders a link as an anchor, or as its description alone when its
+/// target is not allowed
+fn link(target: &str, description: &str) -> String {
+    if !is_allowed_target(target) {
+        return escape_html(description);
+    }
+
+    format!(
+        "<a href=\"{}\">{}</a>",
+        escape_html(target),
+        escape_html(description)
+    )
+}
+
+/// Renders consecutive lines of text as paragraphs, which are separated by
+/// blank lines
+fn render_lines(lines: &[Cow<str>]) -> String {
+    let mut out = String::new();
+
+    for paragraph in lines.split(|line| line.trim().is_empty()) {
+        if paragraph.is_empty() {
+            continue;
+        }
+
+        out += "<p>";
+        out += &paragraph
+            .iter()
+            .map(|line| render_inline(line.trim()))
+            .collect::<Vec<String>>()
+            .join("\n");
+        out += "</p>\n";
+    }
+
+    out
+}
+
+/// Renders a table, with its header, if it has one, as the table head and
+/// the rows below it as the body. Rules are left out.
+fn render_table(table: &TableBlock) -> String {
+    let render_row = |row: &TableRow, cell_tag: &str| -> String {
+        let mut out = "<tr>".to_string();
+        for cell in &row.cells {
+            out += &format!("<{}>{}</{}>", cell_tag, render_inline(cell.content), cell_tag);
+        }
+        out += "</tr>\n";
+        out
+    };
+
+    let mut out = "<table>\n".to_string();
+
+    if let Some(header) = table.header() {
+        out += "<thead>\n";
+        out += &render_row(header, "th");
+        out += "</thead>\n";
+    }
+
+    out += "<tbody>\n";
+    for row in table.body() {
+        out += &render_row(row, "td");
+    }
+    out += "</tbody>\n</table>\n";
+    out
+}
+
+/// Renders a headline's title, with its TODO keyword, priority and tags
+/// marked up apart from it
+fn render_headline(headline: &Headline) -> String {
+    let mut out = String::new();
+
+    if let Some(todo_keyword) = headline.todo_keyword {
+        out += &format!("<span class=\"todo\">{}</span> ", escape_html(todo_keyword));
+    }
+    if let Some(priority) = headline.priority {
+        out += &format!("<span class=\"priority\">[#{}]</span> ", escape_html(priority));
+    }
+
+    out += &render_inline(headline.title.trim());
+
+    if !headline.tags.is_empty() {
+        out += " <span class=\"tags\">";
+        for tag in &headline.tags {
+            out += &format!("<span class=\"tag\">{}</span>", escape_html(tag));
+        }
+        out += "</span>";
+    }
+
+    out
+}
+
+/// Renders gathered lines of text
+fn flush_lines(out: &mut String, lines: &mut Vec<Cow<str>>) {
+    if !lines.is_empty() {
+        *out += &render_lines(lines);
+        lines.clear();
+    }
+}
+
+/// Renders gathered properties as a definition list
+fn flush_properties(out: &mut String, properties: &mut Vec<(&str, &str)>) {
+    if properties.is_empty() {
+        return;
+    }
+
+    *out += "<dl>\n";
+    for (key, value) in properties.drain(..) {
+        *out += &format!(
+            "<dt>{}</dt><dd>{}</dd>\n",
+            escape_html(key),
+            render_inline(value)
+        );
+    }
+    *out += "</dl>\n";
+}
+
+/// Renders a list, with the lists nested in each item inside that item
+fn render_list(block: Block) -> String {
+    let Block::List(list_block) = block else {
+        return String::new();
+    };
+
+    let tag = match list_block.kind {
+        ListKind::Ordered => "ol",
+        ListKind::Unordered => "ul",
+    };
+
+    let mut out = format!("<{}>\n", tag);
+
+    for child in list_block.children {
+        let Block::ListItem(item) = child else {
+            continue;
+        };
+
+        out += "<li>";
+        match item.checkbox_state() {
+            Some(Checkbox::Checked) => out += "<input type=\"checkbox\" disabled checked> ",
+            Some(_) => out += "<input type=\"checkbox\" disabled> ",
+            None => (),
+        }
+        out += &render_inline(item.content.trim_end());
+
+        if !item.children.is_empty() {
+            out += "\n";
+            for nested in item.children {
+                out += &render_list(nested);
+            }
+        }
+
+        out += "</li>\n";
+    }
+
+    out += &format!("</{}>\n", tag);
+    out
+}
+
+/// Renders a line of text, escaping it, turning links into anchors and
+/// emphasis into the matching elements
+fn render_inline(text: &str) -> String {
+    let mut out = String::new();
+
+    for span in TextBlock::spans(None, text) {
+        let tag = match span.markup {
+            None => {
+                out += &escape_html(span.content);
+                continue;
+            }
+            Some(Markup::Link) => {
+                match span.link() {
+                    Some(found) => out += &link(&link_href(&found.target), span.text()),
+                    None => out += &escape_html(span.content),
+                }
+                continue;
+            }
+            Some(Markup::Bold) => "strong",
+            Some(Markup::Italic) => "em",
+            Some(Markup::Underline) => "u",
+            Some(Markup::Verbatim) | Some(Markup::Code) => "code",
+            Some(Markup::StrikeThrough) => "del",
+        };
+
+        out += &format!("<{}>{}</{}>", tag, escape_html(span.text()), tag);
+    }
+
+    out
+}
+
+/// Renders a verbatim block as preformatted code, classed with the language
+/// of a source block
+fn render_verbatim_block(verbatim: &VerbatimBlock) -> String {
+    let class = verbatim
+        .language()
+        .map(|language| format!(" class=\"language-{}\"", escape_html(language)))
+        .unwrap_or_default();
+
+    format!("<pre><code{}>{}</code></pre>\n", class, escape_html(verbatim.content))
+}
+
+/// Renders the children of a category, gathering consecutive lines of text
+/// and consecutive properties so they can be grouped
+fn render_children(children: Vec<Block>) -> String {
+    let mut out = String::new();
+    let mut lines: Vec<Cow<str>> = Vec::new();
+    let mut properties: Vec<(&str, &str)> = Vec::new();
+
+    for block in children {
+        match block {
+            Block::RichText(rich_text_block) => {
+                flush_properties(&mut out, &mut properties);
+                lines.extend(rich_text_block.lines().into_iter().map(|line| line.content));
+            }
+            Block::Text(text_block) => {
+                flush_properties(&mut out, &mut properties);
+                lines.push(Cow::Borrowed(text_block.content));
+            }
+            Block::Property(property_block) => {
+                flush_lines(&mut out, &mut lines);
+                properties.push((property_block.key, property_block.value));
+            }
+            Block::Drawer(drawer_block) => {
+                flush_lines(&mut out, &mut lines);
+                properties.extend(drawer_block.properties.iter().map(|p| (p.key, p.value)));
+            }
+            Block::Category(_) => {
+                flush_lines(&mut out, &mut lines);
+                flush_properties(&mut out, &mut properties);
+                out += &render_to_html(block);
+            }
+            Block::List(_) => {
+                flush_lines(&mut out, &mut lines);
+                flush_properties(&mut out, &mut properties);
+                out += &render_list(block);
+            }
+            Block::ListItem(_) => (),
+            Block::Table(table_block) => {
+                flush_lines(&mut out, &mut lines);
+                flush_properties(&mut out, &mut properties);
+                out += &render_table(&table_block);
+            }
+            Block::Verbatim(verbatim_block) => {
+                flush_lines(&mut out, &mut lines);
+                flush_properties(&mut out, &mut properties);
+                out += &render_verbatim_block(&verbatim_block);
+            }
+            Block::Keyword(_) => (),
+        }
+    }
+
+    flush_lines(&mut out, &mut lines);
+    flush_properties(&mut out, &mut properties);
+    out
+}
+
+/// Renders a block as semantic HTML. Categories become nested `<section>`
+/// elements headed by `<h1>` to `<h6>` depending on their level, holding
+/// the headline's title with its TODO keyword, priority and tags in spans
+/// of their own. Headings are anchored by their title, and sections by their `ID` or `CUSTOM_ID`
+/// property, which is where links to headlines and ids point. Text is
+/// grouped into paragraphs, lists and tables keep their structure,
+/// properties become a definition list and verbatim blocks become `<pre>`
+/// code. Keywords are left out. All text is escaped. As with org, a
+/// category at level 0 is a document root whose name is not rendered.
+pub fn render_to_html(block: Block) -> String {
+    let mut out: String = "".to_string();
+
+    match block {
+        Block::Category(category_block) => {
+            if category_block.level > 0 {
+                let heading = category_block.level.min(MAX_HEADING_LEVEL);
+                let id = category_block
+                    .properties()
+                    .find(|p| {
+                        p.key.eq_ignore_ascii_case("ID") || p.key.eq_ignore_ascii_case("CUSTOM_ID")
+                    })
+                    .map(|p| p.value.trim());
+
+                match id {
+                    Some(id) => out += &format!("<section id=\"{}\">\n", escape_html(id)),
+                    None => out += "<section>\n",
+                }
+                out += &format!(
+                    "<h{} id=\"{}\">{}</h{}>\n",
+                    heading,
+                    escape_html(&slug(category_block.headline.title)),
+                    render_headline(&category_block.headline),
+                    heading
+                );
+                out += &render_children(category_block.children);
+                out += "</section>\n";
+            } else {
+                out += &render_children(category_block.children);
+            }
+        }
+        Block::RichText(_)
+        | Block::Text(_)
+        | Block::Property(_)
+        | Block::Drawer(_)
+        | Block::List(_)
+        | Block::Table(_)
+        | Block::Verbatim(_)
+        | Block::Keyword(_) => {
+            out += &render_children(vec![block]);
+        }
+        Block::ListItem(_) => {
+            out += &render_list(Block::List(ListBlock {
+                id: None,
+                kind: ListKind::Unordered,
+                children: vec![block],
+            }));
+        }
+    }
+
+    out
+}
+
+/// Renders blocks, such as a category or the results of a fold, as a
+/// standalone HTML page
+pub fn render_to_html_page(title: &str, blocks: Vec<Block>) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+        escape_html(title)
+    );
+
+    out += &render_children(blocks);
+    out += "</body>\n</html>\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+
+    use crate::{Block, CategoryBlock, Fold, Parser, RichTextBlock, TextBlock};
+
+    use super::{render_to_html, render_to_html_page};
+
+    fn render_document(document: &str) -> String {
+        render_to_html(Block::Category(CategoryBlock::new("", 0, Parser::new(document).parse())))
+    }
+
+    #[test]
+    fn render_sections_and_escape_text() {
+        let document = "* Project <Website>
+:Owner: Stacy & Sam
+A paragraph
+over two lines
+
+Another paragraph with [[https://example.com?a=1&b=2][a link]]
+** Tasks
+******* Too deep
+";
+
+        assert_eq!(
+            render_document(document),
+            "<section>
+<h1 id=\"project-website\">Project &lt;Website&gt;</h1>
+<dl>
+<dt>Owner</dt><dd>Stacy &amp; Sam</dd>
+</dl>
+<p>A paragraph
+over two lines</p>
+<p>Another paragraph with <a href=\"https://example.com?a=1&amp;b=2\">a link</a></p>
+<section>
+<h2 id=\"tasks\">Tasks</h2>
+<section>
+<h6 id=\"too-deep\">Too deep</h6>
+</section>
+</section>
+</section>
+"
+        );
+    }
+
+    #[test]
+    fn render_lists_and_tables() {
+        let document = "- first
+- [[https://example.com][second]]
+1. one
+2. two
+| Name | Owner |
+|------+-------|
+| Site | <Stacy> |
+";
+
+        assert_eq!(
+            render_document(document),
+            "<ul>
+<li>first</li>
+<li><a href=\"https://example.com\">second</a></li>
+</ul>
+<ol>
+<li>one</li>
+<li>two</li>
+</ol>
+<table>
+<thead>
+<tr><th>Name</th><th>Owner</th></tr>
+</thead>
+<tbody>
+<tr><td>Site</td><td>&lt;Stacy&gt;</td></tr>
+</tbody>
+</table>
+"
+        );
+    }
+
+    #[test]
+    fn render_headline_parts_and_table_rules() {
+        let document = "* TODO [#A] Ship *it* :work:urgent:
+| Name | Owner |
+|------+-------|
+| Site | Stacy |
+|------+-------|
+| Copy | Sam |
+|------+-------|
+| Last | row |
+";
+
+        assert_eq!(
+            render_document(document),
+            "<section>
+<h1 id=\"ship-it\"><span class=\"todo\">TODO</span> <span class=\"priority\">[#A]</span> \
+Ship <strong>it</strong> <span class=\"tags\"><span class=\"tag\">work</span>\
+<span class=\"tag\">urgent</span></span></h1>
+<table>
+<thead>
+<tr><th>Name</th><th>Owner</th></tr>
+</thead>
+<tbody>
+<tr><td>Site</td><td>Stacy</td></tr>
+<tr><td>Copy</td><td>Sam</td></tr>
+<tr><td>Last</td><td>row</td></tr>
+</tbody>
+</table>
+</section>
+"
+        );
+    }
+
+    #[test]
+    fn render_text_lines_as_paragraphs() {
+        let lines = ["1) counted\n", "| not | a table |\n", "see [x](y)\n", "\n", "- last\n"];
+        let rich_text = RichTextBlock {
+            children: lines
+                .iter()
+                .flat_map(|line| TextBlock::spans(None, line))
+                .map(Block::Text)
+                .collect(),
+        };
+
+        assert_eq!(
+            render_to_html(Block::RichText(rich_text)),
+            "<p>1) counted\n| not | a table |\nsee [x](y)</p>\n<p>- last</p>\n"
+        );
+    }
+
+    #[test]
+    fn render_nested_lists_with_checkboxes() {
+        let document = "- [X] Groceries
+  1. [ ] Milk
+  2. Bread
+- Laundry
+";
+
+        assert_eq!(
+            render_document(document),
+            "<ul>
+<li><input type=\"checkbox\" disabled checked> Groceries
+<ol>
+<li><input type=\"checkbox\" disabled> Milk</li>
+<li>Bread</li>
+</ol>
+</li>
+<li>Laundry</li>
+</ul>
+"
+        );
+    }
+
+    #[test]
+    fn render_inline_markup() {
+        let document = "*Bold* and /italic/, _underlined_ +gone+ =a<b= ~code~ [[*Tasks][tasks]]
+- a ~list~ item
+";
+
+        assert_eq!(
+            render_document(document),
+            "<p><strong>Bold</strong> and <em>italic</em>, <u>underlined</u> <del>gone</del> \
+<code>a&lt;b</code> <code>code</code> <a href=\"#tasks\">tasks</a></p>
+<ul>
+<li>a <code>list</code> item</li>
+</ul>
+"
+        );
+    }
+
+    #[test]
+    fn render_unsafe_links_as_text() {
+        let document = "[[javascript:alert(1)][click]] [[ JavaScript:alert(1)][caps]] \
+[[java\tscript:alert(1)][tab]] [[data:text/html,hi][data]] [[file:notes/a:b.html][relative]] \
+[[mailto:sam@example.com][mail]]\n";
+
+        assert_eq!(
+            render_document(document),
+            "<p>click caps <a href=\"#java-scriptalert1\">tab</a> data \
+<a href=\"notes/a:b.html\">relative</a> \
+<a href=\"mailto:sam@example.com\">mail</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn link_to_headline_and_id_anchors() {
+        let document = "* Big Plan
+:PROPERTIES:
+:ID: d775182b
+:END:
+See [[*Big Plan]], [[id:d775182b][by id]] and [[file:notes.org::*Big Plan][notes]]
+";
+
+        assert_eq!(
+            render_document(document),
+            "<section id=\"d775182b\">
+<h1 id=\"big-plan\">Big Plan</h1>
+<dl>
+<dt>ID</dt><dd>d775182b</dd>
+</dl>
+<p>See <a href=\"#big-plan\">*Big Plan</a>, <a href=\"#d775182b\">by id</a> and \
+<a href=\"notes.org#big-plan\">notes</a></p>
+</section>
+"
+        );
+    }
+
+    #[test]
+    fn render_verbatim_blocks_as_code() {
+        let document = "#+TITLE: Snippets
+#+BEGIN_SRC rust
+let ok = a < b && [[c]];
+#+END_SRC
+#+BEGIN_EXAMPLE
+as written
+#+END_EXAMPLE
+";
+
+        assert_eq!(
+            render_document(document),
+            "<pre><code class=\"language-rust\">let ok = a &lt; b &amp;&amp; [[c]];
+</code></pre>
+<pre><code>as written
+</code></pre>
+"
+        );
+    }
+
+    #[test]
+    fn render_fold_result_page() {
+        let document = r#"* TODO Design homepage layout
+* TODO Organize team-building event
+* FOLD
+** FROM
+*** "TODO" title
+** INTO
+*** "DONE" title"#;
+
+        let blocks = Parser::new(document).parse();
+        let fold = match &blocks[2] {
+            Block::Category(category) => Fold::parse(category).expect("Fold should parse"),
+            _ => panic!("Expected a fold"),
+        };
+
+        let bump = Bump::new();
+        let page = render_to_html_page("Done & dusted", fold.evaluate(&bump, &blocks).blocks);
+
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<title>Done &amp; dusted</title>"));
+        let done = "<span class=\"todo\">DONE</span>";
+        assert!(page.contains(&format!("{} Design homepage layout</h1>", done)));
+        assert!(page.contains(&format!("{} Organize team-building event</h1>", done)));
+        assert!(page.ends_with("</body>\n</html>\n"));
+    }
+}
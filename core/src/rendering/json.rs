@@ -0,0 +1,182 @@
+use serde_json::{json, Value};
+
+use crate::{Block, ListKind};
+
+/// Converts a block and its children into the JSON shape documented on
+/// `render_to_json`
+fn to_json_value(block: Block) -> Value {
+    let children = |children: Vec<Block>| -> Value {
+        Value::Array(children.into_iter().map(to_json_value).collect())
+    };
+
+    match block {
+        Block::Category(category_block) => json!({
+            "type": "category",
+            "id": category_block.id,
+            "name": category_block.name.trim_end_matches(['\r', '\n']),
+            "level": category_block.level,
+            "children": children(category_block.children),
+        }),
+        Block::RichText(rich_text_block) => json!({
+            "type": "rich_text",
+            "children": children(rich_text_block.children),
+        }),
+        Block::Text(text_block) => json!({
+            "type": "text",
+            "id": text_block.id,
+            "content": text_block.content,
+            "markup": text_block.markup.map(|markup| markup.as_str()),
+        }),
+        Block::Property(property_block) => json!({
+            "type": "property",
+            "id": property_block.id,
+            "key": property_block.key,
+            "value": property_block.value,
+        }),
+        Block::Drawer(drawer_block) => json!({
+            "type": "drawer",
+            "id": drawer_block.id,
+            "children": children(
+                drawer_block.properties.into_iter().map(Block::Property).collect()
+            ),
+        }),
+        Block::List(list_block) => json!({
+            "type": "list",
+            "id": list_block.id,
+            "ordered": list_block.kind == ListKind::Ordered,
+            "children": children(list_block.children),
+        }),
+        Block::ListItem(list_item_block) => json!({
+            "type": "list_item",
+            "id": list_item_block.id,
+            "bullet": list_item_block.bullet,
+            "checkbox": list_item_block.checkbox,
+            "content": list_item_block.content,
+            "children": children(list_item_block.children),
+        }),
+        Block::Table(table_block) => json!({
+            "type": "table",
+            "id": table_block.id,
+            "rows": table_block
+                .rows
+                .iter()
+                .map(|row| json!({
+                    "id": row.id,
+                    "rule": row.rule,
+                    "cells": row
+                        .cells
+                        .iter()
+                        .map(|cell| json!({"id": cell.id, "content": cell.content}))
+                        .collect::<Vec<Value>>(),
+                }))
+                .collect::<Vec<Value>>(),
+        }),
+        Block::Verbatim(verbatim_block) => json!({
+            "type": "verbatim",
+            "id": verbatim_block.id,
+            "kind": verbatim_block.kind(),
+            "language": verbatim_block.language(),
+            "parameters": verbatim_block.parameters(),
+            "content": verbatim_block.content,
+        }),
+        Block::Keyword(keyword_block) => json!({
+            "type": "keyword",
+            "id": keyword_block.id,
+            "key": keyword_block.key,
+            "value": keyword_block.value,
+        }),
+    }
+}
+
+/// Renders a block as a JSON object, which takes one of these shapes
+/// depending on the kind of block:
+///
+/// ```json
+/// {"type": "category", "id": 1, "name": "Project", "level": 1, "children": [...]}
+/// {"type": "rich_text", "children": [...]}
+/// {"type": "text", "id": 2, "content": "*Some notes*", "markup": "bold"}
+/// {"type": "property", "id": 3, "key": "Due", "value": "<2023-10-14>"}
+/// {"type": "drawer", "id": 4, "children": [...]}
+/// {"type": "list", "id": 5, "ordered": false, "children": [...]}
+/// {"type": "list_item", "id": 6, "bullet": "- ", "checkbox": "[ ]", "content": "Milk\n",
+///  "children": [...]}
+/// {"type": "table", "id": 7, "rows": [{"id": 8, "rule": false, "cells": [...]}]}
+/// {"id": 9, "content": "Milk"}
+/// {"type": "verbatim", "id": 10, "kind": "SRC", "language": "rust", "parameters": ":results none",
+///  "content": "fn main() {}\n"}
+/// {"type": "keyword", "id": 11, "key": "TITLE", "value": "Notes"}
+/// ```
+///
+/// Ids are null for blocks that have not been stored. Category names have
+/// their trailing line break removed, while text content is kept as
+/// written. The children of rich text are the spans of its lines, whose
+/// markup is one of `bold`, `italic`, `underline`, `verbatim`, `code`,
+/// `strike_through` or `link`, or null for plain text. The children of a
+/// drawer are the properties inside it. The checkbox of a list item is null
+/// when it has none, and its children are the lists nested beneath it.
+/// Cells of a table row take the last shape, and rules separating rows have
+/// no cells. The language of a verbatim block is null unless it is a source
+/// block naming one. Keys are always emitted in alphabetical order.
+pub fn render_to_json(block: Block) -> String {
+    to_json_value(block).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::Parser;
+
+    use super::render_to_json;
+
+    #[test]
+    fn render_blocks_as_json() {
+        let document = "* Project
+:PROPERTIES:
+:ID: d775182b
+:END:
+Some notes
+";
+        let mut blocks = Parser::new(document).parse();
+
+        let rendered: Value =
+            serde_json::from_str(&render_to_json(blocks.remove(0))).expect("Should be valid JSON");
+
+        assert_eq!(
+            rendered,
+            json!({
+                "type": "category",
+                "id": null,
+                "name": "Project",
+                "level": 1,
+                "children": [
+                    {
+                        "type": "drawer",
+                        "id": null,
+                        "children": [
+                            {"type": "property", "id": null, "key": "ID", "value": "d775182b"}
+                        ]
+                    },
+                    {
+                        "type": "rich_text",
+                        "children": [
+                            {"type": "text", "id": null, "content": "Some notes\n", "markup": null}
+                        ]
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn json_keys_are_stable() {
+        let blocks = Parser::new(":Due: <2023-10-14>\n").parse();
+
+        assert_eq!(
+            blocks.into_iter().map(render_to_json).collect::<Vec<String>>(),
+            vec![
+                r#"{"id":null,"key":"Due","type":"property","value":"<2023-10-14>"}"#
+            ]
+        );
+    }
+}
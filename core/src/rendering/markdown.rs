@@ -0,0 +1,274 @@
+use crate::{Block, Checkbox, Markup, TableBlock, TableRow, TextBlock};
+
+use super::{align_row, column_widths, link_href};
+
+/// The deepest heading Markdown supports. Deeper categories are rendered at
+/// this level.
+const MAX_HEADING_LEVEL: usize = 6;
+
+/// Renders a span of text, translating its emphasis and links
+fn render_span(text_block: &TextBlock) -> String {
+    let text = text_block.text();
+
+    match text_block.markup {
+        None => text.to_string(),
+        Some(Markup::Bold) => format!("**{}**", text),
+        Some(Markup::Italic) => format!("*{}*", text),
+        Some(Markup::Underline) => format!("<u>{}</u>", text),
+        Some(Markup::Verbatim) | Some(Markup::Code) => format!("`{}`", text),
+        Some(Markup::StrikeThrough) => format!("~~{}~~", text),
+        Some(Markup::Link) => match text_block.link() {
+            Some(link) => format!("[{}]({})", text, link_href(&link.target)),
+            None => text.to_string(),
+        },
+    }
+}
+
+/// Renders a table with its columns aligned and a single delimiter row
+/// below the header. Markdown tables must have a header, so a table
+/// without one is headed by its first row, and its other rules are left
+/// out.
+fn render_table(table: &TableBlock) -> String {
+    let widths = column_widths(table);
+    let rows: Vec<&TableRow> = match table.header() {
+        Some(header) => std::iter::once(header).chain(table.body()).collect(),
+        None => table.rows.iter().filter(|row| !row.rule).collect(),
+    };
+
+    let Some((header, body)) = rows.split_first() else {
+        return String::new();
+    };
+    let delimiter = TableRow {
+        id: None,
+        indent: header.indent,
+        rule: true,
+        cells: Vec::new(),
+    };
+
+    let mut out = align_row(header, &widths, "|");
+    out += &align_row(&delimiter, &widths, "|");
+    for row in body {
+        out += &align_row(row, &widths, "|");
+    }
+
+    out
+}
+
+/// Renders a block as Markdown. Categories become `#` headings at their
+/// level, and properties are rendered as a list of `**Key:** value` items
+/// ended by a blank line. Emphasis, code and links in text are translated,
+/// with underlines written as `<u>` and links to headlines pointing at
+/// their anchors. Lists keep their bullets, with checked
+/// checkboxes written as `[x]`, and tables are aligned with a `|---|---|`
+/// rule below their header. Verbatim blocks become fenced code blocks tagged with
+/// their language, and keywords are left out. As with org, a category at
+/// level 0 is a document root whose name is not rendered.
+pub fn render_to_markdown(block: Block) -> String {
+    let mut out: String = "".to_string();
+
+    match block {
+        Block::Category(category_block) => {
+            if category_block.level > 0 {
+                out += &"#".repeat(category_block.level.min(MAX_HEADING_LEVEL));
+                out += " ";
+                out += category_block.name;
+            }
+
+            // A blank line ends the list of properties, so the block after
+            // it is not read as a continuation of its last item
+            let mut after_property = false;
+
+            for block in category_block.children {
+                let is_property = match &block {
+                    Block::Property(_) => true,
+                    Block::Drawer(drawer_block) => !drawer_block.properties.is_empty(),
+                    _ => false,
+                };
+
+                if after_property && !is_property {
+                    out += "\n";
+                }
+
+                after_property = is_property;
+                out += &render_to_markdown(block);
+            }
+        }
+        Block::RichText(rich_text_block) => {
+            for block in rich_text_block.children {
+                out += &render_to_markdown(block);
+            }
+        }
+        Block::Text(text_block) => {
+            out += &render_span(&text_block);
+        }
+        Block::Property(property_block) => {
+            out += "- **";
+            out += property_block.key;
+            out += ":**";
+            if !property_block.value.is_empty() {
+                out += " ";
+                out += property_block.value;
+            }
+            out += "\n";
+        }
+        Block::Drawer(drawer_block) => {
+            for property_block in drawer_block.properties {
+                out += &render_to_markdown(Block::Property(property_block));
+            }
+        }
+        Block::List(list_block) => {
+            for block in list_block.children {
+                out += &render_to_markdown(block);
+            }
+        }
+        Block::ListItem(list_item_block) => {
+            out += list_item_block.bullet;
+            match list_item_block.checkbox_state() {
+                Some(Checkbox::Checked) => out += "[x] ",
+                Some(checkbox) => {
+                    out += checkbox.as_str();
+                    out += " ";
+                }
+                None => (),
+            }
+            out += list_item_block.content;
+
+            for block in list_item_block.children {
+                out += &render_to_markdown(block);
+            }
+        }
+        Block::Table(table_block) => {
+            out += &render_table(&table_block);
+        }
+        Block::Verbatim(verbatim_block) => {
+            out += "```";
+            out += verbatim_block.language().unwrap_or_default();
+            out += "\n";
+            out += verbatim_block.content;
+            out += "```\n";
+        }
+        Block::Keyword(_) => (),
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+
+    use crate::{Block, CategoryBlock, MarkdownParser, Parser};
+
+    use super::render_to_markdown;
+
+    fn render_document(children: Vec<Block>) -> String {
+        render_to_markdown(Block::Category(CategoryBlock::new("", 0, children)))
+    }
+
+    #[test]
+    fn render_org_as_markdown() {
+        let document = "* Project
+:PROPERTIES:
+:ID: d775182b
+:END:
+:Owner: Stacy
+Some notes
+- a list item
+** Tasks
+******* Too deep
+";
+
+        assert_eq!(
+            render_document(Parser::new(document).parse()),
+            "# Project
+- **ID:** d775182b
+- **Owner:** Stacy
+
+Some notes
+- a list item
+## Tasks
+###### Too deep
+"
+        );
+    }
+
+    #[test]
+    fn render_inline_markup_as_markdown() {
+        let document = "*Bold* /italic/ _under_ +gone+ =v1= ~cargo~ \
+[[https://orgmode.org][Org]] [[*Tasks]]\n";
+
+        assert_eq!(
+            render_document(Parser::new(document).parse()),
+            "**Bold** *italic* <u>under</u> ~~gone~~ `v1` `cargo` [Org](https://orgmode.org) \
+[*Tasks](#tasks)\n"
+        );
+    }
+
+    #[test]
+    fn render_verbatim_blocks_as_fences() {
+        let document = "#+TITLE: Snippets\n#+begin_src python :session\nprint(1)\n#+end_src\n";
+
+        assert_eq!(
+            render_document(Parser::new(document).parse()),
+            "```python\nprint(1)\n```\n"
+        );
+    }
+
+    #[test]
+    fn render_tables_as_markdown() {
+        let document = "| Task | Owner |\n|------+-------|\n| Copy | Sam |\n";
+
+        assert_eq!(
+            render_document(Parser::new(document).parse()),
+            "| Task | Owner |\n|------|-------|\n| Copy | Sam   |\n"
+        );
+    }
+
+    #[test]
+    fn render_tables_with_one_delimiter_row() {
+        let document = "| Task | Owner |
+|------+-------|
+| Copy | Sam |
+|------+-------|
+| Edit | Kim |
+|------+-------|
+";
+
+        assert_eq!(
+            render_document(Parser::new(document).parse()),
+            "| Task | Owner |\n|------|-------|\n| Copy | Sam   |\n| Edit | Kim   |\n"
+        );
+
+        let document = "|------+-------|\n| Copy | Sam |\n| Edit | Kim |\n";
+
+        assert_eq!(
+            render_document(Parser::new(document).parse()),
+            "| Copy | Sam |\n|------|-----|\n| Edit | Kim |\n"
+        );
+    }
+
+    #[test]
+    fn render_markdown_round_trip() {
+        let document = "Preamble
+
+# Project
+Some *notes*
+spanning two lines
+
+## Tasks
+- first item
+  continues here
+1. ordered
+
+```sh
+# not a heading
+```
+";
+
+        let bump = Bump::new();
+        assert_eq!(
+            render_document(MarkdownParser::new(&bump, document).parse()),
+            document
+        );
+    }
+}
@@ -1,4 +1,87 @@
-use crate::Block;
+mod html;
+mod json;
+mod markdown;
+
+pub use html::{render_to_html, render_to_html_page};
+pub use json::render_to_json;
+pub use markdown::render_to_markdown;
+
+use crate::{Block, LinkTarget, TableBlock, TableRow};
+
+/// Turns a headline title into an anchor: lowercased, with whitespace
+/// replaced by `-` and other punctuation dropped, as GitHub does for
+/// Markdown headings
+fn slug(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The URL a link points at in an exported document. Headlines become
+/// anchors made from their title, ids anchors made from the id itself, and
+/// files their path, followed by the anchor of the headline searched for.
+fn link_href(target: &LinkTarget) -> String {
+    match target {
+        LinkTarget::Heading(title) => format!("#{}", slug(title)),
+        LinkTarget::Id(id) => format!("#{}", id.trim()),
+        LinkTarget::File { path, search: None } => path.to_string(),
+        LinkTarget::File { path, search: Some(search) } => format!("{}#{}", path, slug(search)),
+        LinkTarget::Url(url) => url.to_string(),
+    }
+}
+
+/// The width of each column of a table, which is that of its widest cell
+fn column_widths(table: &TableBlock) -> Vec<usize> {
+    let mut widths: Vec<usize> = vec![0; table.columns()];
+
+    for row in &table.rows {
+        for (column, cell) in row.cells.iter().enumerate() {
+            widths[column] = widths[column].max(cell.content.chars().count());
+        }
+    }
+
+    widths
+}
+
+/// Renders a table row with each column padded to `widths`, drawing a rule
+/// with `-` and `joint` where it crosses columns. The row keeps its
+/// indentation.
+fn align_row(row: &TableRow, widths: &[usize], joint: &str) -> String {
+    let mut out = row.indent.to_string();
+    out += "|";
+
+    if row.rule {
+        let dashes: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+        out += &dashes.join(joint);
+        out += "|";
+    } else {
+        for (column, width) in widths.iter().enumerate() {
+            let content = row.cells.get(column).map_or("", |cell| cell.content);
+            out += " ";
+            out += content;
+            out += &" ".repeat(width - content.chars().count());
+            out += " |";
+        }
+    }
+
+    out += "\n";
+    out
+}
+
+/// Renders a table with each column padded to the width of its widest
+/// cell, drawing rules with `-` and `joint` where they cross columns
+fn align_table(table: &TableBlock, joint: &str) -> String {
+    let widths = column_widths(table);
+
+    table.rows.iter().map(|row| align_row(row, &widths, joint)).collect()
+}
 
 pub fn render_to_org(block: Block) -> String {
     let mut out: String = "".to_string();
@@ -7,23 +90,83 @@ pub fn render_to_org(block: Block) -> String {
         Block::Category(category_block) => {
             if category_block.level > 0 {
                 out += &"*".repeat(category_block.level);
-                out += &" ";
+                out += " ";
                 out += category_block.name;
             }
 
             for block in category_block.children {
-                out += &format!("{}", render_to_org(block));
+                out += &render_to_org(block);
             }
         },
         Block::RichText(rich_text_block) => {
             for block in rich_text_block.children {
-                out += &format!("{}", render_to_org(block));
+                out += &render_to_org(block);
             }
         },
         Block::Text(text_block) => {
             out += text_block.content;
         }
+        Block::Property(property_block) => {
+            out += property_block.indent;
+            out += ":";
+            out += property_block.key;
+            out += ":";
+            out += property_block.separator;
+            out += property_block.value;
+            out += property_block.trailing;
+            out += "\n";
+        }
+        Block::Drawer(drawer_block) => {
+            out += drawer_block.begin;
+            out += "\n";
+            for property_block in drawer_block.properties {
+                out += &render_to_org(Block::Property(property_block));
+            }
+            if let Some(end) = drawer_block.end {
+                out += end;
+                out += "\n";
+            }
+        }
+        Block::List(list_block) => {
+            for block in list_block.children {
+                out += &render_to_org(block);
+            }
+        }
+        Block::ListItem(list_item_block) => {
+            out += list_item_block.bullet;
+            if let Some(checkbox) = list_item_block.checkbox {
+                out += checkbox;
+                out += " ";
+            }
+            out += list_item_block.content;
+
+            for block in list_item_block.children {
+                out += &render_to_org(block);
+            }
+        }
+        Block::Table(table_block) => {
+            out += &align_table(&table_block, "+");
+        }
+        Block::Verbatim(verbatim_block) => {
+            out += verbatim_block.begin;
+            out += "\n";
+            out += verbatim_block.content;
+            out += verbatim_block.end;
+            if verbatim_block.end_line_break {
+                out += "\n";
+            }
+        }
+        Block::Keyword(keyword_block) => {
+            out += keyword_block.indent;
+            out += "#+";
+            out += keyword_block.key;
+            out += ":";
+            out += keyword_block.separator;
+            out += keyword_block.value;
+            out += keyword_block.trailing;
+            out += "\n";
+        }
     }
 
-    return out;
+    out
 }
@@ -0,0 +1,28 @@
+-- A database as written by Amble before schema versioning was introduced,
+-- where the text of a category was written after its subcategory
+CREATE TABLE category_blocks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT,
+    parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE
+);
+CREATE TABLE rich_text_blocks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE
+);
+CREATE TABLE text_blocks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    content TEXT,
+    parent_category_id INTEGER NULL REFERENCES category_blocks(id) ON DELETE CASCADE,
+    parent_rich_text_block_id INTEGER NULL REFERENCES rich_text_blocks(id) ON DELETE CASCADE
+);
+
+INSERT INTO category_blocks (id, name, parent_category_id) VALUES (1, 'Notes', NULL);
+INSERT INTO category_blocks (id, name, parent_category_id) VALUES (2, 'Parent
+', 1);
+INSERT INTO category_blocks (id, name, parent_category_id) VALUES (3, 'Child
+', 2);
+INSERT INTO rich_text_blocks (id, parent_category_id) VALUES (50, 2);
+INSERT INTO text_blocks (id, content, parent_category_id, parent_rich_text_block_id) VALUES (51, 'second line of parent
+', NULL, 50);
+INSERT INTO text_blocks (id, content, parent_category_id, parent_rich_text_block_id) VALUES (50, 'body of parent
+', NULL, 50);
@@ -1,13 +1,28 @@
 local ffi = require("ffi")
 
+-- Mirrors core/include/amble.h, which documents who owns what. Every string
+-- and array handed out by amble is copied into lua and then freed right away.
 ffi.cdef [[
+typedef enum {
+	AMBLE_STATUS_OK = 0,
+	AMBLE_STATUS_ERROR = 1,
+	AMBLE_STATUS_PANIC = 2,
+} AmbleStatus;
+
+const char* amble_last_error();
+
+typedef struct AmbleHandle AmbleHandle;
+
+AmbleStatus amble_open(const char* path, AmbleHandle** out);
+void amble_close(AmbleHandle* handle);
+
 typedef struct {
 	int64_t id;
 	const char* name;
 	const char* content;
 } TopLevelCategory;
 
-TopLevelCategory write_category(TopLevelCategory* category);
+AmbleStatus write_category(AmbleHandle* handle, TopLevelCategory* category, TopLevelCategory* out);
 
 typedef struct {
 	int64_t id;
@@ -16,17 +31,90 @@ typedef struct {
 
 typedef struct {
 	const TopLevelCategoryResult* categories;
-	int64_t length;
+	size_t length;
 } TopLevelCategoryResults;
 
-TopLevelCategoryResults get_top_level_categories();
-char* get_category_content(int64_t id);
+AmbleStatus get_top_level_categories(AmbleHandle* handle, TopLevelCategoryResults* out);
+AmbleStatus get_category_content(AmbleHandle* handle, int64_t id, char** out);
+
+typedef struct {
+	int64_t category_id;
+	const char* category_name;
+	const char* path;
+	const char* snippet;
+	double rank;
+} SearchResult;
+
+typedef struct {
+	const SearchResult* results;
+	size_t length;
+} SearchResults;
+
+AmbleStatus amble_search(AmbleHandle* handle, const char* query, SearchResults* out);
+
+typedef struct {
+	int64_t category_id;
+	const char* category_name;
+	const char* path;
+	const char* headline;
+	const char* label;
+	const char* timestamp;
+	const char* date;
+	bool overdue;
+} AgendaResult;
+
+typedef struct {
+	const AgendaResult* results;
+	size_t length;
+} AgendaResults;
+
+AmbleStatus amble_agenda(AmbleHandle* handle, const char* start, const char* end, AgendaResults* out);
+
+typedef struct {
+	int64_t category_id;
+	const char* category_name;
+	const char* path;
+	const char* content;
+	int64_t target_category_id;
+} BacklinkResult;
+
+typedef struct {
+	const BacklinkResult* results;
+	size_t length;
+} BacklinkResults;
+
+AmbleStatus amble_backlinks(AmbleHandle* handle, int64_t id, BacklinkResults* out);
+
+void amble_free_string(char* string);
+void amble_free_category(TopLevelCategory* category);
+void amble_free_category_results(TopLevelCategoryResults* results);
+void amble_free_search_results(SearchResults* results);
+void amble_free_agenda_results(AgendaResults* results);
+void amble_free_backlink_results(BacklinkResults* results);
 ]]
 
 local amble_ffi = {}
 
-function amble_ffi.init(libamble_path)
+-- Raises a lua error describing the failure if a call did not succeed
+local function check(status)
+	if status ~= ffi.C.AMBLE_STATUS_OK then
+		local message = amble_ffi.interface.amble_last_error()
+
+		if message == nil then
+			error("amble call failed without an error message")
+		end
+
+		error(ffi.string(message))
+	end
+end
+
+function amble_ffi.init(libamble_path, db_path)
 	amble_ffi.interface = ffi.load(libamble_path)
+
+	local handle = ffi.new("AmbleHandle*[1]")
+	check(amble_ffi.interface.amble_open(db_path, handle))
+
+	amble_ffi.handle = ffi.gc(handle[0], amble_ffi.interface.amble_close)
 end
 
 function amble_ffi.write_category(id, name, content)
@@ -39,17 +127,23 @@ function amble_ffi.write_category(id, name, content)
 		}
 	)
 
-	local top_level_category = amble_ffi.interface.write_category(category)
+	local top_level_category = ffi.new("TopLevelCategory")
+	check(amble_ffi.interface.write_category(amble_ffi.handle, category, top_level_category))
 
-	return {
+	local written = {
 		id = tonumber(top_level_category.id),
 		content = ffi.string(top_level_category.content),
 		name = ffi.string(top_level_category.name)
 	}
+
+	amble_ffi.interface.amble_free_category(top_level_category)
+
+	return written
 end
 
 function amble_ffi.get_top_level_categories()
-	local results = amble_ffi.interface.get_top_level_categories();
+	local results = ffi.new("TopLevelCategoryResults")
+	check(amble_ffi.interface.get_top_level_categories(amble_ffi.handle, results))
 	local categories = {}
 
 	for i = 0, tonumber(results.length) - 1 do
@@ -59,12 +153,89 @@ function amble_ffi.get_top_level_categories()
 		}
 	end
 
+	amble_ffi.interface.amble_free_category_results(results)
+
 	return categories
 end
 
 function amble_ffi.get_category_content(id)
-	local content_ptr = amble_ffi.interface.get_category_content(id)
-	return ffi.string(content_ptr)
+	local content_ptr = ffi.new("char*[1]")
+	check(amble_ffi.interface.get_category_content(amble_ffi.handle, id, content_ptr))
+
+	local content = ffi.string(content_ptr[0])
+	amble_ffi.interface.amble_free_string(content_ptr[0])
+
+	return content
+end
+
+function amble_ffi.search(query)
+	local results = ffi.new("SearchResults")
+	check(amble_ffi.interface.amble_search(amble_ffi.handle, query, results))
+	local hits = {}
+
+	for i = 0, tonumber(results.length) - 1 do
+		local hit = results.results[i]
+
+		hits[i+1] = {
+			id = tonumber(hit.category_id),
+			name = ffi.string(hit.category_name),
+			path = ffi.string(hit.path),
+			snippet = ffi.string(hit.snippet),
+		}
+	end
+
+	amble_ffi.interface.amble_free_search_results(results)
+
+	return hits
+end
+
+-- start and end are YYYY-MM-DD dates, both included
+function amble_ffi.agenda(start, finish)
+	local results = ffi.new("AgendaResults")
+	check(amble_ffi.interface.amble_agenda(amble_ffi.handle, start, finish, results))
+	local items = {}
+
+	for i = 0, tonumber(results.length) - 1 do
+		local item = results.results[i]
+
+		items[i+1] = {
+			id = tonumber(item.category_id),
+			name = ffi.string(item.category_name),
+			path = ffi.string(item.path),
+			headline = ffi.string(item.headline),
+			label = ffi.string(item.label),
+			timestamp = ffi.string(item.timestamp),
+			date = ffi.string(item.date),
+			overdue = item.overdue,
+		}
+	end
+
+	amble_ffi.interface.amble_free_agenda_results(results)
+
+	return items
+end
+
+-- Lists the notes linking to the category with `id` or a headline in it
+function amble_ffi.backlinks(id)
+	local results = ffi.new("BacklinkResults")
+	check(amble_ffi.interface.amble_backlinks(amble_ffi.handle, id, results))
+	local backlinks = {}
+
+	for i = 0, tonumber(results.length) - 1 do
+		local backlink = results.results[i]
+
+		backlinks[i+1] = {
+			id = tonumber(backlink.category_id),
+			name = ffi.string(backlink.category_name),
+			path = ffi.string(backlink.path),
+			content = ffi.string(backlink.content),
+			target_id = tonumber(backlink.target_category_id),
+		}
+	end
+
+	amble_ffi.interface.amble_free_backlink_results(results)
+
+	return backlinks
 end
 
 return amble_ffi
@@ -1,9 +1,14 @@
 local amble = {}
 
-function amble.init()
+function amble.init(opts)
+	opts = opts or {}
+
+	local lib_path = opts.lib_path or "/usr/local/lib/libamble.so"
+	local db_path = opts.db_path or (vim.fn.stdpath("data") .. "/amble.sqlite")
+
 	amble.ffi = require("amble.amble_ffi")
 
-	amble.ffi.init("/usr/local/lib/libamble.so")
+	amble.ffi.init(lib_path, db_path)
 end
 
 function amble.on_buf_write(category)
@@ -54,6 +59,7 @@ function amble.open_category(category)
 	end
 
 	vim.cmd('edit ' .. path)
+	vim.b.amble_category = category
 
 	vim.api.nvim_create_autocmd('BufWritePost', {
 		pattern = path,
@@ -79,6 +85,32 @@ function amble.get_categories()
 	--return choices
 end
 
+function amble.search(query)
+	return amble.ffi.search(query)
+end
+
+-- Lists dated headlines for the next `days` days, seven by default,
+-- along with anything overdue
+function amble.agenda(days)
+	local today = os.time()
+	local start = os.date("%Y-%m-%d", today)
+	local finish = os.date("%Y-%m-%d", today + ((days or 7) - 1) * 24 * 60 * 60)
+
+	return amble.ffi.agenda(start, finish)
+end
+
+-- Lists the notes that link to `category`, which defaults to the category
+-- open in the current buffer
+function amble.backlinks(category)
+	category = category or vim.b.amble_category
+
+	if category == nil then
+		error("The current buffer is not an amble category")
+	end
+
+	return amble.ffi.backlinks(category.id)
+end
+
 function amble.new_category()
 	local category_name = vim.fn.input("Category name: ")
 	local category_id = amble.ffi.write_category(-1, category_name, "").id
@@ -58,9 +58,153 @@ local amble_picker = function(opts)
 	}):find()
 end
 
+local amble_search = function(opts)
+	opts = opts or {}
+	pickers.new(opts, {
+		prompt_title = "Search: ",
+		attach_mappings = function(prompt_bufnr, map)
+			actions.select_default:replace(function()
+				actions.close(prompt_bufnr)
+				local selection = action_state.get_selected_entry()
+				amble.open_category(selection.value)
+			end)
+			return true
+		end,
+		-- Results are already ranked by amble, so they are not sorted again
+		sorter = require('telescope.sorters').empty(),
+		finder = finders.new_dynamic {
+			fn = function(prompt)
+				if prompt == nil or prompt == "" then
+					return {}
+				end
+
+				return amble.search(prompt)
+			end,
+			entry_maker = function(entry)
+				local location = entry.name
+				if entry.path ~= "" then
+					location = location .. " > " .. entry.path
+				end
+
+				return {
+					value = entry,
+					display = location .. ": " .. entry.snippet,
+					ordinal = location .. " " .. entry.snippet
+				}
+			end
+		},
+		previewer = previewers.new_buffer_previewer({
+			define_preview = function(self, entry, status)
+				vim.api.nvim_buf_set_lines(
+					self.state.bufnr, 0, -1, false,
+					vim.split(amble.get_category_content(entry.value.id), "\n")
+				)
+			end,
+			title = 'Amble Preview'
+		})
+	}):find()
+end
+
+local amble_agenda = function(opts)
+	opts = opts or {}
+	pickers.new(opts, {
+		prompt_title = "Agenda: ",
+		attach_mappings = function(prompt_bufnr, map)
+			actions.select_default:replace(function()
+				actions.close(prompt_bufnr)
+				local selection = action_state.get_selected_entry()
+				amble.open_category(selection.value)
+			end)
+			return true
+		end,
+		sorter = conf.generic_sorter(opts),
+		finder = finders.new_table {
+			results = amble.agenda(opts.days),
+			entry_maker = function(entry)
+				local displayer = entry_display.create({
+					separator = " ",
+					items = {
+						{ width = 10 },
+						{ width = 9 },
+						{ width = 8 },
+						{ remaining = true },
+					},
+				})
+
+				local make_display = function()
+					return displayer({
+						entry.date,
+						entry.label,
+						entry.overdue and { "OVERDUE", "ErrorMsg" } or "",
+						entry.headline .. " (" .. entry.name .. ")",
+					})
+				end
+
+				return {
+					value = entry,
+					display = make_display,
+					ordinal = entry.date .. " " .. entry.label .. " " .. entry.headline .. " " .. entry.name
+				}
+			end
+		},
+		previewer = previewers.new_buffer_previewer({
+			define_preview = function(self, entry, status)
+				vim.api.nvim_buf_set_lines(
+					self.state.bufnr, 0, -1, false,
+					vim.split(amble.get_category_content(entry.value.id), "\n")
+				)
+			end,
+			title = 'Amble Preview'
+		})
+	}):find()
+end
+
+local amble_backlinks = function(opts)
+	opts = opts or {}
+	pickers.new(opts, {
+		prompt_title = "Backlinks: ",
+		attach_mappings = function(prompt_bufnr, map)
+			actions.select_default:replace(function()
+				actions.close(prompt_bufnr)
+				local selection = action_state.get_selected_entry()
+				amble.open_category(selection.value)
+			end)
+			return true
+		end,
+		sorter = conf.generic_sorter(opts),
+		finder = finders.new_table {
+			results = amble.backlinks(opts.category),
+			entry_maker = function(entry)
+				local location = entry.name
+				if entry.path ~= "" then
+					location = location .. " > " .. entry.path
+				end
+
+				return {
+					value = entry,
+					display = location .. ": " .. entry.content,
+					ordinal = location .. " " .. entry.content
+				}
+			end
+		},
+		previewer = previewers.new_buffer_previewer({
+			define_preview = function(self, entry, status)
+				vim.api.nvim_buf_set_lines(
+					self.state.bufnr, 0, -1, false,
+					vim.split(amble.get_category_content(entry.value.id), "\n")
+				)
+			end,
+			title = 'Amble Preview'
+		})
+	}):find()
+end
+
 return require('telescope').register_extension {
 		setup = function() end,
 		exports = {
-				picker = amble_picker
+				picker = amble_picker,
+				search = amble_search,
+				agenda = amble_agenda,
+				backlinks = amble_backlinks
 		}
 }
@@ -1,7 +1,7 @@
 local amble = {}
 
 amble.ffi = require("amble_ffi")
-amble.ffi.init("../../core/target/release/libamble.so")
+amble.ffi.init("../../core/target/release/libamble.so", "amble.sqlite")
 
 print('writing greetings')
 amble.ffi.write_category(-1, "Greetings", [[